@@ -0,0 +1,90 @@
+use apiel::parse::val::{Scalar, Val};
+
+/// How piped stdin is turned into `⍵` for `-e` expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    /// Whitespace-separated numbers, as a numeric vector.
+    Numbers,
+    /// One character vector per line.
+    Lines,
+    /// Comma-separated fields as a matrix; numeric fields become numbers.
+    Csv,
+    /// Don't read stdin.
+    None,
+}
+
+impl std::str::FromStr for InputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "numbers" => Ok(InputMode::Numbers),
+            "lines" => Ok(InputMode::Lines),
+            "csv" => Ok(InputMode::Csv),
+            "none" => Ok(InputMode::None),
+            _ => Err(format!(
+                "unknown input mode '{s}', expected numbers, lines, csv or none"
+            )),
+        }
+    }
+}
+
+pub fn parse_input(text: &str, mode: InputMode) -> Result<Option<Val>, String> {
+    match mode {
+        InputMode::Numbers => {
+            let data = text
+                .split_whitespace()
+                .map(|word| parse_number(word).ok_or_else(|| format!("not a number: '{word}'")))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Some(Val::vector(data)))
+        }
+        InputMode::Lines => {
+            let data = text.lines().map(char_vector).collect();
+            Ok(Some(Val::vector(data)))
+        }
+        InputMode::Csv => {
+            let rows: Vec<Vec<Scalar>> = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    line.split(',')
+                        .map(|field| {
+                            parse_number(field.trim()).unwrap_or_else(|| char_vector(field))
+                        })
+                        .collect()
+                })
+                .collect();
+            let cols = rows.first().map_or(0, Vec::len);
+            if let Some(row) = rows.iter().position(|r| r.len() != cols) {
+                return Err(format!(
+                    "CSV row {} has {} fields, expected {cols}",
+                    row + 1,
+                    rows[row].len()
+                ));
+            }
+            let shape = vec![rows.len(), cols];
+            Ok(Some(Val::new(shape, rows.into_iter().flatten().collect())))
+        }
+        InputMode::None => Ok(None),
+    }
+}
+
+/// Parse an APL or conventional number (`¯3`, `-3`, `2.5`).
+fn parse_number(word: &str) -> Option<Scalar> {
+    let word = word.replace('¯', "-");
+    let numeric = |c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E');
+    if word.is_empty() || !word.chars().all(numeric) {
+        return None;
+    }
+    if let Ok(i) = word.parse::<i64>() {
+        Some(Scalar::Integer(i))
+    } else {
+        word.parse::<f64>().ok().map(Scalar::Float)
+    }
+}
+
+fn char_vector(text: &str) -> Scalar {
+    Scalar::Nested(Box::new(Val::vector(
+        text.chars().map(Scalar::Char).collect(),
+    )))
+}
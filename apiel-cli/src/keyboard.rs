@@ -0,0 +1,50 @@
+use apiel::glyphs::{GLYPHS, PREFIX, glyph_for_key};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rustyline::{
+    Cmd, ConditionalEventHandler, Event, EventContext, KeyCode, KeyEvent, Modifiers, RepeatCount,
+};
+
+/// Turns `` ` `` followed by a key into the matching APL glyph as you type.
+/// The prefix itself is held back until the next key arrives.
+#[derive(Default)]
+pub struct GlyphInput {
+    prefixed: AtomicBool,
+}
+
+impl ConditionalEventHandler for GlyphInput {
+    fn handle(&self, evt: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        let prefixed = self.prefixed.swap(false, Ordering::Relaxed);
+        let KeyEvent(KeyCode::Char(key), mods) = *evt.get(0)? else {
+            return None;
+        };
+        if mods.intersects(Modifiers::CTRL | Modifiers::ALT) {
+            return None;
+        }
+        if !prefixed {
+            if key != PREFIX {
+                return None;
+            }
+            self.prefixed.store(true, Ordering::Relaxed);
+            return Some(Cmd::Noop);
+        }
+        let text = match glyph_for_key(key) {
+            Some(glyph) => glyph.to_string(),
+            None => format!("{PREFIX}{key}"),
+        };
+        Some(Cmd::Insert(1, text))
+    }
+}
+
+/// The `]keyboard` listing: prefix key, glyph and name, four to a line.
+pub fn keyboard_table() -> String {
+    let cells: Vec<String> = GLYPHS
+        .iter()
+        .map(|g| format!("{PREFIX}{} {} {:<15}", g.key, g.glyph, g.name))
+        .collect();
+    cells
+        .chunks(4)
+        .map(|row| row.concat().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
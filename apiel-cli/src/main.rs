@@ -1,13 +1,190 @@
-use std::io::{self, BufRead, Write};
+mod helper;
+mod input;
+mod keyboard;
+
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 use apiel::Env;
-use apiel::parse::{eval_to_val, format_val};
+use apiel::commands::{CommandResult, is_command, run_command};
+use apiel::parse::{EvalError, eval_to_val, format_val, is_complete, run_script};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Config, Editor, Event, EventHandler};
+
+use helper::ReplHelper;
+use input::{InputMode, parse_input};
+use keyboard::{GlyphInput, keyboard_table};
+
+const USAGE: &str = "\
+Usage: apiel-cli [OPTIONS] [SCRIPT [ARGS...]]
+
+Without a script, starts the REPL, or runs stdin as a script when it is not a terminal.
+
+Options:
+  -e, --expr <EXPR>    Evaluate EXPR and print the result; remaining arguments become ⎕ARG
+  -i, --input <MODE>   With -e, read piped stdin into ⍵ as numbers (default), lines, csv or none
+  -h, --help           Print this help";
+
+#[derive(Debug, Default)]
+struct Options {
+    expr: Option<String>,
+    input: Option<InputMode>,
+    script: Option<String>,
+    args: Vec<String>,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-e" | "--expr" => {
+                options.expr = Some(args.next().ok_or("-e requires an expression")?);
+            }
+            "-i" | "--input" => {
+                let mode = args.next().ok_or("-i requires a mode")?;
+                options.input = Some(mode.parse()?);
+            }
+            "--" => {
+                options.args.extend(args.by_ref());
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option '{flag}'"));
+            }
+            _ if options.expr.is_none() && options.script.is_none() => {
+                options.script = Some(arg);
+                options.args.extend(args.by_ref());
+            }
+            _ => options.args.push(arg),
+        }
+    }
+    if options.input.is_some() && options.expr.is_none() {
+        return Err("-i can only be used with -e".to_string());
+    }
+    Ok(Some(options))
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("ERROR: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    if let Some(expr) = &options.expr {
+        run_expr(expr, &options)
+    } else if let Some(path) = &options.script {
+        run_file(path, &options.args)
+    } else if !io::stdin().is_terminal() {
+        run_stdin()
+    } else {
+        repl();
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_result(source: &str, result: Result<(), EvalError>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!(
+                "{source}:{}:{}: ERROR: {}",
+                err.line, err.column, err.message
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
 
-fn main() {
-    tracing_subscriber::fmt().init();
+/// Evaluate a one-shot expression, with piped stdin bound to `⍵`.
+fn run_expr(expr: &str, options: &Options) -> ExitCode {
+    let mut env = Env::new();
+    env.set_args(&options.args);
 
     let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        let mode = options.input.unwrap_or(InputMode::Numbers);
+        let mut text = String::new();
+        let omega = match stdin.lock().read_to_string(&mut text) {
+            Ok(_) => parse_input(&text, mode),
+            Err(err) => Err(err.to_string()),
+        };
+        match omega {
+            Ok(Some(val)) => {
+                env.set_var("⍵", val).expect("⍵ is not a dotted name");
+            }
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("ERROR: stdin: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let result = run_script(expr, &mut env, |val| println!("{}", format_val(val)));
+    print_result("-e", result)
+}
+
+/// Run stdin as a script: no banner and no prompt, for use in pipelines.
+fn run_stdin() -> ExitCode {
+    let mut source = String::new();
+    if let Err(err) = io::stdin().lock().read_to_string(&mut source) {
+        eprintln!("ERROR: stdin: {err}");
+        return ExitCode::FAILURE;
+    }
     let mut env = Env::new();
+    let result = run_script(&source, &mut env, |val| println!("{}", format_val(val)));
+    print_result("<stdin>", result)
+}
+
+/// Run a script file, exposing the remaining command-line arguments as `⎕ARG`.
+fn run_file(path: &str, script_args: &[String]) -> ExitCode {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("ERROR: cannot read {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut env = Env::new();
+    env.set_args(script_args);
+    let result = run_script(&source, &mut env, |val| println!("{}", format_val(val)));
+    print_result(path, result)
+}
+
+fn repl() {
+    let config = Config::builder()
+        .max_history_size(1000)
+        .and_then(|builder| builder.history_ignore_dups(true))
+        .map(|builder| {
+            builder
+                .auto_add_history(false)
+                .completion_type(CompletionType::List)
+                .build()
+        });
+    let mut editor = match config.and_then(Editor::<ReplHelper, DefaultHistory>::with_config) {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("ERROR: cannot start line editor: {err}");
+            return;
+        }
+    };
+    editor.bind_sequence(
+        Event::Any,
+        EventHandler::Conditional(Box::<GlyphInput>::default()),
+    );
+    editor.set_helper(Some(ReplHelper { env: Env::new() }));
 
     println!(
         r#"
@@ -25,24 +202,78 @@ fn main() {
 "#
     );
 
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session
+        editor.load_history(path).ok();
+    }
+
+    // Lines of a statement that still has an open bracket or quote
+    let mut pending = String::new();
     loop {
-        print!(">>> ");
-        io::stdout().flush().ok();
-        match stdin.lock().lines().next() {
-            Some(Ok(line)) => {
-                if line.trim().is_empty() {
+        let prompt = if pending.is_empty() { ">>> " } else { "... " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if pending.is_empty() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                } else {
+                    pending.push('\n');
+                }
+                pending.push_str(&line);
+                if !is_command(&pending) && !is_complete(&pending) {
+                    continue;
+                }
+                let input = std::mem::take(&mut pending);
+                editor.add_history_entry(input.as_str()).ok();
+                if input.trim() == "]keyboard" {
+                    println!("{}", keyboard_table());
+                    continue;
+                }
+                let Some(helper) = editor.helper_mut() else {
+                    break;
+                };
+                if is_command(&input) {
+                    match run_command(&input, &mut helper.env) {
+                        Ok(CommandResult::Output(text)) if text.is_empty() => {}
+                        Ok(CommandResult::Output(text)) => println!("{text}"),
+                        Ok(CommandResult::Off) => break,
+                        Err(err) => eprintln!("ERROR: {err}"),
+                    }
                     continue;
                 }
-                match eval_to_val(&line, &mut env) {
+                match eval_to_val(&input, &mut helper.env) {
                     Ok(val) => println!("{}", format_val(&val)),
                     Err(err) => eprintln!("ERROR: {err}"),
                 }
             }
-            _ => break,
+            // Ctrl-C discards the current input, Ctrl-D exits
+            Err(ReadlineError::Interrupted) => pending.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("ERROR: {err}");
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| editor.save_history(path).map_err(io::Error::other));
+        if let Err(err) = saved {
+            eprintln!("ERROR: cannot save history to {}: {err}", path.display());
         }
     }
 }
 
+/// History file in the user's data directory, e.g. `~/.local/share/apiel/history`.
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("apiel").join("history"))
+}
+
 #[cfg(test)]
 mod tests {
     use apiel::Env;
@@ -88,4 +319,89 @@ mod tests {
             "expected '5 7 9' in output, got: {stdout}"
         );
     }
+
+    fn write_script(name: &str, source: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("apiel-cli-{}-{name}", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn script_file_works() {
+        let script = write_script(
+            "ok.apl",
+            "#!/usr/bin/env apiel-cli\n⍝ sum the numbers\nn←⍳ 4\n+/ n\n\n⍴ ⎕ARG\n⊃ ⎕ARG\n",
+        );
+        let output = Command::new(assert_cmd::cargo::cargo_bin("apiel-cli"))
+            .arg(&script)
+            .args(["first", "second"])
+            .output()
+            .expect("failed to run apiel-cli");
+        std::fs::remove_file(&script).ok();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout, "10\n2\nfirst\n");
+    }
+
+    #[test]
+    fn script_error_reports_position() {
+        let script = write_script("err.apl", "x←1\nx + 2\n3 + nope\n4\n");
+        let output = Command::new(assert_cmd::cargo::cargo_bin("apiel-cli"))
+            .arg(&script)
+            .output()
+            .expect("failed to run apiel-cli");
+        std::fs::remove_file(&script).ok();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(":3:5: ERROR:"),
+            "expected line and column in error, got: {stderr}"
+        );
+    }
+
+    fn run_piped(args: &[&str], stdin: &str) -> std::process::Output {
+        use std::io::Write;
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin("apiel-cli"))
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("failed to run apiel-cli");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    #[test]
+    fn pipe_mode_has_no_banner_or_prompt() {
+        let output = run_piped(&[], "x←3\nx×2\n");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "6\n");
+    }
+
+    #[test]
+    fn expr_reads_stdin_into_omega() {
+        let output = run_piped(&["-e", "+/⍵"], "1 2 3\n4 ¯5\n");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n");
+
+        let output = run_piped(&["--expr", "⍴⍵", "--input", "lines"], "ab\ncde\n\n");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+
+        let output = run_piped(&["-e", "⍴⍵", "-i", "csv"], "a,1,2\nb,3,4\n");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "2 3\n");
+
+        let output = run_piped(&["-e", "1 2 3 ⍳ ⍵"], "3 x\n");
+        assert_eq!(output.status.code(), Some(1), "non-numeric input");
+
+        let output = run_piped(&["-e", "⎕ARG", "-i", "none", "a", "bc"], "");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "(a) (bc)\n");
+    }
 }
@@ -1,26 +1,29 @@
 use apiel::Env;
+use apiel::commands::{CommandResult, is_command, run_command};
 use apiel::parse::{eval_to_val, format_val};
-use std::cell::RefCell;
+use std::sync::{LazyLock, Mutex};
 use wasm_bindgen::prelude::*;
 
-thread_local! {
-    static ENV: RefCell<Env> = RefCell::new(Env::new());
-}
+static ENV: LazyLock<Mutex<Env>> = LazyLock::new(|| Mutex::new(Env::new()));
 
 #[wasm_bindgen]
 pub fn eval_apl(input: &str) -> String {
-    ENV.with(|env| {
-        let mut env = env.borrow_mut();
-        match eval_to_val(input, &mut env) {
-            Ok(val) => format_val(&val),
+    let mut env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    if is_command(input) {
+        return match run_command(input, &mut env) {
+            Ok(CommandResult::Output(text)) => text,
+            // There is no session to end in the browser
+            Ok(CommandResult::Off) => String::new(),
             Err(e) => format!("ERROR: {e}"),
-        }
-    })
+        };
+    }
+    match eval_to_val(input, &mut env) {
+        Ok(val) => format_val(&val),
+        Err(e) => format!("ERROR: {e}"),
+    }
 }
 
 #[wasm_bindgen]
 pub fn reset_env() {
-    ENV.with(|env| {
-        *env.borrow_mut() = Env::new();
-    });
+    *ENV.lock().unwrap_or_else(|e| e.into_inner()) = Env::new();
 }
@@ -9,6 +9,7 @@ fn main() {
         .rust_edition(lrlex::RustEdition::Rust2021)
         .lrpar_config(|ctp| {
             ctp.yacckind(YaccKind::Grmtools)
+                .recoverer(lrpar::RecoveryKind::None)
                 .rust_edition(lrpar::RustEdition::Rust2021)
                 .error_on_conflicts(false)
                 .grammar_in_src_dir("apiel.y")
@@ -1,1146 +1,1728 @@
-%start Expr
-%avoid_insert "INT"
-%expect-unused Unmatched "UNMATCHED"
-%expect 3
-%%
-Expr -> Result<Expr, ()>:
-    Term { $1 }
-    ;
-
-Term -> Result<Expr, ()>:
-      Factor '+' Term {
-        Ok(Expr::Add{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '-' Term {
-        Ok(Expr::Sub{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '×' Term {
-        Ok(Expr::Mul{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '÷' Term {
-        Ok(Expr::Div{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'EXP' Term {
-        Ok(Expr::Power{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '!' Term {
-        Ok(Expr::Binomial{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '?' Term {
-        Ok(Expr::Deal{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '|' Term {
-        Ok(Expr::Residue{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '⌈' Term {
-        Ok(Expr::Max{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '⌊' Term {
-        Ok(Expr::Min{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'LOG' Term {
-        Ok(Expr::Log{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'IOTA' Term {
-        Ok(Expr::IndexOf{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'IOTA_U' Term {
-        Ok(Expr::IntervalIndex{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'EQ' Term {
-        Ok(Expr::Equal{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'NEQ' Term {
-        Ok(Expr::NotEqual{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'LT' Term {
-        Ok(Expr::LessThan{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'GT' Term {
-        Ok(Expr::GreaterThan{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'LTE' Term {
-        Ok(Expr::LessEqual{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'GTE' Term {
-        Ok(Expr::GreaterEqual{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'RHO' Term {
-        Ok(Expr::Reshape{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor ',' Term {
-        Ok(Expr::Catenate{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'ROTATE' Term {
-        Ok(Expr::Rotate{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'CIRCLE' Term {
-        Ok(Expr::Circular{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'AND' Term {
-        Ok(Expr::And{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'OR' Term {
-        Ok(Expr::Or{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'NAND' Term {
-        Ok(Expr::Nand{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'NOR' Term {
-        Ok(Expr::Nor{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '/' Term {
-        Ok(Expr::Replicate{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor '\' Term {
-        Ok(Expr::Expand{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'TAKE' Term {
-        Ok(Expr::Take{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'DROP' Term {
-        Ok(Expr::Drop{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | 'NAME' 'MODASSIGN' Term {
-        let op_str = $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default();
-        let op = match op_str.trim_end_matches('←') {
-            "+" => Ok(Operator::Add),
-            "-" => Ok(Operator::Subtract),
-            "×" => Ok(Operator::Multiply),
-            "÷" => Ok(Operator::Divide),
-            "*" => Ok(Operator::Power),
-            "⍟" => Ok(Operator::Log),
-            "!" => Ok(Operator::Binomial),
-            "|" => Ok(Operator::Residue),
-            "⌈" => Ok(Operator::Max),
-            "⌊" => Ok(Operator::Min),
-            "∧" => Ok(Operator::And),
-            "∨" => Ok(Operator::Or),
-            "⍲" => Ok(Operator::Nand),
-            "⍱" => Ok(Operator::Nor),
-            _ => Err(()),
-        };
-        match op {
-            Ok(operator) => Ok(Expr::ModifiedAssign{
-                span: $span,
-                name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(),
-                operator,
-                rhs: Box::new($3?),
-            }),
-            Err(_) => Err(()),
-        }
-      }
-    | 'NAME' '[' Expr ']' 'ASSIGN' Term {
-        Ok(Expr::IndexedAssign{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), indices: Box::new($3?), rhs: Box::new($6?) })
-      }
-    | 'NAME' 'ASSIGN' Term {
-        Ok(Expr::Assign{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($3?) })
-      }
-    | 'NAME' 'ASSIGN' '{' DfnBody '}' {
-        Ok(Expr::AssignDfn{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), body: Box::new($4?) })
-      }
-    | Factor 'OUTERPRODUCT' Operator Term {
-        match $3 {
-            Ok(op) => Ok(Expr::OuterProduct{ span: $span, lhs: Box::new($1?), operator: op, rhs: Box::new($4?) }),
-            Err(_) => Err(())
-        }
-      }
-    | Factor '{' DfnBody '}' Term {
-        Ok(Expr::DyadicDfn{ span: $span, lhs: Box::new($1?), body: Box::new($3?), rhs: Box::new($5?) })
-      }
-    | '{' DfnBody '}' Term {
-        Ok(Expr::MonadicDfn{ span: $span, body: Box::new($2?), rhs: Box::new($4?) })
-      }
-    | '{' DfnBody '}' 'COMPOSE' '{' DfnBody '}' Term {
-        Ok(Expr::ComposeDfn{ span: $span, f: Box::new($2?), g: Box::new($6?), arg: Box::new($8?) })
-      }
-    | Factor '{' DfnBody '}' 'COMPOSE' '{' DfnBody '}' Term {
-        Ok(Expr::ComposeDyadicDfn{ span: $span, lhs: Box::new($1?), f: Box::new($3?), g: Box::new($7?), arg: Box::new($9?) })
-      }
-    | '{' DfnBody '}' 'OVER' '{' DfnBody '}' Term {
-        Ok(Expr::OverDfn{ span: $span, f: Box::new($2?), g: Box::new($6?), arg: Box::new($8?) })
-      }
-    | Factor '{' DfnBody '}' 'OVER' '{' DfnBody '}' Term {
-        Ok(Expr::OverDyadicDfn{ span: $span, lhs: Box::new($1?), f: Box::new($3?), g: Box::new($7?), arg: Box::new($9?) })
-      }
-    | '{' DfnBody '}' 'AT' Factor Term {
-        Ok(Expr::AtOp{ span: $span, body: Box::new($2?), indices: Box::new($5?), arg: Box::new($6?) })
-      }
-    | '{' DfnBody '}' 'POWOP' Factor Term {
-        Ok(Expr::PowerOp{ span: $span, body: Box::new($2?), count: Box::new($5?), arg: Box::new($6?) })
-      }
-    | '{' DfnBody '}' 'KEY' Term {
-        Ok(Expr::KeyOp{ span: $span, body: Box::new($2?), arg: Box::new($5?) })
-      }
-    | '{' DfnBody '}' 'RANK' Factor Term {
-        Ok(Expr::RankOp{ span: $span, body: Box::new($2?), rank: Box::new($5?), arg: Box::new($6?) })
-      }
-    | '{' DfnBody '}' '/' Term {
-        Ok(Expr::DfnReduce{ span: $span, body: Box::new($2?), term: Box::new($5?) })
-      }
-    | '{' DfnBody '}' 'REDUCEFIRST' Term {
-        Ok(Expr::DfnReduceFirst{ span: $span, body: Box::new($2?), term: Box::new($5?) })
-      }
-    | 'NAME' Factor {
-        Ok(Expr::NamedMonadic{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($2?) })
-      }
-    | Factor 'NAME' Factor {
-        Ok(Expr::NamedDyadic{ span: $span, lhs: Box::new($1?), name: $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($3?) })
-      }
-    | Factor 'UNIQUE' Term {
-        Ok(Expr::Union{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'INTERSECT' Term {
-        Ok(Expr::Intersection{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'TILDE' Term {
-        Ok(Expr::Without{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'PARTITION' Term {
-        Ok(Expr::Partition{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'INDEX' Term {
-        Ok(Expr::Index{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'MATINV' Term {
-        Ok(Expr::MatrixDivide{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'DECODE' Term {
-        Ok(Expr::Decode{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'ENCODE' Term {
-        Ok(Expr::Encode{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'LEFT' Term {
-        Ok(Expr::Left{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'RIGHT' Term {
-        Ok(Expr::Right{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'MATCH' Term {
-        Ok(Expr::Match{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'NOTMATCH' Term {
-        Ok(Expr::NotMatch{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'FIND' Term {
-        Ok(Expr::Find{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'MEMBERSHIP' Term {
-        Ok(Expr::Membership{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'ENCLOSE' Term {
-        Ok(Expr::PartitionedEnclose{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor 'TRANSPOSE' Term {
-        Ok(Expr::DyadicTranspose{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
-      }
-    | Factor Operator 'DOT' Operator Term {
-        match ($2, $4) {
-            (Ok(f), Ok(g)) => Ok(Expr::InnerProduct{ span: $span, lhs: Box::new($1?), f, g, rhs: Box::new($5?) }),
-            _ => Err(())
-        }
-      }
-    | Factor Operator 'EACH' Term {
-        match $2 {
-            Ok(op) => Ok(Expr::DyadicEach{ span: $span, lhs: Box::new($1?), operator: op, rhs: Box::new($4?) }),
-            Err(_) => Err(())
-        }
-      }
-    | Factor Operator 'COMMUTE' Term {
-        match $2 {
-            Ok(op) => Ok(Expr::Commute{ span: $span, lhs: Box::new($1?), operator: op, rhs: Box::new($4?) }),
-            Err(_) => Err(())
-        }
-      }
-    | MonadicFactor {
-        Ok($1?)
-      }
-    | Reduction { $1 }
-    | Factor { $1 }
-    ;
-
-MonadicFactor -> Result<Expr, ()>:
-      '+' Term {
-        Ok(Expr::Conjugate{ span: $span, arg: Box::new($2?) })
-      }
-    | '-' Term {
-        Ok(Expr::Negate{ span: $span, arg: Box::new($2?) })
-      }
-    | '×' Term {
-        Ok(Expr::Direction{ span: $span, arg: Box::new($2?) })
-      }
-    | '÷' Term {
-        Ok(Expr::Reciprocal{ span: $span, arg: Box::new($2?) })
-      }
-    | 'EXP' Term {
-        Ok(Expr::Exp{ span: $span, arg: Box::new($2?) })
-      }
-    | 'LOG' Term {
-        Ok(Expr::NaturalLog{ span: $span, arg: Box::new($2?) })
-      }
-    | 'CIRCLE' Term {
-        Ok(Expr::PiMultiple{ span: $span, arg: Box::new($2?) })
-      }
-    | '!' Term {
-        Ok(Expr::Factorial{ span: $span, arg: Box::new($2?) })
-      }
-    | '?' Term {
-        Ok(Expr::Roll{ span: $span, arg: Box::new($2?) })
-      }
-    | '|' Term {
-        Ok(Expr::Magnitude{ span: $span, arg: Box::new($2?) })
-      }
-    | '⌈' Term {
-        Ok(Expr::Ceil{ span: $span, arg: Box::new($2?) })
-      }
-    | '⌊' Term {
-        Ok(Expr::Floor{ span: $span, arg: Box::new($2?) })
-      }
-    | 'MAX' Term {
-        Ok(Expr::MonadicMax{ span: $span, arg: Box::new($2?) })
-      }
-    | 'MIN' Term {
-        Ok(Expr::MonadicMin{ span: $span, arg: Box::new($2?) })
-      }
-    | 'IOTA' Term {
-        Ok(Expr::GenIndex{ span: $span, arg: Box::new($2?) })
-      }
-    | 'IOTA_U' Term {
-        Ok(Expr::Where{ span: $span, arg: Box::new($2?) })
-      }
-    | 'RHO' Term {
-        Ok(Expr::Shape{ span: $span, arg: Box::new($2?) })
-      }
-    | ',' Term {
-        Ok(Expr::Ravel{ span: $span, arg: Box::new($2?) })
-      }
-    | 'ROTATE' Term {
-        Ok(Expr::Reverse{ span: $span, arg: Box::new($2?) })
-      }
-    | 'TRANSPOSE' Term {
-        Ok(Expr::Transpose{ span: $span, arg: Box::new($2?) })
-      }
-    | 'GRADEUP' Term {
-        Ok(Expr::GradeUp{ span: $span, arg: Box::new($2?) })
-      }
-    | 'GRADEDN' Term {
-        Ok(Expr::GradeDown{ span: $span, arg: Box::new($2?) })
-      }
-    | 'SELF' Term {
-        Ok(Expr::SelfCall{ span: $span, arg: Box::new($2?) })
-      }
-    | 'ENCLOSE' Term {
-        Ok(Expr::Enclose{ span: $span, arg: Box::new($2?) })
-      }
-    | 'FIRST' Term {
-        Ok(Expr::First{ span: $span, arg: Box::new($2?) })
-      }
-    | 'UNIQUE' Term {
-        Ok(Expr::Unique{ span: $span, arg: Box::new($2?) })
-      }
-    | 'TILDE' Term {
-        Ok(Expr::Not{ span: $span, arg: Box::new($2?) })
-      }
-    | 'MATINV' Term {
-        Ok(Expr::MatrixInverse{ span: $span, arg: Box::new($2?) })
-      }
-    | 'LEFT' Term {
-        Ok(Expr::LeftIdentity{ span: $span, arg: Box::new($2?) })
-      }
-    | 'RIGHT' Term {
-        Ok(Expr::RightIdentity{ span: $span, arg: Box::new($2?) })
-      }
-    | 'MATCH' Term {
-        Ok(Expr::Depth{ span: $span, arg: Box::new($2?) })
-      }
-    | 'NOTMATCH' Term {
-        Ok(Expr::Tally{ span: $span, arg: Box::new($2?) })
-      }
-    | 'TAKE' Term {
-        Ok(Expr::Mix{ span: $span, arg: Box::new($2?) })
-      }
-    | 'DROP' Term {
-        Ok(Expr::Split{ span: $span, arg: Box::new($2?) })
-      }
-    | 'RHO' 'EACH' Term {
-        Ok(Expr::MonadicEach{ span: $span, func: "shape".to_string(), arg: Box::new($3?) })
-      }
-    | 'ROTATE' 'EACH' Term {
-        Ok(Expr::MonadicEach{ span: $span, func: "reverse".to_string(), arg: Box::new($3?) })
-      }
-    | 'IOTA' 'EACH' Term {
-        Ok(Expr::MonadicEach{ span: $span, func: "iota".to_string(), arg: Box::new($3?) })
-      }
-    | Operator 'COMMUTE' Term {
-        match $1 {
-            Ok(op) => Ok(Expr::Selfie{ span: $span, operator: op, arg: Box::new($3?) }),
-            Err(_) => Err(())
-        }
-      }
-    ;
-
-DfnBody -> Result<Expr, ()>:
-      Expr 'DIAMOND' DfnBody {
-        Ok(Expr::DfnStatements{ span: $span, first: Box::new($1?), rest: Box::new($3?) })
-      }
-    | Expr ':' Expr 'DIAMOND' DfnBody {
-        Ok(Expr::DfnGuard{ span: $span, cond: Box::new($1?), result: Box::new($3?), rest: Box::new($5?) })
-      }
-    | Expr ':' Expr {
-        Ok(Expr::DfnGuard{ span: $span, cond: Box::new($1?), result: Box::new($3?), rest: Box::new(Expr::ScalarInteger { span: $span }) })
-      }
-    | Expr { $1 }
-    ;
-
-Factor -> Result<Expr, ()>:
-      '(' Expr ')' { $2 }
-    | Factor '[' Expr ']' {
-        Ok(Expr::IndexRead{ span: $span, array: Box::new($1?), indices: Box::new($3?) })
-      }
-
-    | 'VEC' {
-        let elements = match $1 {
-            Ok(_lexeme) => {
-                let full_str = $lexer.span_str($span);
-                let mut current_pos = 0;
-                let mut elements = Vec::new();
-
-                for value in full_str.split_whitespace() {
-                    let start = full_str[current_pos..].find(value).unwrap_or(0) + current_pos;
-                    let end = start + value.len();
-                    current_pos = end;
-
-                    elements.push(Expr::ScalarInteger { span: Span::new(start + $span.start(), end + $span.start()) });
-                }
-                elements
-            },
-            Err(_) => Vec::new(),
-        };
-        Ok(Expr::Vector { span: $span, elements })
-    }
-    | 'INT' {
-        match $1 {
-            Ok(_) => Ok(Expr::ScalarInteger { span: $span }),
-            Err(_) => Err(())
-        }
-    }
-    | 'FLOAT' {
-        match $1 {
-            Ok(_value) => Ok(Expr::ScalarFloat { span: $span }),
-            Err(_) => Err(())
-        }
-    }
-    | 'NAME' {
-        match $1 {
-            Ok(_) => Ok(Expr::Variable { span: $span, name: $lexer.span_str($span).to_string() }),
-            Err(_) => Err(())
-        }
-    }
-    | 'OMEGA' {
-        Ok(Expr::Omega { span: $span })
-    }
-    | 'ALPHA' {
-        Ok(Expr::Alpha { span: $span })
-    }
-    | StringArray { $1 }
-    | 'STRING' {
-        Ok(Expr::StringLiteral { span: $span })
-    }
-    ;
-
-    StringArray -> Result<Expr, ()>:
-    'STRING' 'STRING' {
-        Ok(Expr::StringArray { span: $span, elements: vec![
-            Expr::StringLiteral { span: $1.map(|l| l.span()).unwrap_or($span) },
-            Expr::StringLiteral { span: $2.map(|l| l.span()).unwrap_or($span) },
-        ]})
-    }
-    | StringArray 'STRING' {
-        match $1? {
-            Expr::StringArray { span: _, mut elements } => {
-                elements.push(Expr::StringLiteral { span: $2.map(|l| l.span()).unwrap_or($span) });
-                Ok(Expr::StringArray { span: $span, elements })
-            },
-            _ => Err(()),
-        }
-    }
-    ;
-
-    Reduction -> Result<Expr, ()>:
-    Operator '/' Term {
-        match $1 {
-            Ok(op) => Ok(Expr::Reduce{ span: $span, operator: op, term: Box::new($3?) }),
-            Err(_) => Err(())
-        }
-    }
-    | Operator '\' Term {
-        match $1 {
-            Ok(op) => Ok(Expr::Scan{ span: $span, operator: op, term: Box::new($3?) }),
-            Err(_) => Err(())
-        }
-    }
-    | Operator '/' 'EACH' Term {
-        match $1 {
-            Ok(op) => Ok(Expr::ReduceEach{ span: $span, operator: op, term: Box::new($4?) }),
-            Err(_) => Err(())
-        }
-    }
-    | Operator 'REDUCEFIRST' Term {
-        match $1 {
-            Ok(op) => Ok(Expr::ReduceFirst{ span: $span, operator: op, term: Box::new($3?) }),
-            Err(_) => Err(())
-        }
-    }
-    | Operator 'SCANFIRST' Term {
-        match $1 {
-            Ok(op) => Ok(Expr::ScanFirst{ span: $span, operator: op, term: Box::new($3?) }),
-            Err(_) => Err(())
-        }
-    }
-    ;
-
-    Operator -> Result<Operator, ()>:
-      '+' { Ok(Operator::Add) }
-    | '-' { Ok(Operator::Subtract) }
-    | '×' { Ok(Operator::Multiply) }
-    | '÷' { Ok(Operator::Divide) }
-    | 'EQ' { Ok(Operator::Equal) }
-    | 'NEQ' { Ok(Operator::NotEqual) }
-    | 'LT' { Ok(Operator::LessThan) }
-    | 'GT' { Ok(Operator::GreaterThan) }
-    | 'LTE' { Ok(Operator::LessEqual) }
-    | 'GTE' { Ok(Operator::GreaterEqual) }
-    | '⌈' { Ok(Operator::Max) }
-    | '⌊' { Ok(Operator::Min) }
-    | 'AND' { Ok(Operator::And) }
-    | 'OR' { Ok(Operator::Or) }
-    | 'NAND' { Ok(Operator::Nand) }
-    | 'NOR' { Ok(Operator::Nor) }
-    | 'EXP' { Ok(Operator::Power) }
-    | 'LOG' { Ok(Operator::Log) }
-    | '|' { Ok(Operator::Residue) }
-    | '!' { Ok(Operator::Binomial) }
-    ;
-
-
-Unmatched -> ():
-      "UNMATCHED" { }
-    ;
-%%
-
-use cfgrammar::Span;
-
-#[derive(Debug, Clone)]
-pub enum Expr {
-    // Dyadic
-    Add {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Sub {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Mul {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Div {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Power {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Log {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Min {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Max {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Binomial {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Deal {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Residue {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    IndexOf {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    IntervalIndex {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Equal {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    NotEqual {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    LessThan {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    GreaterThan {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    LessEqual {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    GreaterEqual {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-
-    Reshape {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Catenate {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Rotate {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    And {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Or {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Nand {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Nor {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Replicate {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Expand {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Circular {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Take {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Drop {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Assign {
-        span: Span,
-        name: String,
-        rhs: Box<Expr>,
-    },
-    MonadicDfn {
-        span: Span,
-        body: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    AtOp {
-        span: Span,
-        body: Box<Expr>,
-        indices: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    PowerOp {
-        span: Span,
-        body: Box<Expr>,
-        count: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    RankOp {
-        span: Span,
-        body: Box<Expr>,
-        rank: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    KeyOp {
-        span: Span,
-        body: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    DyadicDfn {
-        span: Span,
-        lhs: Box<Expr>,
-        body: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Variable {
-        span: Span,
-        name: String,
-    },
-    Omega {
-        span: Span,
-    },
-    Alpha {
-        span: Span,
-    },
-    SelfCall {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    DfnStatements {
-        span: Span,
-        first: Box<Expr>,
-        rest: Box<Expr>,
-    },
-    DfnGuard {
-        span: Span,
-        cond: Box<Expr>,
-        result: Box<Expr>,
-        rest: Box<Expr>,
-    },
-    AssignDfn {
-        span: Span,
-        name: String,
-        body: Box<Expr>,
-    },
-    ModifiedAssign {
-        span: Span,
-        name: String,
-        operator: Operator,
-        rhs: Box<Expr>,
-    },
-    IndexedAssign {
-        span: Span,
-        name: String,
-        indices: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    NamedMonadic {
-        span: Span,
-        name: String,
-        rhs: Box<Expr>,
-    },
-    NamedDyadic {
-        span: Span,
-        lhs: Box<Expr>,
-        name: String,
-        rhs: Box<Expr>,
-    },
-    Union {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Intersection {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Without {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Decode {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Encode {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    InnerProduct {
-        span: Span,
-        lhs: Box<Expr>,
-        f: Operator,
-        g: Operator,
-        rhs: Box<Expr>,
-    },
-    Enclose {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    First {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Partition {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    PartitionedEnclose {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    MonadicEach {
-        span: Span,
-        func: String,
-        arg: Box<Expr>,
-    },
-    DyadicEach {
-        span: Span,
-        lhs: Box<Expr>,
-        operator: Operator,
-        rhs: Box<Expr>,
-    },
-    Commute {
-        span: Span,
-        lhs: Box<Expr>,
-        operator: Operator,
-        rhs: Box<Expr>,
-    },
-    Selfie {
-        span: Span,
-        operator: Operator,
-        arg: Box<Expr>,
-    },
-    ReduceEach {
-        span: Span,
-        operator: Operator,
-        term: Box<Expr>,
-    },
-    Unique {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Not {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    MatrixInverse {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Index {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    MatrixDivide {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Left {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Right {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Match {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    NotMatch {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    Find {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    StringLiteral {
-        span: Span,
-    },
-    OuterProduct {
-        span: Span,
-        lhs: Box<Expr>,
-        operator: Operator,
-        rhs: Box<Expr>,
-    },
-    Scan {
-        span: Span,
-        operator: Operator,
-        term: Box<Expr>,
-    },
-    ComposeDfn {
-        span: Span,
-        f: Box<Expr>,
-        g: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    ComposeDyadicDfn {
-        span: Span,
-        lhs: Box<Expr>,
-        f: Box<Expr>,
-        g: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    OverDfn {
-        span: Span,
-        f: Box<Expr>,
-        g: Box<Expr>,
-        arg: Box<Expr>,
-    },
-    OverDyadicDfn {
-        span: Span,
-        lhs: Box<Expr>,
-        f: Box<Expr>,
-        g: Box<Expr>,
-        arg: Box<Expr>,
-    },
-
-    // Monadic
-
-    Shape {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Ravel {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Reverse {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Transpose {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    DyadicTranspose {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    GradeUp {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    GradeDown {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Exp {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    NaturalLog {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Conjugate {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Negate {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Direction {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Reciprocal {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    PiMultiple {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Factorial {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Roll {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Magnitude {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Ceil {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Floor {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    MonadicMax {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    MonadicMin {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    GenIndex {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Where {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    LeftIdentity {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    RightIdentity {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Depth {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Tally {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Mix {
-        span: Span,
-        arg: Box<Expr>,
-    },
-    Split {
-        span: Span,
-        arg: Box<Expr>,
-    },
-
-    Reduce {
-        span: Span,
-        operator: Operator,
-        term: Box<Expr>,
-    },
-    ReduceFirst {
-        span: Span,
-        operator: Operator,
-        term: Box<Expr>,
-    },
-    ScanFirst {
-        span: Span,
-        operator: Operator,
-        term: Box<Expr>,
-    },
-    Membership {
-        span: Span,
-        lhs: Box<Expr>,
-        rhs: Box<Expr>,
-    },
-    IndexRead {
-        span: Span,
-        array: Box<Expr>,
-        indices: Box<Expr>,
-    },
-    DfnReduce {
-        span: Span,
-        body: Box<Expr>,
-        term: Box<Expr>,
-    },
-    DfnReduceFirst {
-        span: Span,
-        body: Box<Expr>,
-        term: Box<Expr>,
-    },
-    StringArray {
-        span: Span,
-        elements: Vec<Expr>,
-    },
-
-    // Values
-
-    ScalarInteger {
-        span: Span,
-    },
-    ScalarFloat {
-        span: Span,
-    },
-    Vector {
-        span: Span,
-        elements: Vec<Expr>,
-    },
-}
-
-#[derive(Debug, Clone)]
-pub enum Operator {
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
-    Equal,
-    NotEqual,
-    LessThan,
-    GreaterThan,
-    LessEqual,
-    GreaterEqual,
-    Max,
-    Min,
-    And,
-    Or,
-    Nand,
-    Nor,
-    Power,
-    Log,
-    Residue,
-    Binomial,
-}
+%start Expr
+%avoid_insert "INT"
+%expect-unused Unmatched "UNMATCHED"
+%expect 3
+%%
+Expr -> Result<Expr, ()>:
+    Term { $1 }
+    | 'FNEXPR' Train {
+        Ok(Expr::Function{ span: $span, func: Box::new($2?) })
+      }
+    ;
+
+Term -> Result<Expr, ()>:
+      Factor '+' Term {
+        Ok(Expr::Add{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '-' Term {
+        Ok(Expr::Sub{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '×' Term {
+        Ok(Expr::Mul{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '÷' Term {
+        Ok(Expr::Div{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'EXP' Term {
+        Ok(Expr::Power{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '!' Term {
+        Ok(Expr::Binomial{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '?' Term {
+        Ok(Expr::Deal{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '|' Term {
+        Ok(Expr::Residue{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '⌈' Term {
+        Ok(Expr::Max{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '⌊' Term {
+        Ok(Expr::Min{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'LOG' Term {
+        Ok(Expr::Log{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'IOTA' Term {
+        Ok(Expr::IndexOf{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'IOTA_U' Term {
+        Ok(Expr::IntervalIndex{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'EQ' Term {
+        Ok(Expr::Equal{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'NEQ' Term {
+        Ok(Expr::NotEqual{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'LT' Term {
+        Ok(Expr::LessThan{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'GT' Term {
+        Ok(Expr::GreaterThan{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'LTE' Term {
+        Ok(Expr::LessEqual{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'GTE' Term {
+        Ok(Expr::GreaterEqual{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'RHO' Term {
+        Ok(Expr::Reshape{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor ',' Term {
+        Ok(Expr::Catenate{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'TABLE' Term {
+        Ok(Expr::CatenateFirst{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'ROTATE' Term {
+        Ok(Expr::Rotate{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'CIRCLE' Term {
+        Ok(Expr::Circular{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'AND' Term {
+        Ok(Expr::And{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'OR' Term {
+        Ok(Expr::Or{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'NAND' Term {
+        Ok(Expr::Nand{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'NOR' Term {
+        Ok(Expr::Nor{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '/' Term {
+        Ok(Expr::Replicate{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '\' Term {
+        Ok(Expr::Expand{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor '/' 'COMMUTE' Term {
+        Ok(Expr::Replicate{ span: $span, lhs: Box::new($4?), rhs: Box::new($1?) })
+      }
+    | Factor 'TAKE' Term {
+        Ok(Expr::Take{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'DROP' Term {
+        Ok(Expr::Drop{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | 'NAME' 'MODASSIGN' Term {
+        let op_str = $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default();
+        let op = match op_str.trim_end_matches('←') {
+            "+" => Ok(Operator::Add),
+            "-" => Ok(Operator::Subtract),
+            "×" => Ok(Operator::Multiply),
+            "÷" => Ok(Operator::Divide),
+            "*" => Ok(Operator::Power),
+            "⍟" => Ok(Operator::Log),
+            "!" => Ok(Operator::Binomial),
+            "|" => Ok(Operator::Residue),
+            "⌈" => Ok(Operator::Max),
+            "⌊" => Ok(Operator::Min),
+            "∧" => Ok(Operator::And),
+            "∨" => Ok(Operator::Or),
+            "⍲" => Ok(Operator::Nand),
+            "⍱" => Ok(Operator::Nor),
+            _ => Err(()),
+        };
+        match op {
+            Ok(operator) => Ok(Expr::ModifiedAssign{
+                span: $span,
+                name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(),
+                operator,
+                rhs: Box::new($3?),
+            }),
+            Err(_) => Err(()),
+        }
+      }
+    | Factor '[' Indices ']' 'ASSIGN' Term {
+        match $1? {
+            Expr::Variable{ name, .. } => Ok(Expr::IndexedAssign{ span: $span, name, indices: $3?, rhs: Box::new($6?) }),
+            _ => Err(()),
+        }
+      }
+    | 'NAME' 'ASSIGN' Term {
+        Ok(Expr::Assign{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($3?) })
+      }
+    | 'SYSNAME' 'ASSIGN' Term {
+        Ok(Expr::SysAssign{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($3?) })
+      }
+    | 'ALPHA' 'ASSIGN' Term {
+        Ok(Expr::DefaultAlpha{ span: $span, rhs: Box::new($3?) })
+      }
+    | Factor 'IBEAM' Term {
+        Ok(Expr::IBeam{ span: $span, code: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | 'NAME' 'FNASSIGN' Train {
+        Ok(Expr::AssignFn{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), func: Box::new($3?) })
+      }
+    | 'NAME' 'ASSIGN' '{' DfnBody '}' {
+        Ok(Expr::AssignDfn{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), body: Box::new($4?) })
+      }
+    | Factor 'OUTERPRODUCT' Operator Term {
+        match $3 {
+            Ok(op) => Ok(Expr::OuterProduct{ span: $span, lhs: Box::new($1?), operator: op, rhs: Box::new($4?) }),
+            Err(_) => Err(())
+        }
+      }
+    | Factor '{' DfnBody '}' Term {
+        Ok(Expr::DyadicDfn{ span: $span, lhs: Box::new($1?), body: Box::new($3?), rhs: Box::new($5?) })
+      }
+    | '{' DfnBody '}' Term {
+        Ok(Expr::MonadicDfn{ span: $span, body: Box::new($2?), rhs: Box::new($4?) })
+      }
+    | '{' DfnBody '}' 'POWOP' Factor Term {
+        Ok(Expr::PowerOp{ span: $span, body: Box::new($2?), count: Box::new($5?), arg: Box::new($6?) })
+      }
+    | '{' DfnBody '}' 'RANK' Factor Term {
+        Ok(Expr::RankOp{ span: $span, body: Box::new($2?), rank: Box::new($5?), arg: Box::new($6?) })
+      }
+    | 'NAME' Factor {
+        Ok(Expr::NamedMonadic{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($2?) })
+      }
+    | 'SYSNAME' Factor {
+        Ok(Expr::SysMonadic{ span: $span, name: $1.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($2?) })
+      }
+    | Factor 'SYSNAME' Factor {
+        Ok(Expr::SysDyadic{ span: $span, lhs: Box::new($1?), name: $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($3?) })
+      }
+    | 'OPERAND_L' Term {
+        Ok(Expr::NamedMonadic{ span: $span, name: "⍺⍺".to_string(), rhs: Box::new($2?) })
+      }
+    | 'OPERAND_R' Term {
+        Ok(Expr::NamedMonadic{ span: $span, name: "⍵⍵".to_string(), rhs: Box::new($2?) })
+      }
+    | Factor 'OPERAND_L' Term {
+        Ok(Expr::NamedDyadic{ span: $span, lhs: Box::new($1?), name: "⍺⍺".to_string(), rhs: Box::new($3?) })
+      }
+    | Factor 'OPERAND_R' Term {
+        Ok(Expr::NamedDyadic{ span: $span, lhs: Box::new($1?), name: "⍵⍵".to_string(), rhs: Box::new($3?) })
+      }
+    | Derived Term {
+        Ok(Expr::DerivedMonadic{ span: $span, func: Box::new($1?), rhs: Box::new($2?) })
+      }
+    | BoundFn Term {
+        Ok(Expr::DerivedMonadic{ span: $span, func: Box::new($1?), rhs: Box::new($2?) })
+      }
+    | Factor Derived Term {
+        Ok(Expr::DerivedDyadic{ span: $span, lhs: Box::new($1?), func: Box::new($2?), rhs: Box::new($3?) })
+      }
+    | Factor 'NAME' Factor {
+        Ok(Expr::NamedDyadic{ span: $span, lhs: Box::new($1?), name: $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), rhs: Box::new($3?) })
+      }
+    | Factor 'UNIQUE' Term {
+        Ok(Expr::Union{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'INTERSECT' Term {
+        Ok(Expr::Intersection{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'TILDE' Term {
+        Ok(Expr::Without{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'PARTITION' Term {
+        Ok(Expr::Partition{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'INDEX' Term {
+        Ok(Expr::Index{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'MATINV' Term {
+        Ok(Expr::MatrixDivide{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'DECODE' Term {
+        Ok(Expr::Decode{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'ENCODE' Term {
+        Ok(Expr::Encode{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'LEFT' Term {
+        Ok(Expr::Left{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'RIGHT' Term {
+        Ok(Expr::Right{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'MATCH' Term {
+        Ok(Expr::Match{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'NOTMATCH' Term {
+        Ok(Expr::NotMatch{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'FIND' Term {
+        Ok(Expr::Find{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'MEMBERSHIP' Term {
+        Ok(Expr::Membership{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'ENCLOSE' Term {
+        Ok(Expr::PartitionedEnclose{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'GRADEUP' Term {
+        Ok(Expr::DyadicGradeUp{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'GRADEDN' Term {
+        Ok(Expr::DyadicGradeDown{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | Factor 'TRANSPOSE' Term {
+        Ok(Expr::DyadicTranspose{ span: $span, lhs: Box::new($1?), rhs: Box::new($3?) })
+      }
+    | AxisFn Term {
+        let (func, axis) = $1?;
+        Ok(Expr::AxisMonadic{ span: $span, func, axis: Box::new(axis), arg: Box::new($2?) })
+      }
+    | Factor AxisFn Term {
+        let (func, axis) = $2?;
+        Ok(Expr::AxisDyadic{ span: $span, lhs: Box::new($1?), func, axis: Box::new(axis), rhs: Box::new($3?) })
+      }
+    | Factor Operator 'DOT' Operator Term {
+        match ($2, $4) {
+            (Ok(f), Ok(g)) => Ok(Expr::InnerProduct{ span: $span, lhs: Box::new($1?), f, g, rhs: Box::new($5?) }),
+            _ => Err(())
+        }
+      }
+    | Factor Operator 'EACH' Term {
+        match $2 {
+            Ok(op) => Ok(Expr::DyadicEach{ span: $span, lhs: Box::new($1?), operator: op, rhs: Box::new($4?) }),
+            Err(_) => Err(())
+        }
+      }
+    | Factor Operator 'COMMUTE' Term {
+        match $2 {
+            Ok(op) => Ok(Expr::Commute{ span: $span, lhs: Box::new($1?), operator: op, rhs: Box::new($4?) }),
+            Err(_) => Err(())
+        }
+      }
+    | MonadicFactor {
+        Ok($1?)
+      }
+    | Reduction { $1 }
+    | Factor { $1 }
+    ;
+
+MonadicFactor -> Result<Expr, ()>:
+      '+' Term {
+        Ok(Expr::Conjugate{ span: $span, arg: Box::new($2?) })
+      }
+    | '-' Term {
+        Ok(Expr::Negate{ span: $span, arg: Box::new($2?) })
+      }
+    | '×' Term {
+        Ok(Expr::Direction{ span: $span, arg: Box::new($2?) })
+      }
+    | '÷' Term {
+        Ok(Expr::Reciprocal{ span: $span, arg: Box::new($2?) })
+      }
+    | 'EXP' Term {
+        Ok(Expr::Exp{ span: $span, arg: Box::new($2?) })
+      }
+    | 'LOG' Term {
+        Ok(Expr::NaturalLog{ span: $span, arg: Box::new($2?) })
+      }
+    | 'CIRCLE' Term {
+        Ok(Expr::PiMultiple{ span: $span, arg: Box::new($2?) })
+      }
+    | '!' Term {
+        Ok(Expr::Factorial{ span: $span, arg: Box::new($2?) })
+      }
+    | '?' Term {
+        Ok(Expr::Roll{ span: $span, arg: Box::new($2?) })
+      }
+    | '|' Term {
+        Ok(Expr::Magnitude{ span: $span, arg: Box::new($2?) })
+      }
+    | '⌈' Term {
+        Ok(Expr::Ceil{ span: $span, arg: Box::new($2?) })
+      }
+    | '⌊' Term {
+        Ok(Expr::Floor{ span: $span, arg: Box::new($2?) })
+      }
+    | 'MAX' Term {
+        Ok(Expr::MonadicMax{ span: $span, arg: Box::new($2?) })
+      }
+    | 'MIN' Term {
+        Ok(Expr::MonadicMin{ span: $span, arg: Box::new($2?) })
+      }
+    | 'IOTA' Term {
+        Ok(Expr::GenIndex{ span: $span, arg: Box::new($2?) })
+      }
+    | 'IOTA_U' Term {
+        Ok(Expr::Where{ span: $span, arg: Box::new($2?) })
+      }
+    | 'RHO' Term {
+        Ok(Expr::Shape{ span: $span, arg: Box::new($2?) })
+      }
+    | ',' Term {
+        Ok(Expr::Ravel{ span: $span, arg: Box::new($2?) })
+      }
+    | 'TABLE' Term {
+        Ok(Expr::Table{ span: $span, arg: Box::new($2?) })
+      }
+    | 'ROTATE' Term {
+        Ok(Expr::Reverse{ span: $span, arg: Box::new($2?) })
+      }
+    | 'TRANSPOSE' Term {
+        Ok(Expr::Transpose{ span: $span, arg: Box::new($2?) })
+      }
+    | 'GRADEUP' Term {
+        Ok(Expr::GradeUp{ span: $span, arg: Box::new($2?) })
+      }
+    | 'GRADEDN' Term {
+        Ok(Expr::GradeDown{ span: $span, arg: Box::new($2?) })
+      }
+    | 'SELF' Term {
+        Ok(Expr::SelfCall{ span: $span, arg: Box::new($2?) })
+      }
+    | 'ENCLOSE' Term {
+        Ok(Expr::Enclose{ span: $span, arg: Box::new($2?) })
+      }
+    | 'FIRST' Term {
+        Ok(Expr::First{ span: $span, arg: Box::new($2?) })
+      }
+    | 'UNIQUE' Term {
+        Ok(Expr::Unique{ span: $span, arg: Box::new($2?) })
+      }
+    | 'TILDE' Term {
+        Ok(Expr::Not{ span: $span, arg: Box::new($2?) })
+      }
+    | 'MATINV' Term {
+        Ok(Expr::MatrixInverse{ span: $span, arg: Box::new($2?) })
+      }
+    | 'LEFT' Term {
+        Ok(Expr::LeftIdentity{ span: $span, arg: Box::new($2?) })
+      }
+    | 'RIGHT' Term {
+        Ok(Expr::RightIdentity{ span: $span, arg: Box::new($2?) })
+      }
+    | 'MATCH' Term {
+        Ok(Expr::Depth{ span: $span, arg: Box::new($2?) })
+      }
+    | 'NOTMATCH' Term {
+        Ok(Expr::Tally{ span: $span, arg: Box::new($2?) })
+      }
+    | 'TAKE' Term {
+        Ok(Expr::Mix{ span: $span, arg: Box::new($2?) })
+      }
+    | 'DROP' Term {
+        Ok(Expr::Split{ span: $span, arg: Box::new($2?) })
+      }
+    | Operator 'COMMUTE' Term {
+        match $1 {
+            Ok(op) => Ok(Expr::Selfie{ span: $span, operator: op, arg: Box::new($3?) }),
+            Err(_) => Err(())
+        }
+      }
+    ;
+
+DfnBody -> Result<Expr, ()>:
+      Expr Separator DfnBody {
+        Ok(Expr::DfnStatements{ span: $span, first: Box::new($1?), rest: Box::new($3?) })
+      }
+    | Expr 'ERRGUARD' Expr Separator DfnBody {
+        Ok(Expr::ErrorGuard{ span: $span, codes: Box::new($1?), handler: Box::new($3?), rest: Box::new($5?) })
+      }
+    | Expr ':' Expr Separator DfnBody {
+        Ok(Expr::DfnGuard{ span: $span, cond: Box::new($1?), result: Box::new($3?), rest: Box::new($5?) })
+      }
+    | Expr ':' Expr {
+        Ok(Expr::DfnGuard{ span: $span, cond: Box::new($1?), result: Box::new($3?), rest: Box::new(Expr::ScalarInteger { span: $span }) })
+      }
+    | Expr { $1 }
+    ;
+
+Indices -> Result<Vec<Option<Expr>>, ()>:
+      AxisIndex { Ok(vec![$1?]) }
+    | AxisIndex ';' Indices {
+        let mut indices = vec![$1?];
+        indices.extend($3?);
+        Ok(indices)
+      }
+    ;
+
+AxisIndex -> Result<Option<Expr>, ()>:
+      { Ok(None) }
+    | Expr { Ok(Some($1?)) }
+    ;
+
+Separator -> ():
+      'DIAMOND' { }
+    | 'NEWLINE' { }
+    ;
+
+Factor -> Result<Expr, ()>:
+      '(' Expr ')' { $2 }
+    | Factor '[' Indices ']' {
+        Ok(Expr::IndexRead{ span: $span, array: Box::new($1?), indices: $3? })
+      }
+
+    | 'VEC' {
+        Ok(Expr::Vector { span: $span, elements: vector_elements($lexer.span_str($span), $span) })
+    }
+    | 'INT' {
+        match $1 {
+            Ok(_) => Ok(Expr::ScalarInteger { span: $span }),
+            Err(_) => Err(())
+        }
+    }
+    | 'FLOAT' {
+        match $1 {
+            Ok(_value) => Ok(Expr::ScalarFloat { span: $span }),
+            Err(_) => Err(())
+        }
+    }
+    | 'NAME' {
+        match $1 {
+            Ok(_) => Ok(Expr::Variable { span: $span, name: $lexer.span_str($span).to_string() }),
+            Err(_) => Err(())
+        }
+    }
+    | 'SYSNAME' {
+        Ok(Expr::SysVar { span: $span, name: $lexer.span_str($span).to_string() })
+    }
+    | 'OMEGA' {
+        Ok(Expr::Omega { span: $span })
+    }
+    | 'ZILDE' {
+        Ok(Expr::Zilde { span: $span })
+    }
+    | 'ALPHA' {
+        Ok(Expr::Alpha { span: $span })
+    }
+    | StringArray { $1 }
+    | 'STRING' {
+        Ok(Expr::StringLiteral { span: $span })
+    }
+    ;
+
+    StringArray -> Result<Expr, ()>:
+    'STRING' 'STRING' {
+        Ok(Expr::StringArray { span: $span, elements: vec![
+            Expr::StringLiteral { span: $1.map(|l| l.span()).unwrap_or($span) },
+            Expr::StringLiteral { span: $2.map(|l| l.span()).unwrap_or($span) },
+        ]})
+    }
+    | StringArray 'STRING' {
+        match $1? {
+            Expr::StringArray { span: _, mut elements } => {
+                elements.push(Expr::StringLiteral { span: $2.map(|l| l.span()).unwrap_or($span) });
+                Ok(Expr::StringArray { span: $span, elements })
+            },
+            _ => Err(()),
+        }
+    }
+    ;
+
+    Reduction -> Result<Expr, ()>:
+    Operator '/' Term {
+        match $1 {
+            Ok(op) => Ok(Expr::Reduce{ span: $span, operator: op, term: Box::new($3?) }),
+            Err(_) => Err(())
+        }
+    }
+    | Operator '\' Term {
+        match $1 {
+            Ok(op) => Ok(Expr::Scan{ span: $span, operator: op, term: Box::new($3?) }),
+            Err(_) => Err(())
+        }
+    }
+    | Operator '/' 'EACH' Term {
+        match $1 {
+            Ok(op) => Ok(Expr::ReduceEach{ span: $span, operator: op, term: Box::new($4?) }),
+            Err(_) => Err(())
+        }
+    }
+    | Operator 'REDUCEFIRST' Term {
+        match $1 {
+            Ok(op) => Ok(Expr::ReduceFirst{ span: $span, operator: op, term: Box::new($3?) }),
+            Err(_) => Err(())
+        }
+    }
+    | Operator 'SCANFIRST' Term {
+        match $1 {
+            Ok(op) => Ok(Expr::ScanFirst{ span: $span, operator: op, term: Box::new($3?) }),
+            Err(_) => Err(())
+        }
+    }
+    ;
+
+    AxisFn -> Result<(AxisFn, Expr), ()>:
+      StructuralFn Axis { Ok(($1?, $2?)) }
+    | Operator '/' Axis { Ok((AxisFn::Reduce($1?), $3?)) }
+    | Operator 'REDUCEFIRST' Axis { Ok((AxisFn::Reduce($1?), $3?)) }
+    | Operator '\' Axis { Ok((AxisFn::Scan($1?), $3?)) }
+    | Operator 'SCANFIRST' Axis { Ok((AxisFn::Scan($1?), $3?)) }
+    ;
+
+    StructuralFn -> Result<AxisFn, ()>:
+      'ROTATE' { Ok(AxisFn::Rotate) }
+    | ',' { Ok(AxisFn::Catenate) }
+    | 'TAKE' { Ok(AxisFn::Take) }
+    | 'DROP' { Ok(AxisFn::Drop) }
+    | 'ENCLOSE' { Ok(AxisFn::Enclose) }
+    | 'PARTITION' { Ok(AxisFn::Partition) }
+    ;
+
+    Axis -> Result<Expr, ()>:
+      '[' Expr ']' { $2 }
+    ;
+
+    Operator -> Result<Operator, ()>:
+      '+' { Ok(Operator::Add) }
+    | '-' { Ok(Operator::Subtract) }
+    | '×' { Ok(Operator::Multiply) }
+    | '÷' { Ok(Operator::Divide) }
+    | 'EQ' { Ok(Operator::Equal) }
+    | 'NEQ' { Ok(Operator::NotEqual) }
+    | 'LT' { Ok(Operator::LessThan) }
+    | 'GT' { Ok(Operator::GreaterThan) }
+    | 'LTE' { Ok(Operator::LessEqual) }
+    | 'GTE' { Ok(Operator::GreaterEqual) }
+    | '⌈' { Ok(Operator::Max) }
+    | '⌊' { Ok(Operator::Min) }
+    | 'AND' { Ok(Operator::And) }
+    | 'OR' { Ok(Operator::Or) }
+    | 'NAND' { Ok(Operator::Nand) }
+    | 'NOR' { Ok(Operator::Nor) }
+    | 'EXP' { Ok(Operator::Power) }
+    | 'LOG' { Ok(Operator::Log) }
+    | '|' { Ok(Operator::Residue) }
+    | '!' { Ok(Operator::Binomial) }
+    ;
+
+
+    Derived -> Result<FnExpr, ()>:
+      DerivedFn { $1 }
+    | 'FNGROUP' Train ')' { $2 }
+    ;
+
+    Train -> Result<FnExpr, ()>:
+      TrainFns {
+        let mut fns = $1?;
+        if fns.len() == 1 {
+            fns.pop().ok_or(())
+        } else {
+            Ok(FnExpr::Train{ span: $span, fns })
+        }
+      }
+    ;
+
+    TrainFns -> Result<Vec<FnExpr>, ()>:
+      TrainFn { Ok(vec![$1?]) }
+    | TrainFn TrainFns {
+        let mut fns = vec![$1?];
+        fns.extend($2?);
+        Ok(fns)
+      }
+    | ArrayTine TrainFn TrainFns {
+        let mut fns = vec![$1?, $2?];
+        fns.extend($3?);
+        Ok(fns)
+      }
+    ;
+
+    ArrayTine -> Result<FnExpr, ()>:
+      ArrayOperand {
+        Ok(FnExpr::Array{ span: $span, value: Box::new($1?) })
+      }
+    ;
+
+    TrainFn -> Result<FnExpr, ()>:
+      Fn { $1 }
+    | Fn 'RANK' Factor {
+        Ok(FnExpr::Rank{ span: $span, func: Box::new($1?), rank: Box::new($3?) })
+      }
+    | Fn 'POWOP' Factor {
+        Ok(FnExpr::Power{ span: $span, func: Box::new($1?), count: Box::new($3?) })
+      }
+    | BoundFn { $1 }
+    ;
+
+    DerivedFn -> Result<FnExpr, ()>:
+      Fn 'MONADICOP' {
+        Ok(FnExpr::Derived{ span: $span, operator: $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), left: Box::new($1?), right: None })
+      }
+    | Fn 'DYADICOP' RightOperand {
+        Ok(FnExpr::Derived{ span: $span, operator: $2.map(|l| $lexer.span_str(l.span()).to_string()).unwrap_or_default(), left: Box::new($1?), right: Some(Box::new($3?)) })
+      }
+    | Operand 'EACH' { prim_derived($span, PrimOp::Each, $1, None) }
+    | Operand '/' { prim_derived($span, PrimOp::Reduce, $1, None) }
+    | Operand 'REDUCEFIRST' { prim_derived($span, PrimOp::ReduceFirst, $1, None) }
+    | Operand '\' { prim_derived($span, PrimOp::Scan, $1, None) }
+    | Operand 'SCANFIRST' { prim_derived($span, PrimOp::ScanFirst, $1, None) }
+    | Operand 'COMMUTE' { prim_derived($span, PrimOp::Commute, $1, None) }
+    | Operand '/' Axis { axis_derived($span, PrimOp::Reduce, $1, $3) }
+    | Operand 'REDUCEFIRST' Axis { axis_derived($span, PrimOp::ReduceFirst, $1, $3) }
+    | Operand '\' Axis { axis_derived($span, PrimOp::Scan, $1, $3) }
+    | Operand 'SCANFIRST' Axis { axis_derived($span, PrimOp::ScanFirst, $1, $3) }
+    | Fn 'COMPOSE' RightOperand { prim_derived($span, PrimOp::Compose, $1, Some($3)) }
+    | Fn 'OVER' RightOperand { prim_derived($span, PrimOp::Over, $1, Some($3)) }
+    | Fn 'KEY' { prim_derived($span, PrimOp::Key, $1, None) }
+    | Fn 'AT' Factor {
+        Ok(FnExpr::At{ span: $span, func: Box::new($1?), indices: Box::new($3?) })
+      }
+    | 'OUTERPRODUCT' OperandAtom { prim_derived($span, PrimOp::Outer, $2, None) }
+    | Operand 'DOT' OperandAtom { prim_derived($span, PrimOp::Inner, $1, Some($3)) }
+    | Operand 'DOT' Operator {
+        let dot = $2.map_err(|_| ())?.span();
+        prim_derived($span, PrimOp::Inner, $1, Some(scalar_fn($3?, Span::new(dot.end(), $span.end()))))
+      }
+    | Operator 'DOT' OperandAtom {
+        let dot = $2.map_err(|_| ())?.span();
+        prim_derived($span, PrimOp::Inner, scalar_fn($1?, Span::new($span.start(), dot.start())), Some($3))
+      }
+    ;
+
+    BoundFn -> Result<FnExpr, ()>:
+      ArrayOperand 'COMPOSE' RightOperand {
+        Ok(FnExpr::Bind{ span: $span, left: Box::new($1?), func: Box::new($3?) })
+      }
+    | BoundFn 'EACH' { prim_derived($span, PrimOp::Each, $1, None) }
+    ;
+
+    ArrayOperand -> Result<Expr, ()>:
+      'INT' { Ok(Expr::ScalarInteger { span: $span }) }
+    | 'FLOAT' { Ok(Expr::ScalarFloat { span: $span }) }
+    | 'VEC' { Ok(Expr::Vector { span: $span, elements: vector_elements($lexer.span_str($span), $span) }) }
+    | 'STRING' { Ok(Expr::StringLiteral { span: $span }) }
+    | '(' Expr ')' { $2 }
+    ;
+
+    RightOperand -> Result<FnExpr, ()>:
+      'NAME' {
+        Ok(FnExpr::Named{ span: $span, name: $lexer.span_str($span).to_string() })
+      }
+    | OperandAtom { $1 }
+    | Operator { scalar_fn($1?, $span) }
+    ;
+
+    Operand -> Result<FnExpr, ()>:
+      OperandAtom { $1 }
+    | DerivedFn { $1 }
+    ;
+
+    Fn -> Result<FnExpr, ()>:
+      FnAtom { $1 }
+    | DerivedFn { $1 }
+    ;
+
+    FnAtom -> Result<FnExpr, ()>:
+      'NAME' {
+        Ok(FnExpr::Named{ span: $span, name: $lexer.span_str($span).to_string() })
+      }
+    | OperandAtom { $1 }
+    | ScalarFn { $1 }
+    ;
+
+    OperandAtom -> Result<FnExpr, ()>:
+      'FNNAME' {
+        Ok(FnExpr::Named{ span: $span, name: $lexer.span_str($span).to_string() })
+      }
+    | 'OPERAND_L' {
+        Ok(FnExpr::Named{ span: $span, name: "⍺⍺".to_string() })
+      }
+    | 'OPERAND_R' {
+        Ok(FnExpr::Named{ span: $span, name: "⍵⍵".to_string() })
+      }
+    | '{' DfnBody '}' {
+        Ok(FnExpr::Dfn{ span: $span, body: Box::new($2?) })
+      }
+    | PrimFn { $1 }
+    | StructuralFn Axis { axis_fn($span, $1?, $2?) }
+    | 'FNGROUP' Train ')' { $2 }
+    ;
+
+    ScalarFn -> Result<FnExpr, ()>:
+      Operator { scalar_fn($1?, $span) }
+    | Operator '/' {
+        primitive($span, Some(Expr::Reduce{ span: $span, operator: $1?, term: omega($span) }), None)
+      }
+    | Operator '\' {
+        primitive($span, Some(Expr::Scan{ span: $span, operator: $1?, term: omega($span) }), None)
+      }
+    | Operator 'REDUCEFIRST' {
+        primitive($span, Some(Expr::ReduceFirst{ span: $span, operator: $1?, term: omega($span) }), None)
+      }
+    | Operator 'SCANFIRST' {
+        primitive($span, Some(Expr::ScanFirst{ span: $span, operator: $1?, term: omega($span) }), None)
+      }
+    | Operator 'EACH' {
+        primitive($span, None, Some(Expr::DyadicEach{ span: $span, lhs: alpha($span), operator: $1?, rhs: omega($span) }))
+      }
+    | Operator 'COMMUTE' {
+        let op = $1?;
+        primitive(
+            $span,
+            Some(Expr::Selfie{ span: $span, operator: op.clone(), arg: omega($span) }),
+            Some(Expr::Commute{ span: $span, lhs: alpha($span), operator: op, rhs: omega($span) }),
+        )
+      }
+    | Operator '/' 'EACH' {
+        primitive($span, Some(Expr::ReduceEach{ span: $span, operator: $1?, term: omega($span) }), None)
+      }
+    | Operator 'DOT' Operator {
+        primitive($span, None, Some(Expr::InnerProduct{ span: $span, lhs: alpha($span), f: $1?, g: $3?, rhs: omega($span) }))
+      }
+    | 'OUTERPRODUCT' Operator {
+        primitive($span, None, Some(Expr::OuterProduct{ span: $span, lhs: alpha($span), operator: $2?, rhs: omega($span) }))
+      }
+    | Operator '/' Axis { axis_fn($span, AxisFn::Reduce($1?), $3?) }
+    | Operator '\' Axis { axis_fn($span, AxisFn::Scan($1?), $3?) }
+    | Operator 'REDUCEFIRST' Axis { axis_fn($span, AxisFn::Reduce($1?), $3?) }
+    | Operator 'SCANFIRST' Axis { axis_fn($span, AxisFn::Scan($1?), $3?) }
+    ;
+
+    PrimFn -> Result<FnExpr, ()>:
+      'MAX' { primitive($span, Some(Expr::MonadicMax{ span: $span, arg: omega($span) }), None) }
+    | 'MIN' { primitive($span, Some(Expr::MonadicMin{ span: $span, arg: omega($span) }), None) }
+    | 'IOTA' {
+        primitive($span, Some(Expr::GenIndex{ span: $span, arg: omega($span) }), Some(Expr::IndexOf{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'IOTA_U' {
+        primitive($span, Some(Expr::Where{ span: $span, arg: omega($span) }), Some(Expr::IntervalIndex{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'RHO' {
+        primitive($span, Some(Expr::Shape{ span: $span, arg: omega($span) }), Some(Expr::Reshape{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | ',' {
+        primitive($span, Some(Expr::Ravel{ span: $span, arg: omega($span) }), Some(Expr::Catenate{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'TABLE' {
+        primitive($span, Some(Expr::Table{ span: $span, arg: omega($span) }), Some(Expr::CatenateFirst{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'ROTATE' {
+        primitive($span, Some(Expr::Reverse{ span: $span, arg: omega($span) }), Some(Expr::Rotate{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'TRANSPOSE' {
+        primitive($span, Some(Expr::Transpose{ span: $span, arg: omega($span) }), Some(Expr::DyadicTranspose{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'GRADEUP' {
+        primitive($span, Some(Expr::GradeUp{ span: $span, arg: omega($span) }), Some(Expr::DyadicGradeUp{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'GRADEDN' {
+        primitive($span, Some(Expr::GradeDown{ span: $span, arg: omega($span) }), Some(Expr::DyadicGradeDown{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'ENCLOSE' {
+        primitive($span, Some(Expr::Enclose{ span: $span, arg: omega($span) }), Some(Expr::PartitionedEnclose{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'FIRST' { primitive($span, Some(Expr::First{ span: $span, arg: omega($span) }), None) }
+    | 'UNIQUE' {
+        primitive($span, Some(Expr::Unique{ span: $span, arg: omega($span) }), Some(Expr::Union{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'TILDE' {
+        primitive($span, Some(Expr::Not{ span: $span, arg: omega($span) }), Some(Expr::Without{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'MATINV' {
+        primitive($span, Some(Expr::MatrixInverse{ span: $span, arg: omega($span) }), Some(Expr::MatrixDivide{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'LEFT' {
+        primitive($span, Some(Expr::LeftIdentity{ span: $span, arg: omega($span) }), Some(Expr::Left{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'RIGHT' {
+        primitive($span, Some(Expr::RightIdentity{ span: $span, arg: omega($span) }), Some(Expr::Right{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'MATCH' {
+        primitive($span, Some(Expr::Depth{ span: $span, arg: omega($span) }), Some(Expr::Match{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'NOTMATCH' {
+        primitive($span, Some(Expr::Tally{ span: $span, arg: omega($span) }), Some(Expr::NotMatch{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'TAKE' {
+        primitive($span, Some(Expr::Mix{ span: $span, arg: omega($span) }), Some(Expr::Take{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'DROP' {
+        primitive($span, Some(Expr::Split{ span: $span, arg: omega($span) }), Some(Expr::Drop{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'CIRCLE' {
+        primitive($span, Some(Expr::PiMultiple{ span: $span, arg: omega($span) }), Some(Expr::Circular{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | '?' {
+        primitive($span, Some(Expr::Roll{ span: $span, arg: omega($span) }), Some(Expr::Deal{ span: $span, lhs: alpha($span), rhs: omega($span) }))
+      }
+    | 'INTERSECT' { primitive($span, None, Some(Expr::Intersection{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    | 'PARTITION' { primitive($span, None, Some(Expr::Partition{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    | 'INDEX' { primitive($span, None, Some(Expr::Index{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    | 'DECODE' { primitive($span, None, Some(Expr::Decode{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    | 'ENCODE' { primitive($span, None, Some(Expr::Encode{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    | 'FIND' { primitive($span, None, Some(Expr::Find{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    | 'MEMBERSHIP' { primitive($span, None, Some(Expr::Membership{ span: $span, lhs: alpha($span), rhs: omega($span) })) }
+    ;
+
+Unmatched -> ():
+      "UNMATCHED" { }
+    ;
+%%
+
+use cfgrammar::Span;
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    // Dyadic
+    Add {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Sub {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Mul {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Div {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Power {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Log {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Min {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Max {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Binomial {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Deal {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Residue {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    IndexOf {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    IntervalIndex {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Equal {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    NotEqual {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    LessThan {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    GreaterThan {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    LessEqual {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    GreaterEqual {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+
+    Reshape {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Catenate {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    CatenateFirst {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Rotate {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    And {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Or {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Nand {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Nor {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Replicate {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Expand {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Circular {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Take {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Drop {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Assign {
+        span: Span,
+        name: String,
+        rhs: Box<Expr>,
+    },
+    MonadicDfn {
+        span: Span,
+        body: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    PowerOp {
+        span: Span,
+        body: Box<Expr>,
+        count: Box<Expr>,
+        arg: Box<Expr>,
+    },
+    RankOp {
+        span: Span,
+        body: Box<Expr>,
+        rank: Box<Expr>,
+        arg: Box<Expr>,
+    },
+    DyadicDfn {
+        span: Span,
+        lhs: Box<Expr>,
+        body: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Variable {
+        span: Span,
+        name: String,
+    },
+    SysVar {
+        span: Span,
+        name: String,
+    },
+    SysAssign {
+        span: Span,
+        name: String,
+        rhs: Box<Expr>,
+    },
+    SysMonadic {
+        span: Span,
+        name: String,
+        rhs: Box<Expr>,
+    },
+    SysDyadic {
+        span: Span,
+        lhs: Box<Expr>,
+        name: String,
+        rhs: Box<Expr>,
+    },
+    Omega {
+        span: Span,
+    },
+    Zilde {
+        span: Span,
+    },
+    DefaultAlpha {
+        span: Span,
+        rhs: Box<Expr>,
+    },
+    IBeam {
+        span: Span,
+        code: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Alpha {
+        span: Span,
+    },
+    SelfCall {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    DfnStatements {
+        span: Span,
+        first: Box<Expr>,
+        rest: Box<Expr>,
+    },
+    DfnGuard {
+        span: Span,
+        cond: Box<Expr>,
+        result: Box<Expr>,
+        rest: Box<Expr>,
+    },
+    ErrorGuard {
+        span: Span,
+        codes: Box<Expr>,
+        handler: Box<Expr>,
+        rest: Box<Expr>,
+    },
+    AssignDfn {
+        span: Span,
+        name: String,
+        body: Box<Expr>,
+    },
+    AssignFn {
+        span: Span,
+        name: String,
+        func: Box<FnExpr>,
+    },
+    /// A function expression on its own. The lexer never produces the
+    /// `FNEXPR` token it starts with: it is only parsed to tell function
+    /// expressions from values.
+    Function {
+        span: Span,
+        func: Box<FnExpr>,
+    },
+    ModifiedAssign {
+        span: Span,
+        name: String,
+        operator: Operator,
+        rhs: Box<Expr>,
+    },
+    /// `name[i;j]←rhs`, with `None` for an elided axis.
+    IndexedAssign {
+        span: Span,
+        name: String,
+        indices: Vec<Option<Expr>>,
+        rhs: Box<Expr>,
+    },
+    NamedMonadic {
+        span: Span,
+        name: String,
+        rhs: Box<Expr>,
+    },
+    DerivedMonadic {
+        span: Span,
+        func: Box<FnExpr>,
+        rhs: Box<Expr>,
+    },
+    DerivedDyadic {
+        span: Span,
+        lhs: Box<Expr>,
+        func: Box<FnExpr>,
+        rhs: Box<Expr>,
+    },
+    NamedDyadic {
+        span: Span,
+        lhs: Box<Expr>,
+        name: String,
+        rhs: Box<Expr>,
+    },
+    Union {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Intersection {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Without {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Decode {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Encode {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    InnerProduct {
+        span: Span,
+        lhs: Box<Expr>,
+        f: Operator,
+        g: Operator,
+        rhs: Box<Expr>,
+    },
+    Enclose {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    First {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Partition {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    PartitionedEnclose {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    DyadicEach {
+        span: Span,
+        lhs: Box<Expr>,
+        operator: Operator,
+        rhs: Box<Expr>,
+    },
+    Commute {
+        span: Span,
+        lhs: Box<Expr>,
+        operator: Operator,
+        rhs: Box<Expr>,
+    },
+    Selfie {
+        span: Span,
+        operator: Operator,
+        arg: Box<Expr>,
+    },
+    ReduceEach {
+        span: Span,
+        operator: Operator,
+        term: Box<Expr>,
+    },
+    Unique {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Not {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    MatrixInverse {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Index {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    MatrixDivide {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Left {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Right {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Match {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    NotMatch {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Find {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    StringLiteral {
+        span: Span,
+    },
+    OuterProduct {
+        span: Span,
+        lhs: Box<Expr>,
+        operator: Operator,
+        rhs: Box<Expr>,
+    },
+    Scan {
+        span: Span,
+        operator: Operator,
+        term: Box<Expr>,
+    },
+
+    // Monadic
+
+    Shape {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Ravel {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Table {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Reverse {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Transpose {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    DyadicTranspose {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// A structural function or reduction with an axis, as in `⌽[1]⍵`.
+    AxisMonadic {
+        span: Span,
+        func: AxisFn,
+        axis: Box<Expr>,
+        arg: Box<Expr>,
+    },
+    /// A structural function with an axis, as in `⍺,[0.5]⍵`.
+    AxisDyadic {
+        span: Span,
+        lhs: Box<Expr>,
+        func: AxisFn,
+        axis: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    GradeUp {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    GradeDown {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    /// `⍺⍋⍵`: grade up with `⍺` as the collation sequence.
+    DyadicGradeUp {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// `⍺⍒⍵`: grade down with `⍺` as the collation sequence.
+    DyadicGradeDown {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Exp {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    NaturalLog {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Conjugate {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Negate {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Direction {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Reciprocal {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    PiMultiple {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Factorial {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Roll {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Magnitude {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Ceil {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Floor {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    MonadicMax {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    MonadicMin {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    GenIndex {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Where {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    LeftIdentity {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    RightIdentity {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Depth {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Tally {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Mix {
+        span: Span,
+        arg: Box<Expr>,
+    },
+    Split {
+        span: Span,
+        arg: Box<Expr>,
+    },
+
+    Reduce {
+        span: Span,
+        operator: Operator,
+        term: Box<Expr>,
+    },
+    ReduceFirst {
+        span: Span,
+        operator: Operator,
+        term: Box<Expr>,
+    },
+    ScanFirst {
+        span: Span,
+        operator: Operator,
+        term: Box<Expr>,
+    },
+    Membership {
+        span: Span,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// `array[i;j]`, with `None` for an elided axis.
+    IndexRead {
+        span: Span,
+        array: Box<Expr>,
+        indices: Vec<Option<Expr>>,
+    },
+    StringArray {
+        span: Span,
+        elements: Vec<Expr>,
+    },
+
+    // Values
+
+    ScalarInteger {
+        span: Span,
+    },
+    ScalarFloat {
+        span: Span,
+    },
+    Vector {
+        span: Span,
+        elements: Vec<Expr>,
+    },
+}
+
+/// A function in operand position: the operands of a user-defined operator
+/// and the derived functions they produce.
+#[derive(Debug, Clone)]
+pub enum FnExpr {
+    /// A primitive or primitive reduction/scan, such as `⌽` or `+/`, as its
+    /// monadic and dyadic forms applied to `⍵` and to `⍺` and `⍵`.
+    Primitive {
+        span: Span,
+        monadic: Option<Box<Expr>>,
+        dyadic: Option<Box<Expr>>,
+    },
+    Named {
+        span: Span,
+        name: String,
+    },
+    Dfn {
+        span: Span,
+        body: Box<Expr>,
+    },
+    /// A user-defined operator applied to its operands.
+    Derived {
+        span: Span,
+        operator: String,
+        left: Box<FnExpr>,
+        right: Option<Box<FnExpr>>,
+    },
+    /// Two or more functions in a row: an atop or fork.
+    Train {
+        span: Span,
+        fns: Vec<FnExpr>,
+    },
+    /// `f⍤k`: `f` applied to the rank-`k` cells.
+    Rank {
+        span: Span,
+        func: Box<FnExpr>,
+        rank: Box<Expr>,
+    },
+    /// `f⍣n`: `f` applied `n` times.
+    Power {
+        span: Span,
+        func: Box<FnExpr>,
+        count: Box<Expr>,
+    },
+    /// `f@i`: `f` applied to the items at indices `i`.
+    At {
+        span: Span,
+        func: Box<FnExpr>,
+        indices: Box<Expr>,
+    },
+    /// An array as the left tine of a fork, as in `(1 + ⊢)`.
+    Array {
+        span: Span,
+        value: Box<Expr>,
+    },
+    /// `A∘f`: `f` with its left argument bound to `A`.
+    Bind {
+        span: Span,
+        left: Box<Expr>,
+        func: Box<FnExpr>,
+    },
+    /// A primitive operator applied to functions, such as `≢¨` or `∘.{⍺,⍵}`.
+    /// `axis` is the `[k]` of a reduction or scan, as in `{⍺+⍵}/[1]`.
+    PrimDerived {
+        span: Span,
+        operator: PrimOp,
+        left: Box<FnExpr>,
+        right: Option<Box<FnExpr>>,
+        axis: Option<Box<Expr>>,
+    },
+}
+
+/// The primitive operators that take any function as operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimOp {
+    Each,
+    Reduce,
+    ReduceFirst,
+    Scan,
+    ScanFirst,
+    Commute,
+    Outer,
+    Inner,
+    Compose,
+    Over,
+    Key,
+}
+
+/// The functions that take an axis, `f[k]`.
+#[derive(Debug, Clone)]
+pub enum AxisFn {
+    Rotate,
+    Catenate,
+    Take,
+    Drop,
+    Enclose,
+    Partition,
+    Reduce(Operator),
+    Scan(Operator),
+}
+
+/// The numbers of a vector literal such as `1 2 3`, whose text is `text`.
+fn vector_elements(text: &str, span: Span) -> Vec<Expr> {
+    let mut current_pos = 0;
+    let mut elements = Vec::new();
+    for value in text.split_whitespace() {
+        let start = text[current_pos..].find(value).unwrap_or(0) + current_pos;
+        let end = start + value.len();
+        current_pos = end;
+        elements.push(Expr::ScalarInteger { span: Span::new(start + span.start(), end + span.start()) });
+    }
+    elements
+}
+
+fn primitive(span: Span, monadic: Option<Expr>, dyadic: Option<Expr>) -> Result<FnExpr, ()> {
+    Ok(FnExpr::Primitive {
+        span,
+        monadic: monadic.map(Box::new),
+        dyadic: dyadic.map(Box::new),
+    })
+}
+
+fn omega(span: Span) -> Box<Expr> {
+    Box::new(Expr::Omega { span })
+}
+
+fn alpha(span: Span) -> Box<Expr> {
+    Box::new(Expr::Alpha { span })
+}
+
+/// A scalar primitive, such as `+` or `⌈`.
+fn scalar_fn(op: Operator, span: Span) -> Result<FnExpr, ()> {
+    let arg = omega(span);
+    let monadic = match op {
+        Operator::Add => Some(Expr::Conjugate { span, arg }),
+        Operator::Subtract => Some(Expr::Negate { span, arg }),
+        Operator::Multiply => Some(Expr::Direction { span, arg }),
+        Operator::Divide => Some(Expr::Reciprocal { span, arg }),
+        Operator::Power => Some(Expr::Exp { span, arg }),
+        Operator::Log => Some(Expr::NaturalLog { span, arg }),
+        Operator::Binomial => Some(Expr::Factorial { span, arg }),
+        Operator::Residue => Some(Expr::Magnitude { span, arg }),
+        Operator::Max => Some(Expr::Ceil { span, arg }),
+        Operator::Min => Some(Expr::Floor { span, arg }),
+        _ => None,
+    };
+    let (lhs, rhs) = (alpha(span), omega(span));
+    let dyadic = match op {
+        Operator::Add => Expr::Add { span, lhs, rhs },
+        Operator::Subtract => Expr::Sub { span, lhs, rhs },
+        Operator::Multiply => Expr::Mul { span, lhs, rhs },
+        Operator::Divide => Expr::Div { span, lhs, rhs },
+        Operator::Equal => Expr::Equal { span, lhs, rhs },
+        Operator::NotEqual => Expr::NotEqual { span, lhs, rhs },
+        Operator::LessThan => Expr::LessThan { span, lhs, rhs },
+        Operator::GreaterThan => Expr::GreaterThan { span, lhs, rhs },
+        Operator::LessEqual => Expr::LessEqual { span, lhs, rhs },
+        Operator::GreaterEqual => Expr::GreaterEqual { span, lhs, rhs },
+        Operator::Max => Expr::Max { span, lhs, rhs },
+        Operator::Min => Expr::Min { span, lhs, rhs },
+        Operator::And => Expr::And { span, lhs, rhs },
+        Operator::Or => Expr::Or { span, lhs, rhs },
+        Operator::Nand => Expr::Nand { span, lhs, rhs },
+        Operator::Nor => Expr::Nor { span, lhs, rhs },
+        Operator::Power => Expr::Power { span, lhs, rhs },
+        Operator::Log => Expr::Log { span, lhs, rhs },
+        Operator::Residue => Expr::Residue { span, lhs, rhs },
+        Operator::Binomial => Expr::Binomial { span, lhs, rhs },
+    };
+    primitive(span, monadic, Some(dyadic))
+}
+
+/// A function with an axis, such as `⌽[1]` or `+/[1]`.
+fn axis_fn(span: Span, func: AxisFn, axis: Expr) -> Result<FnExpr, ()> {
+    primitive(
+        span,
+        Some(Expr::AxisMonadic { span, func: func.clone(), axis: Box::new(axis.clone()), arg: omega(span) }),
+        Some(Expr::AxisDyadic { span, lhs: alpha(span), func, axis: Box::new(axis), rhs: omega(span) }),
+    )
+}
+
+fn prim_derived(
+    span: Span,
+    operator: PrimOp,
+    left: Result<FnExpr, ()>,
+    right: Option<Result<FnExpr, ()>>,
+) -> Result<FnExpr, ()> {
+    Ok(FnExpr::PrimDerived {
+        span,
+        operator,
+        left: Box::new(left?),
+        right: right.transpose()?.map(Box::new),
+        axis: None,
+    })
+}
+
+/// A reduction or scan along axis `[k]`, such as `{⍺+⍵}/[1]`.
+fn axis_derived(
+    span: Span,
+    operator: PrimOp,
+    left: Result<FnExpr, ()>,
+    axis: Result<Expr, ()>,
+) -> Result<FnExpr, ()> {
+    Ok(FnExpr::PrimDerived {
+        span,
+        operator,
+        left: Box::new(left?),
+        right: None,
+        axis: Some(Box::new(axis?)),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessEqual,
+    GreaterEqual,
+    Max,
+    Min,
+    And,
+    Or,
+    Nand,
+    Nor,
+    Power,
+    Log,
+    Residue,
+    Binomial,
+}
@@ -0,0 +1,119 @@
+//! Dyalog-style system commands (`)vars`, `)fns`, `)erase`, ...) for
+//! managing an [`Env`] from a REPL or other front-end.
+
+use crate::Env;
+use crate::workspace::{LoadReport, load_workspace, missing_names, save_workspace};
+
+/// What a front-end should do after a system command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandResult {
+    /// Show this text (may be empty).
+    Output(String),
+    /// End the session (`)off`).
+    Off,
+}
+
+/// Does this input line start with `)`, i.e. is it a system command rather
+/// than an expression?
+pub fn is_command(line: &str) -> bool {
+    line.trim_start().starts_with(')')
+}
+
+/// Run a system command such as `)vars` or `)erase x y`.
+///
+/// Command names are case-insensitive:
+///
+/// - `)vars` / `)fns` list the defined variables / functions
+/// - `)erase names...` removes variables and functions
+/// - `)clear` starts over with an empty environment (`⎕ARG` is kept)
+/// - `)reset` clears the state indicator. A failed function is never left
+///   suspended, so there is nothing to clear; `⎕RL`, `⎕EN` and `⎕DM` are
+///   kept
+/// - `)save path` writes variables and functions to a workspace file
+/// - `)load path` replaces the environment with a saved workspace
+/// - `)copy path [names...]` merges all or selected names from a workspace
+/// - `)off` asks the front-end to exit
+pub fn run_command(line: &str, env: &mut Env) -> Result<CommandResult, String> {
+    let line = line.trim();
+    let mut words = line.strip_prefix(')').unwrap_or(line).split_whitespace();
+    let command = words.next().unwrap_or_default().to_lowercase();
+    let args: Vec<&str> = words.collect();
+
+    let output = match (command.as_str(), args.as_slice()) {
+        ("vars", []) => names(env.vars().keys()),
+        ("fns", []) => names(env.fns().keys()),
+        ("erase", []) => return Err(")erase requires at least one name".to_string()),
+        ("erase", names) => {
+            let missing: Vec<&str> = names
+                .iter()
+                .copied()
+                .filter(|&name| {
+                    let var = env.remove_var(name).is_some();
+                    let dfn = env.remove_fn(name).is_some();
+                    !(var || dfn)
+                })
+                .collect();
+            if missing.is_empty() {
+                String::new()
+            } else {
+                format!("not found: {}", missing.join(" "))
+            }
+        }
+        ("clear", []) => {
+            let args = env.args().to_vec();
+            *env = Env::new();
+            env.set_args(args);
+            "clear ws".to_string()
+        }
+        ("reset", []) => String::new(),
+        ("save", [path]) => {
+            std::fs::write(path, save_workspace(env))
+                .map_err(|err| format!("cannot save {path}: {err}"))?;
+            format!("saved {path}")
+        }
+        ("load", [path]) => {
+            let text = read_file(path)?;
+            let mut loaded = Env::new();
+            loaded.set_args(env.args().to_vec());
+            let report = load_workspace(&text, &mut loaded, None)?;
+            *env = loaded;
+            with_failures(format!("loaded {path}"), &report)
+        }
+        ("copy", [path, names @ ..]) => {
+            let text = read_file(path)?;
+            let only = (!names.is_empty()).then_some(names);
+            let report = load_workspace(&text, env, only)?;
+            let mut output = with_failures(format!("copied {path}"), &report);
+            let missing = missing_names(&report, names);
+            if !missing.is_empty() {
+                output.push_str(&format!("\nnot found: {}", missing.join(" ")));
+            }
+            output
+        }
+        ("save" | "load" | "copy", _) => return Err(format!("){command} requires a path")),
+        ("off", []) => return Ok(CommandResult::Off),
+        ("vars" | "fns" | "clear" | "reset" | "off", _) => {
+            return Err(format!("){command} takes no arguments"));
+        }
+        _ => return Err(format!("unknown command '{line}'")),
+    };
+    Ok(CommandResult::Output(output))
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("cannot read {path}: {err}"))
+}
+
+/// `summary`, followed by a line for each function that failed to load.
+fn with_failures(summary: String, report: &LoadReport) -> String {
+    report.failed.iter().fold(summary, |out, (name, err)| {
+        format!("{out}\ncould not define {name}: {err}")
+    })
+}
+
+/// Sorted names, space-separated.
+fn names<'a>(keys: impl Iterator<Item = &'a String>) -> String {
+    let mut names: Vec<&str> = keys.map(String::as_str).collect();
+    names.sort_unstable();
+    names.join(" ")
+}
@@ -0,0 +1,53 @@
+//! Tab completion of names and glyph names, for REPLs and editors.
+
+use crate::Env;
+use crate::glyphs::GLYPHS;
+
+/// A completion candidate: `display` is shown in the list of alternatives,
+/// `replacement` is inserted in place of the partial word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub display: String,
+    pub replacement: String,
+}
+
+/// Complete the name ending at byte offset `pos` in `line`.
+///
+/// Returns the byte offset where the partial word starts and the candidates:
+/// variables and functions in `env`, sorted, followed by glyphs whose name
+/// starts with the word (`rho` → `⍴`).
+pub fn complete(env: &Env, line: &str, pos: usize) -> (usize, Vec<Completion>) {
+    let before = &line[..pos];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| c.is_ascii_alphanumeric() || c == '_')
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let word = &before[start..];
+    if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+        return (pos, Vec::new());
+    }
+
+    let mut names: Vec<&String> = env
+        .vars
+        .keys()
+        .chain(env.fns.keys())
+        .filter(|name| name.starts_with(word))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let names = names.into_iter().map(|name| Completion {
+        display: name.clone(),
+        replacement: name.clone(),
+    });
+    let glyphs = GLYPHS
+        .iter()
+        .filter(|g| g.name.starts_with(word))
+        .map(|g| Completion {
+            display: format!("{} {}", g.glyph, g.name),
+            replacement: g.glyph.to_string(),
+        });
+    (start, names.chain(glyphs).collect())
+}
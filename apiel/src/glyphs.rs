@@ -0,0 +1,101 @@
+//! APL glyphs with their names and the standard backtick-prefix keys.
+
+/// An APL glyph, its conventional name, and the key that follows the
+/// backtick prefix to type it (`` `r `` → `⍴`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph {
+    pub glyph: char,
+    pub key: char,
+    pub name: &'static str,
+}
+
+const fn g(key: char, glyph: char, name: &'static str) -> Glyph {
+    Glyph { glyph, key, name }
+}
+
+/// The prefix key for glyph input.
+pub const PREFIX: char = '`';
+
+/// Glyph table, in keyboard order (number row, then each letter row,
+/// unshifted keys before shifted ones).
+pub const GLYPHS: &[Glyph] = &[
+    g('`', '⋄', "diamond"),
+    g('1', '¨', "diaeresis"),
+    g('2', '¯', "macron"),
+    g('3', '<', "less"),
+    g('4', '≤', "lessequal"),
+    g('5', '=', "equal"),
+    g('6', '≥', "greaterequal"),
+    g('7', '>', "greater"),
+    g('8', '≠', "notequal"),
+    g('9', '∨', "or"),
+    g('0', '∧', "and"),
+    g('-', '×', "times"),
+    g('=', '÷', "divide"),
+    g('!', '⌶', "ibeam"),
+    g('@', '⍫', "deltilde"),
+    g('#', '⍒', "gradedown"),
+    g('$', '⍋', "gradeup"),
+    g('%', '⌽', "rotate"),
+    g('^', '⍉', "transpose"),
+    g('&', '⊖', "rotatefirst"),
+    g('*', '⍟', "log"),
+    g('(', '⍱', "nor"),
+    g(')', '⍲', "nand"),
+    g('_', '!', "factorial"),
+    g('+', '⌹', "domino"),
+    g('q', '?', "query"),
+    g('w', '⍵', "omega"),
+    g('e', '∊', "epsilon"),
+    g('r', '⍴', "rho"),
+    g('t', '~', "tilde"),
+    g('y', '↑', "uparrow"),
+    g('u', '↓', "downarrow"),
+    g('i', '⍳', "iota"),
+    g('o', '○', "circle"),
+    g('p', '*', "star"),
+    g('[', '←', "leftarrow"),
+    g(']', '→', "rightarrow"),
+    g('E', '⍷', "find"),
+    g('T', '⍨', "commute"),
+    g('I', '⍸', "where"),
+    g('O', '⍥', "over"),
+    g('P', '⍣', "power"),
+    g('{', '⍞', "quotequad"),
+    g('}', '⍬', "zilde"),
+    g('a', '⍺', "alpha"),
+    g('s', '⌈', "ceiling"),
+    g('d', '⌊', "floor"),
+    g('g', '∇', "del"),
+    g('h', '∆', "delta"),
+    g('j', '∘', "jot"),
+    g('l', '⎕', "quad"),
+    g(';', '⍎', "execute"),
+    g('\'', '⍕', "format"),
+    g('\\', '⊢', "right"),
+    g('J', '⍤', "rank"),
+    g('K', '⌸', "key"),
+    g('L', '⌷', "squad"),
+    g(':', '≡', "match"),
+    g('"', '≢', "tally"),
+    g('|', '⊣', "left"),
+    g('z', '⊂', "enclose"),
+    g('x', '⊃', "disclose"),
+    g('c', '∩', "intersection"),
+    g('v', '∪', "union"),
+    g('b', '⊥', "decode"),
+    g('n', '⊤', "encode"),
+    g('m', '|', "stile"),
+    g(',', '⍝', "lamp"),
+    g('.', '⍀', "expandfirst"),
+    g('/', '⌿', "replicatefirst"),
+    g('Z', '⊆', "partition"),
+    g('<', '⍪', "table"),
+    g('>', '⍙', "deltaunderbar"),
+    g('?', '⍠', "variant"),
+];
+
+/// The glyph typed by `` ` `` followed by `key`, if any.
+pub fn glyph_for_key(key: char) -> Option<char> {
+    GLYPHS.iter().find(|g| g.key == key).map(|g| g.glyph)
+}
@@ -1,7 +1,11 @@
 // This lint appears in generated code
 #![allow(clippy::needless_question_mark)]
 
+pub mod commands;
+pub mod complete;
+pub mod glyphs;
 pub mod macros;
 pub mod parse;
+pub mod workspace;
 
 pub use parse::Env;
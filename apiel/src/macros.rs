@@ -4,20 +4,25 @@ macro_rules! apl {
     ($expr:expr) => {{ $crate::parse::parse_and_evaluate($expr) }};
     // Expression with shared environment
     ($expr:expr, $env:expr) => {{ $crate::parse::parse_and_evaluate_with_env($expr, $env) }};
+    // Seeded random generator: `?` gives the same results on every run
+    ($expr:expr, seed: $seed:expr) => {{
+        let mut env = $crate::Env::with_seed($seed);
+        $crate::parse::parse_and_evaluate_with_env($expr, &mut env)
+    }};
     // Monadic: pass right argument (⍵)
     ($expr:expr, omega: $omega:expr) => {{
         let mut env = $crate::Env::new();
-        env.vars
-            .insert("⍵".to_string(), $crate::parse::val::Val::from_f64s($omega));
+        env.set_var("⍵", $crate::parse::val::Val::from_f64s($omega))
+            .expect("⍵ is not a dotted name");
         $crate::parse::parse_and_evaluate_with_env($expr, &mut env)
     }};
     // Dyadic: pass both arguments (⍺ and ⍵)
     ($expr:expr, alpha: $alpha:expr, omega: $omega:expr) => {{
         let mut env = $crate::Env::new();
-        env.vars
-            .insert("⍺".to_string(), $crate::parse::val::Val::from_f64s($alpha));
-        env.vars
-            .insert("⍵".to_string(), $crate::parse::val::Val::from_f64s($omega));
+        env.set_var("⍺", $crate::parse::val::Val::from_f64s($alpha))
+            .expect("⍺ is not a dotted name");
+        env.set_var("⍵", $crate::parse::val::Val::from_f64s($omega))
+            .expect("⍵ is not a dotted name");
         $crate::parse::parse_and_evaluate_with_env($expr, &mut env)
     }};
 }
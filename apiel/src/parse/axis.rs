@@ -0,0 +1,426 @@
+//! Structural functions along an axis of an array, as in `⌽[1]`, `,[0.5]`
+//! or `+/[1]`. Axes here are 0-based; the evaluator converts from APL's
+//! 1-based axis numbers.
+
+use super::errors::ErrorKind;
+use super::val::{Scalar, Val};
+
+/// Row-major strides of `shape`.
+fn strides(shape: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; shape.len()];
+    for axis in (0..shape.len().saturating_sub(1)).rev() {
+        strides[axis] = strides[axis + 1] * shape[axis + 1];
+    }
+    strides
+}
+
+/// Step `index` to the next position in `shape`, last axis fastest.
+fn advance(index: &mut [usize], shape: &[usize]) {
+    for axis in (0..index.len()).rev() {
+        index[axis] += 1;
+        if index[axis] < shape[axis] {
+            return;
+        }
+        index[axis] = 0;
+    }
+}
+
+/// The items of `val` at each position of `shape`, where a step along
+/// axis `i` of the result is `steps[i]` items in `val`.
+fn gather(val: &Val, shape: Vec<usize>, steps: &[usize]) -> Val {
+    let size = shape.iter().product::<usize>();
+    let mut index = vec![0; shape.len()];
+    let mut data = Vec::with_capacity(size);
+    for _ in 0..size {
+        let at: usize = index.iter().zip(steps).map(|(&i, &step)| i * step).sum();
+        data.push(val.data[at].clone());
+        advance(&mut index, &shape);
+    }
+    Val::new(shape, data)
+}
+
+/// `val` with its axes reordered, so that axis `i` of the result is axis
+/// `perm[i]` of `val`.
+pub(crate) fn permute(val: &Val, perm: &[usize]) -> Val {
+    let shape: Vec<usize> = perm.iter().map(|&axis| val.shape[axis]).collect();
+    let strides = strides(&val.shape);
+    let steps: Vec<usize> = perm.iter().map(|&axis| strides[axis]).collect();
+    gather(val, shape, &steps)
+}
+
+/// `targets⍉val`: axis `i` of `val` becomes axis `targets[i]` of the
+/// result. Axes sent to the same place are walked together, taking their
+/// diagonal.
+pub(crate) fn transpose(val: &Val, targets: &[usize]) -> Result<Val, (ErrorKind, &'static str)> {
+    let rank = targets.iter().max().map_or(0, |&axis| axis + 1);
+    if (0..rank).any(|axis| !targets.contains(&axis)) {
+        return Err((ErrorKind::Domain, "Transpose axes must not skip an axis"));
+    }
+    let strides = strides(&val.shape);
+    let merged = |axis: usize| {
+        targets
+            .iter()
+            .zip(val.shape.iter().zip(&strides))
+            .filter(move |(target, _)| **target == axis)
+            .map(|(_, dims)| dims)
+    };
+    let shape: Vec<usize> = (0..rank)
+        .map(|axis| merged(axis).map(|(&len, _)| len).min().unwrap_or(0))
+        .collect();
+    let steps: Vec<usize> = (0..rank)
+        .map(|axis| merged(axis).map(|(_, &stride)| stride).sum())
+        .collect();
+    Ok(gather(val, shape, &steps))
+}
+
+/// `val` with `axis` moved to the end.
+pub(crate) fn axis_last(val: &Val, axis: usize) -> Val {
+    let rank = val.shape.len();
+    let perm: Vec<usize> = (0..rank).filter(|&a| a != axis).chain([axis]).collect();
+    permute(val, &perm)
+}
+
+/// `val` with its last axis moved back to `axis`.
+pub(crate) fn last_to(val: &Val, axis: usize) -> Val {
+    let last = val.shape.len() - 1;
+    let perm: Vec<usize> = (0..=last)
+        .map(|a| match a {
+            a if a < axis => a,
+            a if a == axis => last,
+            a => a - 1,
+        })
+        .collect();
+    permute(val, &perm)
+}
+
+/// `val` with a new axis of length 1 at `axis`.
+pub(crate) fn insert_axis(val: &Val, axis: usize) -> Val {
+    let mut shape = val.shape.clone();
+    shape.insert(axis, 1);
+    Val::new(shape, val.data.clone())
+}
+
+/// Apply `f` to each vector along `axis` of `val`, and put the results back
+/// along that axis. The results must all have the same length.
+pub(crate) fn along<F>(val: &Val, axis: usize, mut f: F) -> Result<Val, (ErrorKind, &'static str)>
+where
+    F: FnMut(usize, Vec<Scalar>) -> Result<Vec<Scalar>, (ErrorKind, &'static str)>,
+{
+    if axis >= val.shape.len() {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    let moved = axis_last(val, axis);
+    let len = val.shape[axis];
+    let count = moved.shape[..moved.shape.len() - 1]
+        .iter()
+        .product::<usize>();
+    let mut new_len = None;
+    let mut data = Vec::with_capacity(val.data.len());
+    for row in 0..count {
+        let result = f(row, moved.data[row * len..(row + 1) * len].to_vec())?;
+        if *new_len.get_or_insert(result.len()) != result.len() {
+            return Err((ErrorKind::Length, "Length error"));
+        }
+        data.extend(result);
+    }
+    let mut shape = moved.shape;
+    *shape.last_mut().unwrap() = new_len.unwrap_or(len);
+    Ok(last_to(&Val::new(shape, data), axis))
+}
+
+/// `val` with the items along `axis` reduced to one, and that axis removed.
+pub(crate) fn reduce<F>(val: &Val, axis: usize, mut f: F) -> Result<Val, (ErrorKind, &'static str)>
+where
+    F: FnMut(&Scalar, &Scalar) -> Option<Scalar>,
+{
+    let mut result = along(val, axis, |_, items| {
+        let mut items = items.into_iter().rev();
+        let last = items
+            .next()
+            .ok_or((ErrorKind::Domain, "Reduce of an empty axis"))?;
+        let folded = items.try_fold(last, |acc, item| f(&item, &acc));
+        Ok(vec![folded.ok_or((
+            ErrorKind::Domain,
+            "Arithmetic error in Reduce",
+        ))?])
+    })?;
+    result.shape.remove(axis);
+    Ok(result)
+}
+
+/// `val` with each item along `axis` replaced by the reduction of the items
+/// up to it.
+pub(crate) fn scan<F>(val: &Val, axis: usize, mut f: F) -> Result<Val, (ErrorKind, &'static str)>
+where
+    F: FnMut(&Scalar, &Scalar) -> Option<Scalar>,
+{
+    along(val, axis, |_, items| {
+        (0..items.len())
+            .map(|end| {
+                items[..end]
+                    .iter()
+                    .rev()
+                    .try_fold(items[end].clone(), |acc, item| f(item, &acc))
+                    .ok_or((ErrorKind::Domain, "Arithmetic error in Scan"))
+            })
+            .collect()
+    })
+}
+
+/// `,[axes]val`: `val` with the contiguous `axes` merged into one.
+pub(crate) fn merge_axes(val: &Val, axes: &[usize]) -> Result<Val, (ErrorKind, &'static str)> {
+    let (Some(&first), Some(&last)) = (axes.first(), axes.last()) else {
+        return Err((ErrorKind::Domain, "Ravel needs at least one axis"));
+    };
+    if last >= val.shape.len() {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    if axes.windows(2).any(|w| w[1] != w[0] + 1) {
+        return Err((
+            ErrorKind::Domain,
+            "Ravel axes must be contiguous and ascending",
+        ));
+    }
+    let mut shape = val.shape[..first].to_vec();
+    shape.push(val.shape[first..=last].iter().product());
+    shape.extend_from_slice(&val.shape[last + 1..]);
+    Ok(Val::new(shape, val.data.clone()))
+}
+
+/// `⌽[axis]`.
+pub(crate) fn reverse(val: &Val, axis: usize) -> Result<Val, (ErrorKind, &'static str)> {
+    if val.is_scalar() {
+        return Ok(val.clone());
+    }
+    along(val, axis, |_, mut items| {
+        items.reverse();
+        Ok(items)
+    })
+}
+
+/// `amounts⌽[axis]val`: a single amount for every vector along `axis`, or
+/// one for each.
+pub(crate) fn rotate(
+    amounts: &Val,
+    val: &Val,
+    axis: usize,
+) -> Result<Val, (ErrorKind, &'static str)> {
+    let amounts = amounts
+        .data
+        .iter()
+        .map(|n| match n {
+            Scalar::Integer(n) => Ok(*n),
+            _ => Err((ErrorKind::Domain, "Rotate left argument must be integers")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if val.is_scalar() {
+        return Ok(val.clone());
+    }
+    if axis >= val.shape.len() {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    let mut others = val.shape.clone();
+    others.remove(axis);
+    if amounts.len() != 1 && amounts.len() != others.iter().product::<usize>() {
+        return Err((
+            ErrorKind::Length,
+            "Rotate left argument must match the other axes",
+        ));
+    }
+    along(val, axis, |row, mut items| {
+        let len = items.len() as i64;
+        if len > 0 {
+            let n = amounts[if amounts.len() == 1 { 0 } else { row }];
+            items.rotate_left(n.rem_euclid(len) as usize);
+        }
+        Ok(items)
+    })
+}
+
+/// `lhs,[axis]rhs`. A scalar is extended to fill the axis, and an array of
+/// rank one less is treated as having length 1 along `axis`.
+pub(crate) fn catenate(
+    lhs: &Val,
+    rhs: &Val,
+    axis: usize,
+) -> Result<Val, (ErrorKind, &'static str)> {
+    let rank = lhs.shape.len().max(rhs.shape.len()).max(1);
+    if axis >= rank {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    let extend = |val: &Val, other: &Val| -> Result<Val, (ErrorKind, &'static str)> {
+        match val.shape.len() {
+            0 => {
+                let mut shape = if other.shape.is_empty() {
+                    vec![1]
+                } else {
+                    other.shape.clone()
+                };
+                shape[axis] = 1;
+                let size = shape.iter().product();
+                Ok(Val::new(shape, vec![val.data[0].clone(); size]))
+            }
+            r if r == rank => Ok(val.clone()),
+            r if r + 1 == rank => Ok(insert_axis(val, axis)),
+            _ => Err((ErrorKind::Rank, "Rank error in catenate")),
+        }
+    };
+    let lhs = extend(lhs, rhs)?;
+    let rhs = extend(rhs, &lhs)?;
+    let mut lhs_others = lhs.shape.clone();
+    lhs_others.remove(axis);
+    let mut rhs_others = rhs.shape.clone();
+    rhs_others.remove(axis);
+    if lhs_others != rhs_others {
+        return Err((ErrorKind::Length, "Length error in catenate"));
+    }
+    let lhs = axis_last(&lhs, axis);
+    let rhs = axis_last(&rhs, axis);
+    let (m, n) = (lhs.shape[rank - 1], rhs.shape[rank - 1]);
+    let rows = lhs_others.iter().product::<usize>();
+    let mut data = Vec::with_capacity(lhs.data.len() + rhs.data.len());
+    for row in 0..rows {
+        data.extend_from_slice(&lhs.data[row * m..(row + 1) * m]);
+        data.extend_from_slice(&rhs.data[row * n..(row + 1) * n]);
+    }
+    let mut shape = lhs_others;
+    shape.push(m + n);
+    Ok(last_to(&Val::new(shape, data), axis))
+}
+
+/// `lhs,[k]rhs` for a fractional `k`: join two arrays of the same shape
+/// along a new axis at `axis`. A scalar is extended to the other's shape.
+pub(crate) fn laminate(
+    lhs: &Val,
+    rhs: &Val,
+    axis: usize,
+) -> Result<Val, (ErrorKind, &'static str)> {
+    let fill = |val: &Val, other: &Val| match val.is_scalar() {
+        true => Val::new(
+            other.shape.clone(),
+            vec![val.data[0].clone(); other.data.len()],
+        ),
+        false => val.clone(),
+    };
+    let (lhs, rhs) = (fill(lhs, rhs), fill(rhs, lhs));
+    if lhs.shape != rhs.shape {
+        return Err((ErrorKind::Length, "Length error in laminate"));
+    }
+    if axis > lhs.shape.len() {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    catenate(&insert_axis(&lhs, axis), &insert_axis(&rhs, axis), axis)
+}
+
+/// `↓[axis]`: the vectors along `axis`, enclosed, in an array of the other
+/// axes.
+pub(crate) fn split(val: &Val, axis: usize) -> Result<Val, (ErrorKind, &'static str)> {
+    if val.is_scalar() {
+        return Ok(val.clone());
+    }
+    let mut result = along(val, axis, |_, items| {
+        Ok(vec![Scalar::Nested(Box::new(Val::vector(items)))])
+    })?;
+    result.shape.remove(axis);
+    Ok(result)
+}
+
+/// Move the item axes of a mixed array, which come last, to `axis`. `mixed`
+/// has `frame` leading axes.
+pub(crate) fn place_item_axes(
+    mixed: &Val,
+    frame: usize,
+    axis: usize,
+) -> Result<Val, (ErrorKind, &'static str)> {
+    if axis > frame {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    let rank = mixed.shape.len();
+    let perm: Vec<usize> = (0..axis).chain(frame..rank).chain(axis..frame).collect();
+    Ok(permute(mixed, &perm))
+}
+
+/// `⊂[axes]`: the subarrays along `axes`, enclosed, in an array of the
+/// remaining axes.
+pub(crate) fn enclose(val: &Val, axes: &[usize]) -> Result<Val, (ErrorKind, &'static str)> {
+    let rank = val.shape.len();
+    if axes.iter().any(|&a| a >= rank) {
+        return Err((ErrorKind::Index, "Axis out of range"));
+    }
+    let perm: Vec<usize> = (0..rank)
+        .filter(|a| !axes.contains(a))
+        .chain(axes.iter().copied())
+        .collect();
+    let moved = permute(val, &perm);
+    let frame = moved.shape[..rank - axes.len()].to_vec();
+    let cell_shape = moved.shape[rank - axes.len()..].to_vec();
+    let size = cell_shape.iter().product::<usize>();
+    let data = (0..frame.iter().product::<usize>())
+        .map(|i| {
+            let cell = moved.data[i * size..(i + 1) * size].to_vec();
+            Scalar::Nested(Box::new(Val::new(cell_shape.clone(), cell)))
+        })
+        .collect();
+    Ok(Val::new(frame, data))
+}
+
+/// `mask⊂items` for a vector: a new partition starts at each positive
+/// mask, and items before the first one are dropped.
+pub(crate) fn partitioned_enclose(
+    mask: &[Scalar],
+    items: Vec<Scalar>,
+) -> Result<Vec<Scalar>, (ErrorKind, &'static str)> {
+    if mask.len() != items.len() {
+        return Err((
+            ErrorKind::Length,
+            "Partitioned enclose: left and right must be same length",
+        ));
+    }
+    let mut partitions: Vec<Vec<Scalar>> = Vec::new();
+    for (m, item) in mask.iter().zip(items) {
+        let m: f64 = m.clone().into();
+        if m >= 1.0 {
+            partitions.push(vec![item]);
+        } else if let Some(part) = partitions.last_mut().filter(|_| m == 0.0) {
+            part.push(item);
+        }
+    }
+    Ok(partitions
+        .into_iter()
+        .map(|p| Scalar::Nested(Box::new(Val::vector(p))))
+        .collect())
+}
+
+/// `mask⊆items` for a vector: each run of positive masks is a partition,
+/// and items with a zero mask are dropped.
+pub(crate) fn partition(
+    mask: &[Scalar],
+    items: Vec<Scalar>,
+) -> Result<Vec<Scalar>, (ErrorKind, &'static str)> {
+    if mask.len() != items.len() {
+        return Err((
+            ErrorKind::Length,
+            "Partition: arguments must have same length",
+        ));
+    }
+    let mut groups = Vec::new();
+    let mut current = Vec::new();
+    for (m, item) in mask.iter().zip(items) {
+        let m = match m {
+            Scalar::Integer(i) => *i,
+            Scalar::Float(f) => *f as i64,
+            _ => 0,
+        };
+        if m > 0 {
+            current.push(item);
+        } else if !current.is_empty() {
+            groups.push(Scalar::Nested(Box::new(Val::vector(std::mem::take(
+                &mut current,
+            )))));
+        }
+    }
+    if !current.is_empty() {
+        groups.push(Scalar::Nested(Box::new(Val::vector(current))));
+    }
+    Ok(groups)
+}
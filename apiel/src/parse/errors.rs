@@ -0,0 +1,58 @@
+//! APL error classes and numbers, as reported by `⎕EN` and matched by error
+//! guards.
+
+use std::borrow::Cow;
+
+use cfgrammar::Span;
+
+/// An evaluator error: where it happened, its class and its message.
+pub type Error = (Span, ErrorKind, Cow<'static, str>);
+
+/// The class of an evaluator error, which decides its number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Syntax,
+    Index,
+    Rank,
+    Length,
+    Value,
+    Domain,
+    /// Raised by `⎕SIGNAL` with this number.
+    Signal(i64),
+}
+
+impl ErrorKind {
+    /// The error number, as in `⎕EN`.
+    pub fn number(self) -> i64 {
+        match self {
+            ErrorKind::Syntax => 2,
+            ErrorKind::Index => 3,
+            ErrorKind::Rank => 4,
+            ErrorKind::Length => 5,
+            ErrorKind::Value => 6,
+            ErrorKind::Domain => 11,
+            ErrorKind::Signal(number) => number,
+        }
+    }
+}
+
+/// Attach a position to an error from a helper that has none.
+pub(crate) fn at(span: Span) -> impl Fn((ErrorKind, &'static str)) -> Error {
+    move |(kind, msg)| (span, kind, msg.into())
+}
+
+/// The event message for an error number, as in `⎕DM`.
+pub fn error_name(number: i64) -> &'static str {
+    match number {
+        1 => "WS FULL",
+        2 => "SYNTAX ERROR",
+        3 => "INDEX ERROR",
+        4 => "RANK ERROR",
+        5 => "LENGTH ERROR",
+        6 => "VALUE ERROR",
+        10 => "LIMIT ERROR",
+        11 => "DOMAIN ERROR",
+        16 => "NONCE ERROR",
+        _ => "ERROR",
+    }
+}
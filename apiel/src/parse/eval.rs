@@ -1,44 +1,1607 @@
 use super::*;
-use crate::parse::apiel_y::{Expr, Operator};
+use crate::parse::apiel_y::{AxisFn, Expr, FnExpr, Operator, PrimOp};
+use errors::{Error, ErrorKind, at, error_name};
 use eyre::{OptionExt, Result};
+use namespace::{Namespace, Scope, as_namespace, names_of_class, split_name};
 use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedNeg, CheckedSub};
-use rand::Rng;
+use rand::distributions::Open01;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
 use tracing::{debug, error};
+use tradfn::Tradfn;
 use val::{CheckedPow, Log, Scalar, Val};
 
 #[derive(Debug, Clone)]
 pub struct StoredDfn {
-    pub body: Rc<Expr>,
-    pub source: String, // original input line for correct span resolution
+    pub body: Arc<Expr>,
+    pub source: Arc<str>, // original input line for correct span resolution
+    start: usize,         // where the definition starts in `source`
+    operands: usize,      // 1 for a dop using `⍺⍺`, 2 if it uses `⍵⍵`
+    // `source` lexed once, rather than on every call
+    lexemes: Arc<[DefaultLexeme<u32>]>,
 }
 
-#[derive(Debug, Clone, Default)]
+impl StoredDfn {
+    /// A dfn parsed from `span` of the text behind `lexer`. The text up to
+    /// the end of the span is kept, since the body's spans index into it.
+    pub(crate) fn new(
+        body: Arc<Expr>,
+        lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>,
+        span: Span,
+    ) -> Self {
+        StoredDfn {
+            body,
+            source: lexer.span_str(Span::new(0, span.end())).into(),
+            lexemes: lexer
+                .iter()
+                .flatten()
+                .take_while(|lexeme| lexeme.span().end() <= span.end())
+                .collect(),
+            start: span.start(),
+            operands: operands(lexer, span),
+        }
+    }
+
+    /// Source text of the definition.
+    pub fn definition(&self) -> &str {
+        &self.source[self.start..]
+    }
+
+    /// Is this a dop, an operator taking `⍺⍺` (and `⍵⍵`) as operands?
+    pub fn is_operator(&self) -> bool {
+        self.operands > 0
+    }
+
+    /// Is this a dop taking a right operand `⍵⍵`?
+    pub fn is_dyadic_operator(&self) -> bool {
+        self.operands == 2
+    }
+}
+
+/// How many operands the dfn in `span` takes: 2 if its body uses `⍵⍵`, 1
+/// if it uses only `⍺⍺`. Operands of dfns nested in it don't count, and
+/// strings are single tokens, so their text is not seen.
+fn operands(lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>, span: Span) -> usize {
+    let mut depth = 0_i32;
+    let mut operands = 0;
+    let within = |at: Span| at.start() >= span.start() && at.end() <= span.end();
+    for lexeme in lexer.iter().flatten().filter(|l| within(l.span())) {
+        match (lexeme.tok_id(), lexer.span_str(lexeme.span())) {
+            (_, "{") => depth += 1,
+            (_, "}") => depth -= 1,
+            (tok, _) if depth == 1 && tok == apiel_l::T_OPERAND_L => operands = operands.max(1),
+            (tok, _) if depth == 1 && tok == apiel_l::T_OPERAND_R => operands = 2,
+            _ => {}
+        }
+    }
+    operands
+}
+
+/// A primitive function used as an operand, such as `⌽` or `+/`, applied
+/// through its monadic and dyadic forms.
+#[derive(Debug)]
+pub struct Primitive {
+    pub glyphs: String,
+    monadic: Option<StoredDfn>,
+    dyadic: Option<StoredDfn>,
+}
+
+/// A function derived by applying a dop to its operands.
+#[derive(Debug)]
+pub struct Derived {
+    pub operator: StoredDfn,
+    pub left: StoredFn,
+    pub right: Option<StoredFn>,
+    pub source: String,
+}
+
+/// A train of functions, `f g h`. Read from the right, the last three form a
+/// fork and a leftover function on the left is applied atop the rest.
+#[derive(Debug)]
+pub struct Train {
+    pub tines: Vec<Tine>,
+    pub source: String,
+}
+
+/// A tine of a train: a function, or an array as the left tine of a fork,
+/// as in `(1 + ⊢)`.
+#[derive(Debug)]
+pub enum Tine {
+    Fn(StoredFn),
+    Array(Val),
+}
+
+/// A function assigned by name, as in `avg←+/÷≢`. The source is the whole
+/// assignment, so running it again defines the same function.
+#[derive(Debug)]
+pub struct Tacit {
+    pub func: StoredFn,
+    pub source: String,
+}
+
+/// `f⍤k`: a function applied to the rank-`k` cells of its arguments.
+#[derive(Debug)]
+pub struct Rank {
+    pub func: StoredFn,
+    pub rank: usize,
+    pub source: String,
+}
+
+/// `f⍣n`: a function applied `n` times.
+#[derive(Debug)]
+pub struct Power {
+    pub func: StoredFn,
+    pub count: usize,
+    pub source: String,
+}
+
+/// A function derived by a primitive operator, such as `≢¨`, `,/` or
+/// `f.g`. `right` is the right operand of `.`, `axis` the `[k]` of `f/[k]`.
+#[derive(Debug)]
+pub struct PrimDerived {
+    pub operator: PrimOp,
+    pub left: StoredFn,
+    pub right: Option<StoredFn>,
+    pub axis: Option<Val>,
+    pub source: String,
+}
+
+/// `f@i`: a function applied to the items of its argument at `indices`.
+#[derive(Debug)]
+pub struct At {
+    pub func: StoredFn,
+    pub indices: Val,
+    pub source: String,
+}
+
+/// `A∘f`: a function with its left argument bound.
+#[derive(Debug)]
+pub struct Bind {
+    pub left: Val,
+    pub func: StoredFn,
+    pub source: String,
+}
+
+/// A function written in Rust, added with [`Env::register_fn`]. It is
+/// called with `⍺` (if given) and `⍵`, and an error is a DOMAIN ERROR.
+pub struct Native {
+    pub name: String,
+    func: Box<NativeFn>,
+}
+
+type NativeFn = dyn Fn(Option<Val>, Val) -> Result<Val, String> + Send + Sync;
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function value, as held in [`Env::fns`].
+#[derive(Debug, Clone)]
+pub enum StoredFn {
+    Dfn(StoredDfn),
+    Tradfn(Arc<Tradfn>),
+    Primitive(Arc<Primitive>),
+    Derived(Arc<Derived>),
+    Train(Arc<Train>),
+    Tacit(Arc<Tacit>),
+    Rank(Arc<Rank>),
+    Power(Arc<Power>),
+    PrimDerived(Arc<PrimDerived>),
+    At(Arc<At>),
+    Bind(Arc<Bind>),
+    Native(Arc<Native>),
+}
+
+impl StoredFn {
+    /// Source text of the definition.
+    pub fn source(&self) -> &str {
+        match self {
+            StoredFn::Dfn(dfn) => dfn.definition(),
+            StoredFn::Tradfn(f) => &f.source,
+            StoredFn::Primitive(f) => &f.glyphs,
+            StoredFn::Derived(f) => &f.source,
+            StoredFn::Train(f) => &f.source,
+            StoredFn::Tacit(f) => &f.source,
+            StoredFn::Rank(f) => &f.source,
+            StoredFn::Power(f) => &f.source,
+            StoredFn::PrimDerived(f) => &f.source,
+            StoredFn::At(f) => &f.source,
+            StoredFn::Bind(f) => &f.source,
+            StoredFn::Native(f) => &f.name,
+        }
+    }
+
+    /// Is this a dop rather than a function?
+    pub fn is_operator(&self) -> bool {
+        matches!(self, StoredFn::Dfn(dfn) if dfn.is_operator())
+    }
+}
+
+/// Interpreter state. `Env` is `Send + Sync`, and cloning it is cheap: the
+/// variables and functions are shared until a clone assigns one, which
+/// copies the table.
+///
+/// [`Env::vars`] and [`Env::fns`] are the names of the running scope: the
+/// workspace at the top level, a function's locals inside a call. Names not
+/// found there are looked up in the namespace the function runs in, then
+/// in its callers' scopes.
+///
+/// Some things stay shared between clones:
+/// - namespaces, which are references: `cfg.n←99` in one clone is seen by
+///   every clone holding `cfg`;
+/// - the random generator used by `?`, so dfn calls advance the caller's
+///   stream. Call [`Env::set_seed`] on a clone to give it its own.
+///
+/// The last error, `⎕EN` and `⎕DM`, is shared only along a call chain, so
+/// an error caught inside a dfn is visible to its caller. A clone starts
+/// with a copy of it, and its errors are its own.
+#[derive(Debug)]
 pub struct Env {
-    pub vars: HashMap<String, Val>,
-    pub fns: HashMap<String, StoredDfn>,
+    pub(crate) vars: Arc<HashMap<String, Val>>,
+    pub(crate) fns: Arc<HashMap<String, StoredFn>>,
+    pub(crate) ns: Option<Namespace>,
+    pub(crate) outer: Option<Arc<Scope>>,
+    args: Arc<[String]>,
+    rl: u64,
+    rng: Arc<Mutex<StdRng>>,
+    errors: Arc<Mutex<ErrorState>>,
+}
+
+/// The last error, for `⎕EN` and `⎕DM`.
+#[derive(Debug, Default, Clone)]
+struct ErrorState {
+    en: i64,
+    dm: Vec<String>,
+}
+
+impl Clone for Env {
+    fn clone(&self) -> Self {
+        self.sharing_errors(Arc::new(Mutex::new(self.errors().clone())))
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env {
+            vars: Arc::default(),
+            fns: Arc::default(),
+            ns: None,
+            outer: None,
+            args: Arc::default(),
+            rl: 0,
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+            errors: Arc::default(),
+        }
+    }
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An environment whose random generator starts from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut env = Self::new();
+        env.set_seed(seed);
+        env
+    }
+
+    /// The value of `⎕RL`: the last seed set, or 0 if seeded from entropy.
+    pub fn seed(&self) -> u64 {
+        self.rl
+    }
+
+    /// Reseed the random generator, as `⎕RL←seed` does. A seed of 0 reseeds
+    /// from system entropy.
+    pub fn set_seed(&mut self, seed: u64) {
+        let rng = if seed == 0 {
+            StdRng::from_entropy()
+        } else {
+            StdRng::seed_from_u64(seed)
+        };
+        self.rl = seed;
+        self.rng = Arc::new(Mutex::new(rng));
+    }
+
+    /// A scope for one call of `dfn`, with its arguments bound and `∇`
+    /// naming it. A monadic call leaves `⍺` unbound, even if the caller had
+    /// one, so `⍺←` can supply a default.
+    pub(crate) fn call_scope(
+        &self,
+        ns: Option<Namespace>,
+        dfn: Option<&StoredDfn>,
+        alpha: Option<Val>,
+        omega: Val,
+    ) -> Env {
+        let mut env = self.scope(ns);
+        let vars = Arc::make_mut(&mut env.vars);
+        if let Some(alpha) = alpha {
+            vars.insert("⍺".to_string(), alpha);
+        }
+        vars.insert("⍵".to_string(), omega);
+        if let Some(dfn) = dfn {
+            Arc::make_mut(&mut env.fns).insert("∇".to_string(), StoredFn::Dfn(dfn.clone()));
+        }
+        env
+    }
+
+    /// The variables of the running scope.
+    pub fn vars(&self) -> &HashMap<String, Val> {
+        &self.vars
+    }
+
+    /// The functions of the running scope.
+    pub fn fns(&self) -> &HashMap<String, StoredFn> {
+        &self.fns
+    }
+
+    /// The value of `⎕ARG`: arguments passed to a script by its host.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn set_args<I, S>(&mut self, args: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+    }
+
+    /// Make a Rust function callable as `name`, like any other function:
+    /// `name ⍵`, `⍺ name ⍵`, or as an operand as in `name¨`.
+    pub fn register_fn<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn(Option<Val>, Val) -> Result<Val, String> + Send + Sync + 'static,
+    {
+        let native = Native {
+            name: name.to_string(),
+            func: Box::new(func),
+        };
+        Arc::make_mut(&mut self.fns).insert(name.to_string(), StoredFn::Native(Arc::new(native)));
+    }
+
+    /// The values of `⎕EN` and `⎕DM`: the last error's number, and its event
+    /// message and detail. `(0, [])` if there has been no error.
+    pub fn last_error(&self) -> (i64, Vec<String>) {
+        let errors = self.errors();
+        (errors.en, errors.dm.clone())
+    }
+
+    /// Forget the last error, so `⎕EN` is 0 and `⎕DM` empty.
+    pub fn clear_error(&self) {
+        *self.errors() = ErrorState::default();
+    }
+
+    /// A clone that shares this environment's last error, for evaluating
+    /// part of the same call chain.
+    pub(crate) fn fork(&self) -> Env {
+        self.sharing_errors(Arc::clone(&self.errors))
+    }
+
+    fn sharing_errors(&self, errors: Arc<Mutex<ErrorState>>) -> Env {
+        Env {
+            vars: Arc::clone(&self.vars),
+            fns: Arc::clone(&self.fns),
+            ns: self.ns.clone(),
+            outer: self.outer.clone(),
+            args: Arc::clone(&self.args),
+            rl: self.rl,
+            rng: Arc::clone(&self.rng),
+            errors,
+        }
+    }
+
+    /// Make an evaluator error the last error, returning its number. The
+    /// event message of a `⎕SIGNAL` is the signalled text itself.
+    pub(crate) fn record_error(&self, kind: ErrorKind, message: &str) -> i64 {
+        let en = kind.number();
+        let dm = match kind {
+            ErrorKind::Signal(_) => vec![message.to_string(), String::new()],
+            _ => vec![error_name(en).to_string(), message.to_string()],
+        };
+        *self.errors() = ErrorState { en, dm };
+        en
+    }
+
+    fn rng(&self) -> MutexGuard<'_, StdRng> {
+        self.rng.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn errors(&self) -> MutexGuard<'_, ErrorState> {
+        self.errors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// `message ⎕SIGNAL number`: raise an error that guards catch by `number`.
+/// An empty `number` signals nothing.
+fn signal(span: Span, message: Option<Val>, number: Val) -> Result<Val, Error> {
+    let en = match number.data.as_slice() {
+        [] => return Ok(Val::vector(vec![])),
+        [Scalar::Integer(i), ..] if (1..=999).contains(i) => *i,
+        _ => {
+            return Err((
+                span,
+                ErrorKind::Domain,
+                "⎕SIGNAL number must be an integer from 1 to 999".into(),
+            ));
+        }
+    };
+    let text = match message {
+        Some(message) => message
+            .data
+            .iter()
+            .map(|s| match s {
+                Scalar::Char(c) => Ok(*c),
+                _ => Err((
+                    span,
+                    ErrorKind::Domain,
+                    "⎕SIGNAL message must be a character vector".into(),
+                )),
+            })
+            .collect::<Result<String, _>>()?,
+        None => error_name(en).to_string(),
+    };
+    Err((span, ErrorKind::Signal(en), text.into()))
+}
+
+/// `⎕NS names`: `ns` with copies of the named variables and functions.
+fn new_namespace(span: Span, ns: Namespace, names: Val, env: &Env) -> Result<Val, Error> {
+    let names =
+        names_in(&names).ok_or((span, ErrorKind::Domain, "⎕NS argument must be names".into()))?;
+    for name in names {
+        let (_, member) = split_name(&name);
+        if let Some(val) = env.get_var(&name) {
+            ns.lock().vars.insert(member.to_string(), val);
+        } else if let Some(f) = env.get_fn(&name) {
+            ns.lock().fns.insert(member.to_string(), f);
+        } else {
+            return Err((span, ErrorKind::Value, "Undefined name".into()));
+        }
+    }
+    Ok(ns.to_val())
+}
+
+/// `⎕NC`: 2 for a variable (including a bound `⍺` or `⍵`), 3 for a
+/// function, 4 for an operator, 9 for a namespace and 0 for an unused name.
+fn name_class(name: &str, env: &Env) -> i64 {
+    match env.get_var(name) {
+        Some(val) if as_namespace(&val).is_some() => 9,
+        Some(_) => 2,
+        None => match env.get_fn(name) {
+            Some(f) if f.is_operator() => 4,
+            Some(_) => 3,
+            None => 0,
+        },
+    }
+}
+
+/// The names in a character vector, or a vector of them. Empty for `''`.
+fn names_in(val: &Val) -> Option<Vec<String>> {
+    let text = |v: &Val| -> Option<String> {
+        v.data
+            .iter()
+            .map(|s| match s {
+                Scalar::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    };
+    if val.data.iter().all(|s| matches!(s, Scalar::Char(_))) {
+        return Some(text(val)?.split_whitespace().map(String::from).collect());
+    }
+    val.data
+        .iter()
+        .map(|s| match s {
+            Scalar::Nested(v) => text(v).map(|t| t.trim().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Names as a vector of character vectors, or as a blank-padded matrix.
+fn name_list(names: Vec<String>, nested: bool) -> Val {
+    let chars = |name: &String| name.chars().map(Scalar::Char).collect::<Vec<_>>();
+    if nested {
+        let data = names
+            .iter()
+            .map(|name| Scalar::Nested(Box::new(Val::vector(chars(name)))))
+            .collect();
+        return Val::vector(data);
+    }
+    let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+    let data = names
+        .iter()
+        .flat_map(|name| {
+            let mut row = chars(name);
+            row.resize(width, Scalar::Char(' '));
+            row
+        })
+        .collect();
+    Val::new(vec![names.len(), width], data)
+}
+
+fn eval_stored_dfn(stored: &StoredDfn, env: &mut Env) -> Result<Val, Error> {
+    let mut newlines = NewlineCache::new();
+    newlines.feed(&stored.source);
+    let lexemes = stored.lexemes.iter().copied().map(Ok).collect();
+    let lex = LRNonStreamingLexer::new(&stored.source, lexemes, newlines);
+    eval(&lex, (*stored.body).clone(), env)
+}
+
+/// Apply a function to its arguments, reporting errors at `span`. `name` is
+/// what the function was called by, so a function in a namespace sees its
+/// siblings.
+fn call_fn(
+    span: Span,
+    name: &str,
+    f: &StoredFn,
+    alpha: Option<Val>,
+    omega: Val,
+    env: &mut Env,
+) -> Result<Val, Error> {
+    // The body's spans index the definition, so errors are reported here
+    let here = |(_, kind, msg): Error| (span, kind, msg);
+    let (stored, ns, operands) = match f {
+        StoredFn::Tradfn(f) => {
+            return tradfn::call(f, alpha, Some(omega), env)
+                .map_err(|(kind, msg)| (span, kind, msg.into()));
+        }
+        StoredFn::Dfn(stored) if stored.is_operator() => {
+            return Err((
+                span,
+                ErrorKind::Syntax,
+                "An operator needs an operand".into(),
+            ));
+        }
+        StoredFn::Dfn(stored) => {
+            // A function in a namespace runs there, so it sees its siblings
+            let ns = match split_name(name) {
+                (Some(path), _) => env.namespace_at(path).ok(),
+                (None, _) => None,
+            };
+            (stored, ns, None)
+        }
+        StoredFn::Primitive(f) => {
+            let form = match alpha {
+                Some(_) => f.dyadic.as_ref().ok_or((
+                    span,
+                    ErrorKind::Syntax,
+                    "Function has no dyadic form".into(),
+                )),
+                None => f.monadic.as_ref().ok_or((
+                    span,
+                    ErrorKind::Syntax,
+                    "Function has no monadic form".into(),
+                )),
+            }?;
+            let mut prim_env = env.call_scope(None, None, alpha, omega);
+            return eval_stored_dfn(form, &mut prim_env).map_err(here);
+        }
+        StoredFn::Tacit(f) => return call_fn(span, name, &f.func, alpha, omega, env),
+        StoredFn::Train(f) => return call_train(span, &f.tines, alpha, omega, env),
+        StoredFn::Rank(f) => return call_rank(span, f, alpha, omega, env),
+        StoredFn::PrimDerived(f) => return call_prim_derived(span, f, alpha, omega, env),
+        StoredFn::At(f) => return call_at(span, f, alpha, omega, env),
+        StoredFn::Native(f) => {
+            return (f.func)(alpha, omega).map_err(|msg| (span, ErrorKind::Domain, msg.into()));
+        }
+        StoredFn::Bind(f) => {
+            if alpha.is_some() {
+                return Err((
+                    span,
+                    ErrorKind::Syntax,
+                    "Function has no dyadic form".into(),
+                ));
+            }
+            return call_fn(span, "", &f.func, Some(f.left.clone()), omega, env);
+        }
+        StoredFn::Power(f) => {
+            let mut result = omega;
+            for _ in 0..f.count {
+                result = call_fn(span, "", &f.func, alpha.clone(), result, env)?;
+            }
+            return Ok(result);
+        }
+        StoredFn::Derived(f) => (&f.operator, None, Some(f)),
+    };
+    let mut dfn_env = env.call_scope(ns, Some(stored), alpha, omega);
+    if let Some(f) = operands {
+        let fns = Arc::make_mut(&mut dfn_env.fns);
+        fns.insert("⍺⍺".to_string(), f.left.clone());
+        if let Some(right) = &f.right {
+            fns.insert("⍵⍵".to_string(), right.clone());
+        }
+    }
+    eval_stored_dfn(stored, &mut dfn_env).map_err(here)
+}
+
+/// Apply a train: `(f g h) ⍵` is `(f ⍵) g (h ⍵)`, `(f g) ⍵` is `f (g ⍵)`,
+/// and longer trains nest from the right. An array left tine is its own
+/// value, so `(A g h) ⍵` is `A g (h ⍵)`.
+fn call_train(
+    span: Span,
+    tines: &[Tine],
+    alpha: Option<Val>,
+    omega: Val,
+    env: &mut Env,
+) -> Result<Val, Error> {
+    let func = |tine: &Tine| match tine {
+        Tine::Fn(f) => Ok(f.clone()),
+        Tine::Array(_) => Err((
+            span,
+            ErrorKind::Syntax,
+            "Only the left tine of a fork can be an array".into(),
+        )),
+    };
+    match tines {
+        [] => Err((span, ErrorKind::Domain, "Empty train".into())),
+        [f] => call_fn(span, "", &func(f)?, alpha, omega, env),
+        [f, rest @ ..] if tines.len().is_multiple_of(2) => {
+            let rhs = call_train(span, rest, alpha, omega, env)?;
+            call_fn(span, "", &func(f)?, None, rhs, env)
+        }
+        [f, g, rest @ ..] => {
+            let rhs = call_train(span, rest, alpha.clone(), omega.clone(), env)?;
+            let lhs = match f {
+                Tine::Array(val) => val.clone(),
+                Tine::Fn(f) => call_fn(span, "", f, alpha, omega, env)?,
+            };
+            call_fn(span, "", &func(g)?, Some(lhs), rhs, env)
+        }
+    }
+}
+
+/// Apply `f⍤k` to each rank-`k` cell of `omega`, paired with the cells of
+/// `alpha` when dyadic. A single cell is paired with every cell of the other.
+fn call_rank(
+    span: Span,
+    f: &Rank,
+    alpha: Option<Val>,
+    omega: Val,
+    env: &mut Env,
+) -> Result<Val, Error> {
+    let (frame, omegas) = cells(&omega, f.rank);
+    let Some(alpha) = alpha else {
+        let results = omegas
+            .into_iter()
+            .map(|cell| call_fn(span, "", &f.func, None, cell, env))
+            .collect::<Result<_, _>>()?;
+        return Ok(from_cells(frame, results));
+    };
+    let (alpha_frame, alphas) = cells(&alpha, f.rank);
+    let frame = match (alpha_frame.is_empty(), frame.is_empty()) {
+        (_, true) => alpha_frame,
+        (true, _) => frame,
+        _ if alpha_frame == frame => frame,
+        _ => return Err((span, ErrorKind::Length, "Rank frames must match".into())),
+    };
+    let count = frame.iter().product::<usize>();
+    let pick = |cells: &[Val], i: usize| cells[if cells.len() == 1 { 0 } else { i }].clone();
+    let results = (0..count)
+        .map(|i| {
+            call_fn(
+                span,
+                "",
+                &f.func,
+                Some(pick(&alphas, i)),
+                pick(&omegas, i),
+                env,
+            )
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(from_cells(frame, results))
+}
+
+/// Evaluate the indices of `x[i;j]`, right to left as APL does.
+fn eval_indices(
+    lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>,
+    indices: Vec<Option<Expr>>,
+    env: &mut Env,
+) -> Result<Vec<Option<Val>>, Error> {
+    let mut vals = indices
+        .into_iter()
+        .rev()
+        .map(|index| index.map(|index| eval(lexer, index, env)).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    vals.reverse();
+    Ok(vals)
+}
+
+/// The shape of an indexing result and where its items are in the array.
+type Selection = (Vec<usize>, Vec<usize>);
+
+/// The shape of `x[i;j]` for an array of `shape` and the positions in the
+/// array of its items. An elided axis selects the whole axis, and each
+/// index contributes its own shape, so a scalar index drops its axis.
+fn index_positions(
+    span: Span,
+    shape: &[usize],
+    indices: Vec<Option<Val>>,
+) -> Result<Selection, Error> {
+    // `s[]` for a scalar `s`
+    if shape.is_empty() && matches!(indices.as_slice(), [None]) {
+        return Ok((vec![], vec![0]));
+    }
+    if indices.len() != shape.len() {
+        return Err((
+            span,
+            ErrorKind::Rank,
+            "Rank error: one index per axis is needed".into(),
+        ));
+    }
+    let mut result_shape = Vec::new();
+    let mut positions = vec![0];
+    for (index, &len) in indices.into_iter().zip(shape) {
+        let axis: Vec<usize> = match index {
+            None => {
+                result_shape.push(len);
+                (0..len).collect()
+            }
+            Some(index) => {
+                result_shape.extend_from_slice(&index.shape);
+                index
+                    .data
+                    .into_iter()
+                    .map(|s| match usize::try_from(s) {
+                        Ok(i) if (1..=len).contains(&i) => Ok(i - 1),
+                        Ok(_) => Err((span, ErrorKind::Index, "Index out of bounds".into())),
+                        Err(_) => Err((span, ErrorKind::Domain, "Index must be integer".into())),
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        positions = positions
+            .iter()
+            .flat_map(|p| axis.iter().map(move |i| p * len + i))
+            .collect();
+    }
+    Ok((result_shape, positions))
+}
+
+/// Apply a function derived by a primitive operator.
+fn call_prim_derived(
+    span: Span,
+    f: &PrimDerived,
+    alpha: Option<Val>,
+    omega: Val,
+    env: &mut Env,
+) -> Result<Val, Error> {
+    let func = &f.left;
+    match (f.operator, alpha) {
+        (PrimOp::Each, None) => {
+            let data = omega
+                .data
+                .iter()
+                .map(|item| call_fn(span, "", func, None, item_val(item), env).map(enclose_item))
+                .collect::<Result<_, _>>()?;
+            Ok(Val::new(omega.shape, data))
+        }
+        (PrimOp::Each, Some(alpha)) => {
+            let shape = match (alpha.data.len(), omega.data.len()) {
+                (1, _) => omega.shape.clone(),
+                (_, 1) => alpha.shape.clone(),
+                _ if alpha.shape == omega.shape => omega.shape.clone(),
+                _ => return Err((span, ErrorKind::Length, "Each: lengths must match".into())),
+            };
+            let count = shape.iter().product::<usize>();
+            let pick =
+                |val: &Val, i: usize| item_val(&val.data[if val.data.len() == 1 { 0 } else { i }]);
+            let data = (0..count)
+                .map(|i| {
+                    call_fn(span, "", func, Some(pick(&alpha, i)), pick(&omega, i), env)
+                        .map(enclose_item)
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Val::new(shape, data))
+        }
+        (PrimOp::Reduce | PrimOp::ReduceFirst, None) => {
+            let (shape, vectors) = match derived_axis(f, &omega).map_err(at(span))? {
+                Some(k) => along_axis(&axis::axis_last(&omega, k), false),
+                None => along_axis(&omega, f.operator == PrimOp::ReduceFirst),
+            };
+            let data = vectors
+                .into_iter()
+                .map(|items| match items.split_last() {
+                    Some((last, rest)) => rest.iter().rev().try_fold(last.clone(), |acc, item| {
+                        call_fn(span, "", func, Some(item_val(item)), item_val(&acc), env)
+                            .map(enclose_item)
+                    }),
+                    None => Err((
+                        span,
+                        ErrorKind::Domain,
+                        "Reduce of an empty argument".into(),
+                    )),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Val::new(shape, data))
+        }
+        (PrimOp::Scan | PrimOp::ScanFirst, None) => {
+            // Along an axis, scan the last one and move it back after
+            let axis = derived_axis(f, &omega).map_err(at(span))?;
+            let omega = match axis {
+                Some(k) => axis::axis_last(&omega, k),
+                None => omega,
+            };
+            let first = axis.is_none() && f.operator == PrimOp::ScanFirst;
+            let (frame, vectors) = along_axis(&omega, first);
+            let len = vectors.first().map_or(0, Vec::len);
+            let mut scanned = Vec::with_capacity(vectors.len());
+            for items in vectors {
+                let mut results = Vec::with_capacity(items.len());
+                for end in 0..items.len() {
+                    let mut acc = items[end].clone();
+                    for item in items[..end].iter().rev() {
+                        acc = enclose_item(call_fn(
+                            span,
+                            "",
+                            func,
+                            Some(item_val(item)),
+                            item_val(&acc),
+                            env,
+                        )?);
+                    }
+                    results.push(acc);
+                }
+                scanned.push(results);
+            }
+            // Put the scanned axis back where it came from
+            let count = frame.iter().product::<usize>();
+            let data = if first {
+                (0..len * count)
+                    .map(|i| scanned[i % count][i / count].clone())
+                    .collect()
+            } else {
+                scanned.into_iter().flatten().collect()
+            };
+            let result = Val::new(omega.shape, data);
+            Ok(match axis {
+                Some(k) => axis::last_to(&result, k),
+                None => result,
+            })
+        }
+        (PrimOp::Reduce | PrimOp::ReduceFirst | PrimOp::Scan | PrimOp::ScanFirst, Some(_)) => {
+            Err((
+                span,
+                ErrorKind::Syntax,
+                "Reduction and scan take no left argument".into(),
+            ))
+        }
+        (PrimOp::Commute, alpha) => {
+            let alpha = alpha.unwrap_or_else(|| omega.clone());
+            call_fn(span, "", func, Some(omega), alpha, env)
+        }
+        (PrimOp::Outer, Some(alpha)) => {
+            let mut data = Vec::with_capacity(alpha.data.len() * omega.data.len());
+            for a in &alpha.data {
+                for w in &omega.data {
+                    let result = call_fn(span, "", func, Some(item_val(a)), item_val(w), env)?;
+                    data.push(enclose_item(result));
+                }
+            }
+            let mut shape = alpha.shape;
+            shape.extend_from_slice(&omega.shape);
+            Ok(Val::new(shape, data))
+        }
+        (PrimOp::Inner, Some(alpha)) => {
+            let g = f.right.as_ref().ok_or((
+                span,
+                ErrorKind::Domain,
+                "Inner product needs two operands".into(),
+            ))?;
+            let (alpha_frame, rows) = along_axis(&alpha, false);
+            let (omega_frame, cols) = along_axis(&omega, true);
+            let mut data = Vec::with_capacity(rows.len() * cols.len());
+            for row in &rows {
+                for col in &cols {
+                    let len = match (row.len(), col.len()) {
+                        (1, n) | (n, 1) => n,
+                        (m, n) if m == n => n,
+                        _ => {
+                            return Err((
+                                span,
+                                ErrorKind::Length,
+                                "Inner product: lengths must match".into(),
+                            ));
+                        }
+                    };
+                    let pick = |items: &[Scalar], i: usize| {
+                        item_val(&items[if items.len() == 1 { 0 } else { i }])
+                    };
+                    let products = (0..len)
+                        .map(|i| {
+                            call_fn(span, "", g, Some(pick(row, i)), pick(col, i), env)
+                                .map(enclose_item)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let (last, rest) = products.split_last().ok_or((
+                        span,
+                        ErrorKind::Domain,
+                        "Inner product of empty arguments".into(),
+                    ))?;
+                    data.push(rest.iter().rev().try_fold(last.clone(), |acc, item| {
+                        call_fn(span, "", func, Some(item_val(item)), item_val(&acc), env)
+                            .map(enclose_item)
+                    })?);
+                }
+            }
+            let mut shape = alpha_frame;
+            shape.extend_from_slice(&omega_frame);
+            Ok(Val::new(shape, data))
+        }
+        (PrimOp::Compose, alpha) => {
+            let g = f.right.as_ref().ok_or((
+                span,
+                ErrorKind::Domain,
+                "Compose needs two operands".into(),
+            ))?;
+            let omega = call_fn(span, "", g, None, omega, env)?;
+            call_fn(span, "", func, alpha, omega, env)
+        }
+        (PrimOp::Over, alpha) => {
+            let g = f.right.as_ref().ok_or((
+                span,
+                ErrorKind::Domain,
+                "Over needs two operands".into(),
+            ))?;
+            let omega = call_fn(span, "", g, None, omega, env)?;
+            let alpha = alpha
+                .map(|alpha| call_fn(span, "", g, None, alpha, env))
+                .transpose()?;
+            call_fn(span, "", func, alpha, omega, env)
+        }
+        (PrimOp::Key, None) => {
+            // The unique items, each with the 1-based indices where it occurs
+            let mut keys: Vec<Scalar> = Vec::new();
+            let mut groups: Vec<Vec<Scalar>> = Vec::new();
+            for (i, item) in omega.data.iter().enumerate() {
+                let index = Scalar::Integer(i as i64 + 1);
+                match keys.iter().position(|key| key == item) {
+                    Some(pos) => groups[pos].push(index),
+                    None => {
+                        keys.push(item.clone());
+                        groups.push(vec![index]);
+                    }
+                }
+            }
+            let mut data = Vec::new();
+            for (key, indices) in keys.into_iter().zip(groups) {
+                let result = call_fn(
+                    span,
+                    "",
+                    func,
+                    Some(item_val(&key)),
+                    Val::vector(indices),
+                    env,
+                )?;
+                data.extend(result.data);
+            }
+            Ok(Val::vector(data))
+        }
+        (PrimOp::Key, Some(_)) => {
+            Err((span, ErrorKind::Syntax, "Key takes no left argument".into()))
+        }
+        (PrimOp::Outer | PrimOp::Inner, None) => Err((
+            span,
+            ErrorKind::Syntax,
+            "Function has no monadic form".into(),
+        )),
+    }
+}
+
+/// Apply `f@i`: `f` applied to each item of `omega` at the 1-based indices.
+fn call_at(
+    span: Span,
+    f: &At,
+    alpha: Option<Val>,
+    mut omega: Val,
+    env: &mut Env,
+) -> Result<Val, Error> {
+    if alpha.is_some() {
+        return Err((
+            span,
+            ErrorKind::Syntax,
+            "Function has no dyadic form".into(),
+        ));
+    }
+    for index in &f.indices.data {
+        let i = usize::try_from(index.clone())
+            .map_err(|_| (span, ErrorKind::Domain, "At index must be integer".into()))?;
+        if !(1..=omega.data.len()).contains(&i) {
+            return Err((span, ErrorKind::Index, "At index out of bounds".into()));
+        }
+        let result = call_fn(span, "", &f.func, None, item_val(&omega.data[i - 1]), env)?;
+        omega.data[i - 1] = enclose_item(result);
+    }
+    Ok(omega)
+}
+
+/// An item of an array as an array: the contents of a nested item, or a
+/// simple scalar.
+fn item_val(item: &Scalar) -> Val {
+    match item {
+        Scalar::Nested(v) => (**v).clone(),
+        s => Val::scalar(s.clone()),
+    }
+}
+
+/// A function result as an item of an array: scalars as they are, other
+/// arrays enclosed.
+fn enclose_item(val: Val) -> Scalar {
+    if val.shape.is_empty() && val.data.len() == 1 {
+        val.data.into_iter().next().unwrap()
+    } else {
+        Scalar::Nested(Box::new(val))
+    }
+}
+
+/// The 0-based axis a reduction or scan such as `f/[k]` works along, or
+/// `None` without an axis or for a scalar argument.
+fn derived_axis(f: &PrimDerived, val: &Val) -> Result<Option<usize>, (ErrorKind, &'static str)> {
+    let Some(axis) = &f.axis else {
+        return Ok(None);
+    };
+    match axis_index(axis)? {
+        _ if val.is_scalar() => Ok(None),
+        k if k < val.shape.len() => Ok(Some(k)),
+        _ => Err((ErrorKind::Index, "Axis out of range")),
+    }
+}
+
+/// The vectors along the last axis of `val`, or the first if `first`, with
+/// the shape of the remaining axes. A scalar or vector is a single vector.
+fn along_axis(val: &Val, first: bool) -> (Vec<usize>, Vec<Vec<Scalar>>) {
+    if val.shape.len() <= 1 {
+        return (vec![], vec![val.data.clone()]);
+    }
+    if !first {
+        let (frame, rows) = cells(val, 1);
+        return (frame, rows.into_iter().map(|row| row.data).collect());
+    }
+    let len = val.shape[0];
+    let stride = val.data.len() / len.max(1);
+    let cols = (0..stride)
+        .map(|col| {
+            (0..len)
+                .map(|row| val.data[row * stride + col].clone())
+                .collect()
+        })
+        .collect();
+    (val.shape[1..].to_vec(), cols)
+}
+
+/// The counts of `↑` or `↓` for the leading axes of `rhs`. A scalar `rhs`
+/// is treated as having one axis of length 1 per count.
+fn axis_counts(lhs: &Val, rhs: &Val) -> Option<Vec<i64>> {
+    let counts = lhs
+        .data
+        .iter()
+        .map(|n| match n {
+            Scalar::Integer(n) => Some(*n),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (lhs.shape.len() <= 1 && (rhs.is_scalar() || counts.len() <= rhs.shape.len())).then_some(counts)
+}
+
+/// `counts↑val`: the first `n` (or with `¯n`, the last `n`) along each
+/// leading axis, padded with the fill of `val` where `n` is larger than the
+/// axis. Axes without a count are kept whole.
+fn take(val: &Val, counts: &[i64]) -> Val {
+    let shape = if val.is_scalar() {
+        vec![1; counts.len()]
+    } else {
+        val.shape.clone()
+    };
+    let new_shape: Vec<usize> = shape
+        .iter()
+        .enumerate()
+        .map(|(axis, &len)| counts.get(axis).map_or(len, |n| n.unsigned_abs() as usize))
+        .collect();
+    // Where each result axis starts in the argument, which is negative
+    // when padding comes first
+    let offsets: Vec<i64> = shape
+        .iter()
+        .zip(&new_shape)
+        .enumerate()
+        .map(|(axis, (&len, &new_len))| match counts.get(axis) {
+            Some(n) if *n < 0 => len as i64 - new_len as i64,
+            _ => 0,
+        })
+        .collect();
+    let fill = val.fill();
+    let size: usize = new_shape.iter().product();
+    let mut index = vec![0; new_shape.len()];
+    let mut data = Vec::with_capacity(size);
+    for _ in 0..size {
+        let mut source = Some(0);
+        for (axis, &i) in index.iter().enumerate() {
+            let from = i as i64 + offsets[axis];
+            source = source
+                .filter(|_| (0..shape[axis] as i64).contains(&from))
+                .map(|s| s * shape[axis] + from as usize);
+        }
+        data.push(source.map_or_else(|| fill.clone(), |s| val.data[s].clone()));
+        // Advance the index, last axis fastest
+        for axis in (0..index.len()).rev() {
+            index[axis] += 1;
+            if index[axis] < new_shape[axis] {
+                break;
+            }
+            index[axis] = 0;
+        }
+    }
+    Val::new(new_shape, data)
+}
+
+/// `counts↓val`: dropping `n` is taking what is left from the other end.
+fn drop(val: &Val, counts: &[i64]) -> Val {
+    let counts: Vec<i64> = counts
+        .iter()
+        .zip(&val.shape)
+        .map(|(&n, &len)| {
+            let rest = (len as i64 - n.abs()).max(0);
+            if n >= 0 { -rest } else { rest }
+        })
+        .collect();
+    take(val, &counts)
+}
+
+/// `↑val`: the items of `val`, which must share a shape, as the trailing
+/// axes of an array framed by `val`.
+fn mix(val: Val) -> Val {
+    let cells: Vec<Val> = val
+        .data
+        .iter()
+        .filter_map(|s| match s {
+            Scalar::Nested(v) => Some((**v).clone()),
+            _ => None,
+        })
+        .collect();
+    if cells.is_empty() {
+        return val;
+    }
+    let mut shape = if cells.len() == val.data.len() {
+        val.shape
+    } else {
+        vec![cells.len()]
+    };
+    shape.extend_from_slice(&cells[0].shape);
+    let data = cells.into_iter().flat_map(|v| v.data).collect();
+    Val::new(shape, data)
 }
 
-impl Env {
-    pub fn new() -> Self {
-        Self::default()
+/// The numbers in an axis specification `[k]`, in APL's 1-based terms.
+fn axis_numbers(axis: &Val) -> Result<Vec<f64>, (ErrorKind, &'static str)> {
+    if axis.shape.len() > 1 {
+        return Err((ErrorKind::Domain, "Axis must be a scalar or vector"));
+    }
+    axis.data
+        .iter()
+        .map(|k| match k {
+            Scalar::Integer(k) => Ok(*k as f64),
+            Scalar::Float(k) => Ok(*k),
+            _ => Err((ErrorKind::Domain, "Axis must be numeric")),
+        })
+        .collect()
+}
+
+/// The 0-based axes named by `[k]` or `[j k]`, which must be whole numbers.
+fn axis_indices(axis: &Val) -> Result<Vec<usize>, (ErrorKind, &'static str)> {
+    axis_numbers(axis)?
+        .into_iter()
+        .map(|k| match k {
+            k if k.fract() == 0.0 && k >= 1.0 => Ok(k as usize - 1),
+            _ => Err((ErrorKind::Index, "Axis out of range")),
+        })
+        .collect()
+}
+
+/// The single 0-based axis named by `[k]`.
+fn axis_index(axis: &Val) -> Result<usize, (ErrorKind, &'static str)> {
+    match axis_indices(axis)?[..] {
+        [k] => Ok(k),
+        _ => Err((ErrorKind::Domain, "Axis must be a single number")),
+    }
+}
+
+/// A single fractional axis `[k]`, as the 0-based position `⌊k` where a new
+/// axis goes, or `None` for whole numbers.
+fn new_axis(axis: &Val) -> Result<Option<usize>, (ErrorKind, &'static str)> {
+    match axis_numbers(axis)?[..] {
+        [k] if k.fract() != 0.0 && k > 0.0 => Ok(Some(k.floor() as usize)),
+        [k] if k.fract() != 0.0 => Err((ErrorKind::Index, "Axis out of range")),
+        _ => Ok(None),
+    }
+}
+
+/// The counts of `counts↑[axes]` or `counts↓[axes]` for every axis of
+/// `val`, with `whole` for the axes not named.
+fn counts_on_axes(
+    counts: &Val,
+    axis: &Val,
+    val: &Val,
+    whole: impl Fn(usize) -> i64,
+) -> Result<Vec<i64>, (ErrorKind, &'static str)> {
+    let axes = axis_indices(axis)?;
+    if counts.shape.len() > 1 || counts.data.len() != axes.len() {
+        return Err((
+            ErrorKind::Length,
+            "Left argument must have one count per axis",
+        ));
+    }
+    let mut full: Vec<i64> = val.shape.iter().map(|&len| whole(len)).collect();
+    for (count, axis) in counts.data.iter().zip(axes) {
+        let Scalar::Integer(n) = count else {
+            return Err((ErrorKind::Domain, "Left argument must be integers"));
+        };
+        *full
+            .get_mut(axis)
+            .ok_or((ErrorKind::Index, "Axis out of range"))? = *n;
+    }
+    Ok(full)
+}
+
+/// `f[k]⍵` for a structural function or reduction `f`.
+fn axis_monadic(func: AxisFn, axis: &Val, val: Val) -> Result<Val, (ErrorKind, &'static str)> {
+    match func {
+        AxisFn::Rotate => axis::reverse(&val, axis_index(axis)?),
+        AxisFn::Catenate => match new_axis(axis)? {
+            Some(k) if k <= val.shape.len() => Ok(axis::insert_axis(&val, k)),
+            Some(_) => Err((ErrorKind::Index, "Axis out of range")),
+            None => axis::merge_axes(&val, &axis_indices(axis)?),
+        },
+        AxisFn::Take => {
+            let frame = val.shape.len();
+            let at = match new_axis(axis)? {
+                Some(k) => k,
+                None => axis_index(axis)?,
+            };
+            axis::place_item_axes(&mix(val), frame, at)
+        }
+        AxisFn::Drop => axis::split(&val, axis_index(axis)?),
+        AxisFn::Enclose => axis::enclose(&val, &axis_indices(axis)?),
+        AxisFn::Partition => Err((ErrorKind::Syntax, "Function has no monadic form")),
+        AxisFn::Reduce(_) | AxisFn::Scan(_) if val.is_scalar() => Ok(val),
+        AxisFn::Reduce(op) => axis::reduce(&val, axis_index(axis)?, get_operator_fn(op)),
+        AxisFn::Scan(op) => axis::scan(&val, axis_index(axis)?, get_operator_fn(op)),
+    }
+}
+
+/// `⍺ f[k] ⍵` for a structural function `f`.
+fn axis_dyadic(
+    lhs: Val,
+    func: AxisFn,
+    axis: &Val,
+    rhs: Val,
+) -> Result<Val, (ErrorKind, &'static str)> {
+    match func {
+        AxisFn::Rotate => axis::rotate(&lhs, &rhs, axis_index(axis)?),
+        AxisFn::Catenate => match new_axis(axis)? {
+            Some(k) => axis::laminate(&lhs, &rhs, k),
+            None => axis::catenate(&lhs, &rhs, axis_index(axis)?),
+        },
+        AxisFn::Take => {
+            let counts = counts_on_axes(&lhs, axis, &rhs, |len| len as i64)?;
+            Ok(take(&rhs, &counts))
+        }
+        AxisFn::Drop => {
+            let counts = counts_on_axes(&lhs, axis, &rhs, |_| 0)?;
+            Ok(drop(&rhs, &counts))
+        }
+        AxisFn::Enclose => axis::along(&rhs, axis_index(axis)?, |_, items| {
+            axis::partitioned_enclose(&lhs.data, items)
+        }),
+        AxisFn::Partition => axis::along(&rhs, axis_index(axis)?, |_, items| {
+            axis::partition(&lhs.data, items)
+        }),
+        AxisFn::Reduce(_) | AxisFn::Scan(_) => Err((
+            ErrorKind::Syntax,
+            "Reduction and scan take no left argument",
+        )),
+    }
+}
+
+/// The 1-based indices that put `count` items in order, keeping equal
+/// items in their original order.
+fn grade_by(count: usize, down: bool, order: impl Fn(usize, usize) -> Ordering) -> Val {
+    let mut indices: Vec<usize> = (0..count).collect();
+    indices.sort_by(|&a, &b| match down {
+        true => order(b, a),
+        false => order(a, b),
+    });
+    Val::vector(
+        indices
+            .into_iter()
+            .map(|i| Scalar::Integer(i as i64 + 1))
+            .collect(),
+    )
+}
+
+/// `collation⍋val`: grade the major cells of a character array by where
+/// each character is in `collation`, characters not in it coming last. With
+/// a matrix `collation`, cells are compared by column of `collation` first
+/// and rows only break ties, so `2 26⍴'ab…AB…'` sorts ignoring case.
+fn collated_grade(
+    collation: &Val,
+    val: &Val,
+    down: bool,
+) -> Result<Val, (ErrorKind, &'static str)> {
+    let shape = match collation.is_scalar() {
+        true => vec![1],
+        false => collation.shape.clone(),
+    };
+    // The index of a character along each axis of `collation`, last first
+    let key = |c: &Scalar| {
+        if !matches!(c, Scalar::Char(_)) {
+            return Err((
+                ErrorKind::Domain,
+                "Grade with a collation sequence needs characters",
+            ));
+        }
+        let mut index = match collation.data.iter().position(|x| x == c) {
+            Some(mut at) => {
+                let mut index = vec![0; shape.len()];
+                for (i, len) in index.iter_mut().zip(&shape).rev() {
+                    *i = at % len;
+                    at /= len;
+                }
+                index
+            }
+            None => shape.clone(),
+        };
+        index.reverse();
+        Ok(index)
+    };
+    let (_, major) = cells(val, val.shape.len().saturating_sub(1));
+    let keys = major
+        .iter()
+        .map(|cell| {
+            let items = cell.data.iter().map(key).collect::<Result<Vec<_>, _>>()?;
+            Ok((0..shape.len())
+                .map(|pass| items.iter().map(|item| item[pass]).collect())
+                .collect::<Vec<Vec<usize>>>())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(grade_by(keys.len(), down, |a, b| keys[a].cmp(&keys[b])))
+}
+
+/// Solve `a x = b` in the least-squares sense by Householder QR, for an
+/// `m`×`n` matrix `a` with `m ≥ n` and an `m`×`k` matrix `b`, both row-major.
+/// Returns `x`, `n`×`k`, or `None` if the columns of `a` are not independent.
+fn least_squares(
+    mut a: Vec<f64>,
+    m: usize,
+    n: usize,
+    mut b: Vec<f64>,
+    k: usize,
+) -> Option<Vec<f64>> {
+    let scale = a.iter().fold(0.0_f64, |max, x| max.max(x.abs()));
+    let tolerance = 1e-12 * scale;
+    for j in 0..n {
+        let norm = (j..m).map(|i| a[i * n + j].powi(2)).sum::<f64>().sqrt();
+        if norm <= tolerance {
+            return None;
+        }
+        // Reflect column j onto alpha times the unit vector
+        let alpha = if a[j * n + j] > 0.0 { -norm } else { norm };
+        let mut v: Vec<f64> = (j..m).map(|i| a[i * n + j]).collect();
+        v[0] -= alpha;
+        let v_norm2: f64 = v.iter().map(|x| x * x).sum();
+        let reflect = |data: &mut [f64], width: usize, col: usize| {
+            let dot: f64 = v
+                .iter()
+                .enumerate()
+                .map(|(r, x)| x * data[(j + r) * width + col])
+                .sum();
+            let factor = 2.0 * dot / v_norm2;
+            for (r, x) in v.iter().enumerate() {
+                data[(j + r) * width + col] -= factor * x;
+            }
+        };
+        for col in j..n {
+            reflect(&mut a, n, col);
+        }
+        for col in 0..k {
+            reflect(&mut b, k, col);
+        }
+    }
+    // Back-substitute through the upper triangle R
+    let mut x = vec![0.0_f64; n * k];
+    for col in 0..k {
+        for i in (0..n).rev() {
+            let sum: f64 = (i + 1..n).map(|j| a[i * n + j] * x[j * k + col]).sum();
+            x[i * k + col] = (b[i * k + col] - sum) / a[i * n + i];
+        }
+    }
+    Some(x)
+}
+
+/// The frame of `val` and its cells of rank `k` (or less, if `val` has
+/// lower rank).
+fn cells(val: &Val, k: usize) -> (Vec<usize>, Vec<Val>) {
+    let split = val.shape.len().saturating_sub(k);
+    let (frame, cell_shape) = val.shape.split_at(split);
+    let size: usize = cell_shape.iter().product();
+    let cells = (0..frame.iter().product::<usize>())
+        .map(|i| {
+            Val::new(
+                cell_shape.to_vec(),
+                val.data[i * size..(i + 1) * size].to_vec(),
+            )
+        })
+        .collect();
+    (frame.to_vec(), cells)
+}
+
+/// Reassemble the results for each cell of `frame`, taking the shape of the
+/// first result as the cell shape.
+fn from_cells(mut frame: Vec<usize>, results: Vec<Val>) -> Val {
+    if let Some(first) = results.first() {
+        frame.extend_from_slice(&first.shape);
+    }
+    Val::new(frame, results.into_iter().flat_map(|r| r.data).collect())
+}
+
+/// The function an operand expression denotes.
+fn fn_value(
+    lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>,
+    f: FnExpr,
+    env: &Env,
+) -> Result<StoredFn, Error> {
+    match f {
+        FnExpr::Primitive {
+            span,
+            monadic,
+            dyadic,
+        } => {
+            let form = |body: Box<Expr>| StoredDfn::new(Arc::new(*body), lexer, span);
+            Ok(StoredFn::Primitive(Arc::new(Primitive {
+                glyphs: lexer.span_str(span).trim().to_string(),
+                monadic: monadic.map(form),
+                dyadic: dyadic.map(form),
+            })))
+        }
+        FnExpr::Named { span, name } => {
+            env.get_fn(&name)
+                .ok_or((span, ErrorKind::Value, "Undefined function".into()))
+        }
+        FnExpr::Dfn { span, body } => {
+            Ok(StoredFn::Dfn(StoredDfn::new(Arc::new(*body), lexer, span)))
+        }
+        FnExpr::Derived {
+            span,
+            operator,
+            left,
+            right,
+        } => {
+            let operator = match env.get_fn(&operator) {
+                Some(StoredFn::Dfn(dop)) if dop.is_operator() => dop,
+                Some(_) => return Err((span, ErrorKind::Domain, "Not an operator".into())),
+                None => return Err((span, ErrorKind::Value, "Undefined operator".into())),
+            };
+            if right.is_some() != operator.is_dyadic_operator() {
+                return Err((span, ErrorKind::Syntax, "Wrong number of operands".into()));
+            }
+            let left = fn_value(lexer, *left, env)?;
+            let right = right.map(|f| fn_value(lexer, *f, env)).transpose()?;
+            Ok(StoredFn::Derived(Arc::new(Derived {
+                operator,
+                left,
+                right,
+                source: lexer.span_str(span).to_string(),
+            })))
+        }
+        FnExpr::Rank { span, func, rank } => Ok(StoredFn::Rank(Arc::new(Rank {
+            func: fn_value(lexer, *func, env)?,
+            rank: count_operand(
+                lexer,
+                *rank,
+                env,
+                span,
+                "Rank must be a non-negative integer",
+            )?,
+            source: lexer.span_str(span).to_string(),
+        }))),
+        FnExpr::Power { span, func, count } => Ok(StoredFn::Power(Arc::new(Power {
+            func: fn_value(lexer, *func, env)?,
+            count: count_operand(
+                lexer,
+                *count,
+                env,
+                span,
+                "Power operator count must be a non-negative integer",
+            )?,
+            source: lexer.span_str(span).to_string(),
+        }))),
+        FnExpr::PrimDerived {
+            span,
+            operator,
+            left,
+            right,
+            axis,
+        } => Ok(StoredFn::PrimDerived(Arc::new(PrimDerived {
+            operator,
+            left: fn_value(lexer, *left, env)?,
+            right: right.map(|f| fn_value(lexer, *f, env)).transpose()?,
+            axis: axis.map(|k| eval(lexer, *k, &mut env.fork())).transpose()?,
+            source: lexer.span_str(span).to_string(),
+        }))),
+        FnExpr::At {
+            span,
+            func,
+            indices,
+        } => Ok(StoredFn::At(Arc::new(At {
+            func: fn_value(lexer, *func, env)?,
+            indices: eval(lexer, *indices, &mut env.fork())?,
+            source: lexer.span_str(span).to_string(),
+        }))),
+        FnExpr::Bind { span, left, func } => Ok(StoredFn::Bind(Arc::new(Bind {
+            left: eval(lexer, *left, &mut env.fork())?,
+            func: fn_value(lexer, *func, env)?,
+            source: lexer.span_str(span).to_string(),
+        }))),
+        FnExpr::Array { span, .. } => {
+            Err((span, ErrorKind::Syntax, "An array is not a function".into()))
+        }
+        FnExpr::Train { span, fns } => {
+            let tines = fns
+                .into_iter()
+                .map(|f| match f {
+                    FnExpr::Array { value, .. } => {
+                        eval(lexer, *value, &mut env.fork()).map(Tine::Array)
+                    }
+                    f => fn_value(lexer, f, env).map(Tine::Fn),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(StoredFn::Train(Arc::new(Train {
+                tines,
+                source: lexer.span_str(span).to_string(),
+            })))
+        }
     }
 }
 
-fn eval_stored_dfn(stored: &StoredDfn, env: &mut Env) -> Result<Val, (Span, String)> {
-    use crate::parse::apiel_l;
-    let lexerdef = apiel_l::lexerdef();
-    let lex = lexerdef.lexer(&stored.source);
-    eval(&lex, (*stored.body).clone(), env).map_err(|(span, msg)| (span, msg.to_string()))
+/// The non-negative integer right operand of `⍤` or `⍣`.
+fn count_operand(
+    lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>,
+    operand: Expr,
+    env: &Env,
+    span: Span,
+    message: &'static str,
+) -> Result<usize, Error> {
+    let val = eval(lexer, operand, &mut env.fork())?;
+    match val.data.as_slice() {
+        [n] => n
+            .clone()
+            .try_into()
+            .map_err(|_| (span, ErrorKind::Domain, message.into())),
+        _ => Err((span, ErrorKind::Domain, message.into())),
+    }
 }
 
-fn apply_dyadic_operation<F>(
+/// The function defined by `name←func`, whose source is `span`.
+pub(crate) fn tacit_fn(
+    lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>,
     span: Span,
-    lhs: &Val,
-    rhs: &Val,
-    operation: F,
-) -> Result<Val, (Span, &'static str)>
+    func: FnExpr,
+    env: &Env,
+) -> Result<StoredFn, Error> {
+    Ok(StoredFn::Tacit(Arc::new(Tacit {
+        func: fn_value(lexer, func, env)?,
+        source: lexer.span_str(span).to_string(),
+    })))
+}
+
+fn apply_dyadic_operation<F>(span: Span, lhs: &Val, rhs: &Val, operation: F) -> Result<Val, Error>
 where
     F: Fn(&Scalar, &Scalar) -> Result<Scalar>,
 {
@@ -51,7 +1614,7 @@ where
             .iter()
             .map(|r| operation(&lhs.data[0], r))
             .collect::<Result<Vec<Scalar>, _>>()
-            .map_err(|_| (span, "Operation failed"))?;
+            .map_err(|_| (span, ErrorKind::Domain, "Operation failed".into()))?;
         Ok(Val::new(rhs.shape.clone(), data))
     } else if rhs_scalar && !lhs_scalar {
         let data = lhs
@@ -59,7 +1622,7 @@ where
             .iter()
             .map(|l| operation(l, &rhs.data[0]))
             .collect::<Result<Vec<Scalar>, _>>()
-            .map_err(|_| (span, "Operation failed"))?;
+            .map_err(|_| (span, ErrorKind::Domain, "Operation failed".into()))?;
         Ok(Val::new(lhs.shape.clone(), data))
     } else if lhs.shape == rhs.shape || (lhs_scalar && rhs_scalar) {
         let data = lhs
@@ -68,21 +1631,18 @@ where
             .zip(rhs.data.iter())
             .map(|(l, r)| operation(l, r))
             .collect::<Result<Vec<Scalar>, _>>()
-            .map_err(|_| (span, "Operation failed"))?;
+            .map_err(|_| (span, ErrorKind::Domain, "Operation failed".into()))?;
         Ok(Val::new(lhs.shape.clone(), data))
     } else {
         Err((
             Span::new(0, 0),
-            "operands must be of the same shape or one must be scalar",
+            ErrorKind::Length,
+            "operands must be of the same shape or one must be scalar".into(),
         ))
     }
 }
 
-fn apply_monadic_operation<F>(
-    span: Span,
-    arg: &Val,
-    operation: F,
-) -> Result<Val, (Span, &'static str)>
+fn apply_monadic_operation<F>(span: Span, arg: &Val, operation: F) -> Result<Val, Error>
 where
     F: Fn(&Scalar) -> Result<Scalar>,
 {
@@ -91,7 +1651,7 @@ where
         .iter()
         .map(operation)
         .collect::<Result<Vec<Scalar>>>()
-        .map_err(|_| (span, "Operation failed"))?;
+        .map_err(|_| (span, ErrorKind::Domain, "Operation failed".into()))?;
     Ok(Val::new(arg.shape.clone(), data))
 }
 
@@ -178,7 +1738,7 @@ pub fn eval(
     lexer: &dyn NonStreamingLexer<DefaultLexerTypes<u32>>,
     e: Expr,
     env: &mut Env,
-) -> Result<Val, (Span, &'static str)> {
+) -> Result<Val, Error> {
     match e {
         Expr::Add { span, lhs, rhs } => {
             debug!("Dyadic Add");
@@ -326,17 +1886,37 @@ pub fn eval(
             let rhs_eval = eval(lexer, *rhs, env)?;
 
             if !lhs_eval.is_scalar() || !rhs_eval.is_scalar() {
-                return Err((span, "Deal operation is only available for two scalars"));
+                return Err((
+                    span,
+                    ErrorKind::Domain,
+                    "Deal operation is only available for two scalars".into(),
+                ));
             }
 
-            let (lhs, rhs) = match (&lhs_eval.data[0], &rhs_eval.data[0]) {
-                (Scalar::Integer(lhs), Scalar::Integer(rhs)) => (*lhs, *rhs),
-                _ => return Err((span, "Deal arguments must be integers")),
+            let (count, limit) = match (&lhs_eval.data[0], &rhs_eval.data[0]) {
+                (Scalar::Integer(lhs), Scalar::Integer(rhs)) if *lhs >= 0 && *rhs >= 0 => {
+                    (*lhs as usize, *rhs as usize)
+                }
+                _ => {
+                    return Err((
+                        span,
+                        ErrorKind::Domain,
+                        "Deal arguments must be non-negative integers".into(),
+                    ));
+                }
             };
+            if count > limit {
+                return Err((
+                    span,
+                    ErrorKind::Domain,
+                    "Deal left argument must not exceed the right".into(),
+                ));
+            }
 
-            let mut rng = rand::thread_rng();
-            let data: Vec<Scalar> = (0..lhs)
-                .map(|_| Scalar::Integer(rng.gen_range(0..=rhs)))
+            // k?n: k distinct integers drawn from ⍳n
+            let data: Vec<Scalar> = rand::seq::index::sample(&mut *env.rng(), limit, count)
+                .into_iter()
+                .map(|i| Scalar::Integer(i as i64 + 1))
                 .collect();
             Ok(Val::vector(data))
         }
@@ -409,8 +1989,13 @@ pub fn eval(
                 .data
                 .iter()
                 .map(|s| {
-                    usize::try_from(s.clone())
-                        .map_err(|_| (span, "Reshape dimensions must be non-negative integers"))
+                    usize::try_from(s.clone()).map_err(|_| {
+                        (
+                            span,
+                            ErrorKind::Domain,
+                            "Reshape dimensions must be non-negative integers".into(),
+                        )
+                    })
                 })
                 .collect::<Result<Vec<usize>, _>>()?;
 
@@ -423,33 +2008,21 @@ pub fn eval(
             debug!("Dyadic Catenate");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
-            let _ = span;
-
-            let mut data = lhs_eval.data;
-            data.extend(rhs_eval.data);
-            Ok(Val::vector(data))
+            let last = lhs_eval.shape.len().max(rhs_eval.shape.len()).max(1) - 1;
+            axis::catenate(&lhs_eval, &rhs_eval, last).map_err(at(span))
+        }
+        Expr::CatenateFirst { span, lhs, rhs } => {
+            debug!("Dyadic Catenate First");
+            let lhs_eval = eval(lexer, *lhs, env)?;
+            let rhs_eval = eval(lexer, *rhs, env)?;
+            axis::catenate(&lhs_eval, &rhs_eval, 0).map_err(at(span))
         }
         Expr::Rotate { span, lhs, rhs } => {
             debug!("Dyadic Rotate");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
-
-            if !lhs_eval.is_scalar() {
-                return Err((span, "Rotate left argument must be a scalar integer"));
-            }
-            let n = match &lhs_eval.data[0] {
-                Scalar::Integer(i) => *i,
-                _ => return Err((span, "Rotate left argument must be an integer")),
-            };
-
-            let len = rhs_eval.data.len();
-            if len == 0 {
-                return Ok(rhs_eval);
-            }
-            let rot = ((n % len as i64) + len as i64) as usize % len;
-            let mut data = rhs_eval.data;
-            data.rotate_left(rot);
-            Ok(Val::new(rhs_eval.shape, data))
+            let last = rhs_eval.shape.len().saturating_sub(1);
+            axis::rotate(&lhs_eval, &rhs_eval, last).map_err(at(span))
         }
         Expr::Equal { span, lhs, rhs } => {
             debug!("Dyadic Equal");
@@ -548,7 +2121,13 @@ pub fn eval(
                 // Scalar left: repeat each element n times
                 let n = match lhs_eval.data[0] {
                     Scalar::Integer(i) if i >= 0 => i as usize,
-                    _ => return Err((span, "Replicate count must be a non-negative integer")),
+                    _ => {
+                        return Err((
+                            span,
+                            ErrorKind::Domain,
+                            "Replicate count must be a non-negative integer".into(),
+                        ));
+                    }
                 };
                 let data: Vec<Scalar> = rhs_eval
                     .data
@@ -560,7 +2139,8 @@ pub fn eval(
                 if lhs_eval.data.len() != rhs_eval.data.len() {
                     return Err((
                         span,
-                        "Replicate: left and right arguments must have same length",
+                        ErrorKind::Length,
+                        "Replicate: left and right arguments must have same length".into(),
                     ));
                 }
                 let data: Vec<Scalar> = lhs_eval
@@ -599,7 +2179,13 @@ pub fn eval(
                                 data.push(v.clone());
                             }
                         }
-                        None => return Err((span, "Expand: not enough data elements")),
+                        None => {
+                            return Err((
+                                span,
+                                ErrorKind::Length,
+                                "Expand: not enough data elements".into(),
+                            ));
+                        }
                     }
                 } else {
                     data.push(Scalar::Integer(0));
@@ -640,61 +2226,28 @@ pub fn eval(
             debug!("Dyadic Take");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
-
-            if !lhs_eval.is_scalar() {
-                return Err((span, "Take left argument must be a scalar integer"));
-            }
-            let n = match lhs_eval.data[0] {
-                Scalar::Integer(i) => i,
-                _ => return Err((span, "Take left argument must be an integer")),
-            };
-
-            let len = rhs_eval.data.len();
-            let abs_n = n.unsigned_abs() as usize;
-            let mut data = if n >= 0 {
-                let mut d: Vec<Scalar> = rhs_eval.data.iter().take(abs_n).cloned().collect();
-                while d.len() < abs_n {
-                    d.push(Scalar::Integer(0));
-                }
-                d
-            } else {
-                let skip = len.saturating_sub(abs_n);
-                let mut d: Vec<Scalar> = rhs_eval.data.iter().skip(skip).cloned().collect();
-                while d.len() < abs_n {
-                    d.insert(0, Scalar::Integer(0));
-                }
-                d
-            };
-            let _ = &mut data;
-            Ok(Val::vector(data))
+            let counts = axis_counts(&lhs_eval, &rhs_eval).ok_or((
+                span,
+                ErrorKind::Domain,
+                "Take left argument must be integers, one per axis".into(),
+            ))?;
+            Ok(take(&rhs_eval, &counts))
         }
         Expr::Drop { span, lhs, rhs } => {
             debug!("Dyadic Drop");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
-
-            if !lhs_eval.is_scalar() {
-                return Err((span, "Drop left argument must be a scalar integer"));
-            }
-            let n = match lhs_eval.data[0] {
-                Scalar::Integer(i) => i,
-                _ => return Err((span, "Drop left argument must be an integer")),
-            };
-
-            let len = rhs_eval.data.len();
-            let data: Vec<Scalar> = if n >= 0 {
-                let skip = (n as usize).min(len);
-                rhs_eval.data.into_iter().skip(skip).collect()
-            } else {
-                let take = len.saturating_sub(n.unsigned_abs() as usize);
-                rhs_eval.data.into_iter().take(take).collect()
-            };
-            Ok(Val::vector(data))
+            let counts = axis_counts(&lhs_eval, &rhs_eval).ok_or((
+                span,
+                ErrorKind::Domain,
+                "Drop left argument must be integers, one per axis".into(),
+            ))?;
+            Ok(drop(&rhs_eval, &counts))
         }
-        Expr::Assign { name, rhs, .. } => {
+        Expr::Assign { span, name, rhs } => {
             debug!("Assignment");
             let val = eval(lexer, *rhs, env)?;
-            env.vars.insert(name, val.clone());
+            env.set_var(&name, val.clone()).map_err(at(span))?;
             Ok(val)
         }
         Expr::ModifiedAssign {
@@ -704,17 +2257,17 @@ pub fn eval(
             rhs,
         } => {
             debug!("Modified Assign: {name}");
-            let current = env
-                .vars
-                .get(&name)
-                .cloned()
-                .ok_or((span, "Undefined variable for modified assignment"))?;
+            let current = env.get_var(&name).ok_or((
+                span,
+                ErrorKind::Value,
+                "Undefined variable for modified assignment".into(),
+            ))?;
             let rhs_eval = eval(lexer, *rhs, env)?;
             let op_fn = get_operator_fn(operator);
             let result = apply_dyadic_operation(span, &current, &rhs_eval, |a, b| {
                 op_fn(a, b).ok_or_eyre("Modified assignment operation failed")
             })?;
-            env.vars.insert(name, result.clone());
+            env.update_var(&name, result.clone()).map_err(at(span))?;
             Ok(result)
         }
         Expr::IndexedAssign {
@@ -724,45 +2277,34 @@ pub fn eval(
             rhs,
         } => {
             debug!("Indexed Assign: {name}");
-            let mut current = env
-                .vars
-                .get(&name)
-                .cloned()
-                .ok_or((span, "Undefined variable for indexed assignment"))?;
-            let idx_val = eval(lexer, *indices, env)?;
+            let mut current = env.get_var(&name).ok_or((
+                span,
+                ErrorKind::Value,
+                "Undefined variable for indexed assignment".into(),
+            ))?;
+            let indices = eval_indices(lexer, indices, env)?;
             let rhs_val = eval(lexer, *rhs, env)?;
-
-            let idxs: Vec<usize> = idx_val
-                .data
-                .iter()
-                .map(|s| {
-                    let i: usize = s
-                        .clone()
-                        .try_into()
-                        .map_err(|_| (span, "Index must be integer"))?;
-                    if i < 1 || i > current.data.len() {
-                        return Err((span, "Index out of bounds"));
-                    }
-                    Ok(i - 1) // 1-based to 0-based
-                })
-                .collect::<Result<Vec<_>, _>>()?;
+            let (_, positions) = index_positions(span, &current.shape, indices)?;
 
             if rhs_val.is_scalar() {
                 // Scalar: set all indexed positions to same value
-                for &idx in &idxs {
-                    current.data[idx] = rhs_val.data[0].clone();
+                for &i in &positions {
+                    current.data[i] = rhs_val.data[0].clone();
                 }
             } else {
-                // Vector: must match length
-                if rhs_val.data.len() != idxs.len() {
-                    return Err((span, "Indexed assign: value length must match index count"));
+                if rhs_val.data.len() != positions.len() {
+                    return Err((
+                        span,
+                        ErrorKind::Length,
+                        "Indexed assign: value length must match index count".into(),
+                    ));
                 }
-                for (i, &idx) in idxs.iter().enumerate() {
-                    current.data[idx] = rhs_val.data[i].clone();
+                for (&i, value) in positions.iter().zip(rhs_val.data) {
+                    current.data[i] = value;
                 }
             }
 
-            env.vars.insert(name, current.clone());
+            env.update_var(&name, current.clone()).map_err(at(span))?;
             Ok(current)
         }
         Expr::OuterProduct {
@@ -784,7 +2326,13 @@ pub fn eval(
                 for r in &rhs_eval.data {
                     match op_fn(l, r) {
                         Some(v) => data.push(v),
-                        None => return Err((span, "Outer product operation failed")),
+                        None => {
+                            return Err((
+                                span,
+                                ErrorKind::Domain,
+                                "Outer product operation failed".into(),
+                            ));
+                        }
                     }
                 }
             }
@@ -898,15 +2446,14 @@ pub fn eval(
             debug!("Monadic Roll");
             let arg_eval = eval(lexer, *arg, env)?;
 
-            let roll_operation = |limit: &Scalar| {
-                let mut rng = rand::thread_rng();
-
-                match limit {
-                    Scalar::Integer(val) if *val == 0 => Ok(Scalar::Integer(rng.r#gen())),
-                    Scalar::Integer(val) => Ok(Scalar::Integer(rng.gen_range(0..=*val))),
-                    _ => {
-                        eyre::bail!("Roll right argument must consist of non-negative integer(s)")
-                    }
+            // ?0 is a float in (0,1); ?n is an integer in ⍳n
+            let roll_operation = |limit: &Scalar| match limit {
+                Scalar::Integer(0) => Ok(Scalar::Float(env.rng().sample(Open01))),
+                Scalar::Integer(val) if *val > 0 => {
+                    Ok(Scalar::Integer(env.rng().gen_range(1..=*val)))
+                }
+                _ => {
+                    eyre::bail!("Roll right argument must consist of non-negative integer(s)")
                 }
             };
 
@@ -956,7 +2503,7 @@ pub fn eval(
                 .data
                 .iter()
                 .max()
-                .ok_or((span, "Cannot find max"))
+                .ok_or((span, ErrorKind::Domain, "Cannot find max".into()))
                 .map(|num| Val::scalar(num.clone()))
         }
         Expr::MonadicMin { span, arg } => {
@@ -967,7 +2514,7 @@ pub fn eval(
                 .data
                 .iter()
                 .min()
-                .ok_or((span, "Cannot find min"))
+                .ok_or((span, ErrorKind::Domain, "Cannot find min".into()))
                 .map(|num| Val::scalar(num.clone()))
         }
         Expr::GenIndex { span, arg } => {
@@ -975,7 +2522,11 @@ pub fn eval(
             let arg_eval = eval(lexer, *arg, env)?;
 
             if !arg_eval.is_scalar() {
-                return Err((span, "Generate index only accepts a scalar integer"));
+                return Err((
+                    span,
+                    ErrorKind::Domain,
+                    "Generate index only accepts a scalar integer".into(),
+                ));
             }
 
             match arg_eval.data[0] {
@@ -985,7 +2536,9 @@ pub fn eval(
                 }
                 _ => Err((
                     span,
-                    "Generate index only accepts non-negative integer values as right operand",
+                    ErrorKind::Domain,
+                    "Generate index only accepts non-negative integer values as right operand"
+                        .into(),
                 )),
             }
         }
@@ -1025,132 +2578,113 @@ pub fn eval(
             let arg_eval = eval(lexer, *arg, env)?;
             Ok(Val::vector(arg_eval.data))
         }
-        Expr::Reverse { arg, .. } => {
+        Expr::Table { arg, .. } => {
+            debug!("Monadic Table");
+            let arg_eval = eval(lexer, *arg, env)?;
+            let rows = arg_eval.shape.first().copied().unwrap_or(1);
+            let cols = arg_eval.shape.iter().skip(1).product();
+            Ok(Val::new(vec![rows, cols], arg_eval.data))
+        }
+        Expr::Reverse { span, arg } => {
             debug!("Monadic Reverse");
             let arg_eval = eval(lexer, *arg, env)?;
-            let mut data = arg_eval.data;
-            data.reverse();
-            Ok(Val::new(arg_eval.shape, data))
+            let last = arg_eval.shape.len().saturating_sub(1);
+            axis::reverse(&arg_eval, last).map_err(at(span))
+        }
+        Expr::AxisMonadic {
+            span,
+            func,
+            axis,
+            arg,
+        } => {
+            debug!("Monadic with axis");
+            let arg_eval = eval(lexer, *arg, env)?;
+            let axis_eval = eval(lexer, *axis, env)?;
+            axis_monadic(func, &axis_eval, arg_eval).map_err(at(span))
+        }
+        Expr::AxisDyadic {
+            span,
+            lhs,
+            func,
+            axis,
+            rhs,
+        } => {
+            debug!("Dyadic with axis");
+            let rhs_eval = eval(lexer, *rhs, env)?;
+            let axis_eval = eval(lexer, *axis, env)?;
+            let lhs_eval = eval(lexer, *lhs, env)?;
+            axis_dyadic(lhs_eval, func, &axis_eval, rhs_eval).map_err(at(span))
         }
         Expr::Transpose { arg, .. } => {
             debug!("Monadic Transpose");
             let arg_eval = eval(lexer, *arg, env)?;
-            match arg_eval.shape.len() {
-                0 | 1 => Ok(arg_eval),
-                2 => {
-                    let rows = arg_eval.shape[0];
-                    let cols = arg_eval.shape[1];
-                    let mut data = vec![Scalar::Integer(0); rows * cols];
-                    for r in 0..rows {
-                        for c in 0..cols {
-                            data[c * rows + r] = arg_eval.data[r * cols + c].clone();
-                        }
-                    }
-                    Ok(Val::new(vec![cols, rows], data))
-                }
-                _ => Err((Span::new(0, 0), "Transpose only supports rank 0, 1, or 2")),
-            }
+            let perm: Vec<usize> = (0..arg_eval.shape.len()).rev().collect();
+            Ok(axis::permute(&arg_eval, &perm))
         }
         Expr::DyadicTranspose { span, lhs, rhs } => {
             debug!("Dyadic Transpose");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
 
-            // Parse permutation vector (1-based to 0-based)
-            let perm: Vec<usize> = lhs_eval
+            // Where each axis goes (1-based to 0-based)
+            let targets: Vec<usize> = lhs_eval
                 .data
                 .iter()
                 .map(|s| {
-                    let v: usize = s
-                        .clone()
-                        .try_into()
-                        .map_err(|_| (span, "Transpose perm must be integers"))?;
+                    let v: usize = s.clone().try_into().map_err(|_| {
+                        (
+                            span,
+                            ErrorKind::Domain,
+                            "Transpose perm must be integers".into(),
+                        )
+                    })?;
                     if v < 1 || v > rhs_eval.shape.len() {
-                        return Err((span, "Transpose permutation out of range"));
+                        return Err((
+                            span,
+                            ErrorKind::Index,
+                            "Transpose permutation out of range".into(),
+                        ));
                     }
                     Ok(v - 1)
                 })
                 .collect::<Result<Vec<_>, _>>()?;
 
-            if perm.len() != rhs_eval.shape.len() {
-                return Err((span, "Transpose permutation length must match array rank"));
-            }
-
-            let old_shape = &rhs_eval.shape;
-            let rank = old_shape.len();
-
-            // New shape: new_shape[perm[i]] = old_shape[i]
-            let mut new_shape = vec![0usize; rank];
-            for i in 0..rank {
-                new_shape[perm[i]] = old_shape[i];
-            }
-
-            let total: usize = new_shape.iter().product();
-            let mut new_data = vec![Scalar::Integer(0); total];
-
-            // Compute strides for old shape
-            let mut old_strides = vec![1usize; rank];
-            for i in (0..rank - 1).rev() {
-                old_strides[i] = old_strides[i + 1] * old_shape[i + 1];
-            }
-
-            // Compute strides for new shape
-            let mut new_strides = vec![1usize; rank];
-            for i in (0..rank - 1).rev() {
-                new_strides[i] = new_strides[i + 1] * new_shape[i + 1];
-            }
-
-            // For each element in old array, compute its new position
-            for old_flat in 0..total {
-                // Convert flat index to multi-dimensional old index
-                let mut old_idx = vec![0usize; rank];
-                let mut remaining = old_flat;
-                for i in 0..rank {
-                    old_idx[i] = remaining / old_strides[i];
-                    remaining %= old_strides[i];
-                }
-
-                // Apply permutation: new_idx[perm[i]] = old_idx[i]
-                let mut new_idx = vec![0usize; rank];
-                for i in 0..rank {
-                    new_idx[perm[i]] = old_idx[i];
-                }
-
-                // Convert new multi-dimensional index to flat
-                let new_flat: usize = new_idx
-                    .iter()
-                    .zip(new_strides.iter())
-                    .map(|(&i, &s)| i * s)
-                    .sum();
-
-                new_data[new_flat] = rhs_eval.data[old_flat].clone();
+            if targets.len() != rhs_eval.shape.len() {
+                return Err((
+                    span,
+                    ErrorKind::Length,
+                    "Transpose permutation length must match array rank".into(),
+                ));
             }
-
-            Ok(Val::new(new_shape, new_data))
+            axis::transpose(&rhs_eval, &targets).map_err(at(span))
         }
-        Expr::GradeUp { span, arg } => {
+        Expr::GradeUp { arg, .. } => {
             debug!("Monadic Grade Up");
             let arg_eval = eval(lexer, *arg, env)?;
-            let _ = span;
-            let mut indices: Vec<usize> = (0..arg_eval.data.len()).collect();
-            indices.sort_by(|&a, &b| arg_eval.data[a].cmp(&arg_eval.data[b]));
-            let data: Vec<Scalar> = indices
-                .iter()
-                .map(|&i| Scalar::Integer(i as i64 + 1))
-                .collect();
-            Ok(Val::vector(data))
+            let (_, major) = cells(&arg_eval, arg_eval.shape.len().saturating_sub(1));
+            Ok(grade_by(major.len(), false, |a, b| {
+                major[a].tao_cmp(&major[b])
+            }))
         }
-        Expr::GradeDown { span, arg } => {
+        Expr::GradeDown { arg, .. } => {
             debug!("Monadic Grade Down");
             let arg_eval = eval(lexer, *arg, env)?;
-            let _ = span;
-            let mut indices: Vec<usize> = (0..arg_eval.data.len()).collect();
-            indices.sort_by(|&a, &b| arg_eval.data[b].cmp(&arg_eval.data[a]));
-            let data: Vec<Scalar> = indices
-                .iter()
-                .map(|&i| Scalar::Integer(i as i64 + 1))
-                .collect();
-            Ok(Val::vector(data))
+            let (_, major) = cells(&arg_eval, arg_eval.shape.len().saturating_sub(1));
+            Ok(grade_by(major.len(), true, |a, b| {
+                major[a].tao_cmp(&major[b])
+            }))
+        }
+        Expr::DyadicGradeUp { span, lhs, rhs } => {
+            debug!("Dyadic Grade Up");
+            let rhs_eval = eval(lexer, *rhs, env)?;
+            let lhs_eval = eval(lexer, *lhs, env)?;
+            collated_grade(&lhs_eval, &rhs_eval, false).map_err(at(span))
+        }
+        Expr::DyadicGradeDown { span, lhs, rhs } => {
+            debug!("Dyadic Grade Down");
+            let rhs_eval = eval(lexer, *rhs, env)?;
+            let lhs_eval = eval(lexer, *lhs, env)?;
+            collated_grade(&lhs_eval, &rhs_eval, true).map_err(at(span))
         }
         Expr::Reduce {
             span,
@@ -1176,9 +2710,11 @@ pub fn eval(
                         Some(right) => op_fn(&n, &right).map(Some),
                     })
                     .flatten();
-                result
-                    .map(Val::scalar)
-                    .ok_or((span, "Arithmetic error or invalid operation in Reduce"))
+                result.map(Val::scalar).ok_or((
+                    span,
+                    ErrorKind::Domain,
+                    "Arithmetic error or invalid operation in Reduce".into(),
+                ))
             } else {
                 // Higher-rank: reduce along last axis
                 let last_dim = *term_eval.shape.last().unwrap();
@@ -1196,7 +2732,7 @@ pub fn eval(
                             Some(right) => op_fn(&n, &right).map(Some),
                         })
                         .flatten()
-                        .ok_or((span, "Arithmetic error in Reduce"))?;
+                        .ok_or((span, ErrorKind::Domain, "Arithmetic error in Reduce".into()))?;
                     results.push(result);
                 }
                 let new_shape = term_eval.shape[..term_eval.shape.len() - 1].to_vec();
@@ -1214,26 +2750,12 @@ pub fn eval(
         } => {
             debug!("Scan");
             let term_eval = eval(lexer, *term, env)?;
-
-            let op_fn = get_operator_fn(operator);
-
-            // Scan: each element i is the right-fold reduce of prefix 0..=i
-            let mut data = Vec::with_capacity(term_eval.data.len());
-            for i in 0..term_eval.data.len() {
-                let prefix = &term_eval.data[..=i];
-                let result = prefix
-                    .iter()
-                    .rev()
-                    .cloned()
-                    .try_fold(None::<Scalar>, |acc, n| match acc {
-                        None => Some(Some(n)),
-                        Some(right) => op_fn(&n, &right).map(Some),
-                    })
-                    .flatten()
-                    .ok_or((span, "Arithmetic error in Scan"))?;
-                data.push(result);
-            }
-            Ok(Val::vector(data))
+            let term_eval = match term_eval.is_scalar() {
+                true => Val::vector(term_eval.data),
+                false => term_eval,
+            };
+            let last = term_eval.shape.len() - 1;
+            axis::scan(&term_eval, last, get_operator_fn(operator)).map_err(at(span))
         }
         Expr::ReduceFirst {
             span,
@@ -1256,9 +2778,11 @@ pub fn eval(
                         Some(right) => op_fn(&n, &right).map(Some),
                     })
                     .flatten();
-                result
-                    .map(Val::scalar)
-                    .ok_or((span, "Arithmetic error in ReduceFirst"))
+                result.map(Val::scalar).ok_or((
+                    span,
+                    ErrorKind::Domain,
+                    "Arithmetic error in ReduceFirst".into(),
+                ))
             } else {
                 // Higher-rank: reduce along FIRST axis (columns)
                 let first_dim = term_eval.shape[0];
@@ -1277,7 +2801,11 @@ pub fn eval(
                             Some(right) => op_fn(&n, &right).map(Some),
                         })
                         .flatten()
-                        .ok_or((span, "Arithmetic error in ReduceFirst"))?;
+                        .ok_or((
+                            span,
+                            ErrorKind::Domain,
+                            "Arithmetic error in ReduceFirst".into(),
+                        ))?;
                     results.push(result);
                 }
                 let new_shape = term_eval.shape[1..].to_vec();
@@ -1311,7 +2839,11 @@ pub fn eval(
                             Some(right) => op_fn(&n, &right).map(Some),
                         })
                         .flatten()
-                        .ok_or((span, "Arithmetic error in ScanFirst"))?;
+                        .ok_or((
+                            span,
+                            ErrorKind::Domain,
+                            "Arithmetic error in ScanFirst".into(),
+                        ))?;
                     data.push(result);
                 }
                 Ok(Val::vector(data))
@@ -1324,8 +2856,11 @@ pub fn eval(
                     for row in 1..first_dim {
                         let prev = data[(row - 1) * stride + col].clone();
                         let curr = data[row * stride + col].clone();
-                        data[row * stride + col] =
-                            op_fn(&prev, &curr).ok_or((span, "Arithmetic error in ScanFirst"))?;
+                        data[row * stride + col] = op_fn(&prev, &curr).ok_or((
+                            span,
+                            ErrorKind::Domain,
+                            "Arithmetic error in ScanFirst".into(),
+                        ))?;
                     }
                 }
                 Ok(Val::new(term_eval.shape.clone(), data))
@@ -1346,112 +2881,16 @@ pub fn eval(
             Ok(Val::new(lhs_eval.shape.clone(), data))
         }
         Expr::IndexRead {
-            span,
-            array,
-            indices,
-        } => {
-            debug!("Index Read");
-            let arr = eval(lexer, *array, env)?;
-            let idx_val = eval(lexer, *indices, env)?;
-            let indices: Vec<usize> = idx_val
-                .data
-                .iter()
-                .map(|s| {
-                    let i: usize = s
-                        .clone()
-                        .try_into()
-                        .map_err(|_| (span, "Index must be integer"))?;
-                    if i < 1 || i > arr.data.len() {
-                        return Err((span, "Index out of bounds"));
-                    }
-                    Ok(i - 1)
-                })
-                .collect::<Result<Vec<_>, _>>()?;
-            let data: Vec<Scalar> = indices.iter().map(|&i| arr.data[i].clone()).collect();
-            if data.len() == 1 {
-                Ok(Val::scalar(data.into_iter().next().unwrap()))
-            } else {
-                Ok(Val::vector(data))
-            }
-        }
-        Expr::DfnReduce { span, body, term } => {
-            debug!("Dfn Reduce");
-            let term_eval = eval(lexer, *term, env)?;
-            if term_eval.data.len() < 2 {
-                return Ok(term_eval);
-            }
-            let body_rc = Rc::new(*body);
-            // Right fold: f/ a b c = a f (b f c)
-            let mut acc = Val::scalar(term_eval.data.last().unwrap().clone());
-            for i in (0..term_eval.data.len() - 1).rev() {
-                let left = match &term_eval.data[i] {
-                    Scalar::Nested(v) => (**v).clone(),
-                    s => Val::scalar(s.clone()),
-                };
-                let stored = StoredDfn {
-                    body: Rc::clone(&body_rc),
-                    source: lexer.span_str(span).to_string(),
-                };
-                let mut dfn_env = env.clone();
-                dfn_env.vars.insert("⍺".to_string(), left);
-                dfn_env.vars.insert("⍵".to_string(), acc);
-                dfn_env.fns.insert("∇".to_string(), stored);
-                acc = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
-            }
-            Ok(acc)
-        }
-        Expr::DfnReduceFirst { span, body, term } => {
-            debug!("Dfn Reduce First");
-            let term_eval = eval(lexer, *term, env)?;
-            // For vectors, same as DfnReduce
-            // For matrices, reduce along first axis (column-wise)
-            if term_eval.shape.len() <= 1 {
-                let body_rc = Rc::new(*body);
-                if term_eval.data.len() < 2 {
-                    return Ok(term_eval);
-                }
-                let mut acc = Val::scalar(term_eval.data.last().unwrap().clone());
-                for i in (0..term_eval.data.len() - 1).rev() {
-                    let left = match &term_eval.data[i] {
-                        Scalar::Nested(v) => (**v).clone(),
-                        s => Val::scalar(s.clone()),
-                    };
-                    let stored = StoredDfn {
-                        body: Rc::clone(&body_rc),
-                        source: lexer.span_str(span).to_string(),
-                    };
-                    let mut dfn_env = env.clone();
-                    dfn_env.vars.insert("⍺".to_string(), left);
-                    dfn_env.vars.insert("⍵".to_string(), acc);
-                    dfn_env.fns.insert("∇".to_string(), stored);
-                    acc = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
-                }
-                Ok(acc)
-            } else {
-                // Higher-rank: reduce along first axis
-                let body_rc = Rc::new(*body);
-                let first_dim = term_eval.shape[0];
-                let stride: usize = term_eval.data.len() / first_dim;
-                let cell_shape = term_eval.shape[1..].to_vec();
-                // Start with last row
-                let mut acc_data = term_eval.data[(first_dim - 1) * stride..].to_vec();
-                for row in (0..first_dim - 1).rev() {
-                    let row_data = &term_eval.data[row * stride..(row + 1) * stride];
-                    let left = Val::new(cell_shape.clone(), row_data.to_vec());
-                    let right = Val::new(cell_shape.clone(), acc_data);
-                    let stored = StoredDfn {
-                        body: Rc::clone(&body_rc),
-                        source: lexer.span_str(span).to_string(),
-                    };
-                    let mut dfn_env = env.clone();
-                    dfn_env.vars.insert("⍺".to_string(), left);
-                    dfn_env.vars.insert("⍵".to_string(), right);
-                    dfn_env.fns.insert("∇".to_string(), stored);
-                    let result = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
-                    acc_data = result.data;
-                }
-                Ok(Val::new(cell_shape, acc_data))
-            }
+            span,
+            array,
+            indices,
+        } => {
+            debug!("Index Read");
+            let arr = eval(lexer, *array, env)?;
+            let indices = eval_indices(lexer, indices, env)?;
+            let (shape, positions) = index_positions(span, &arr.shape, indices)?;
+            let data = positions.iter().map(|&i| arr.data[i].clone()).collect();
+            Ok(Val::new(shape, data))
         }
         Expr::StringArray { span: _, elements } => {
             debug!("String Array");
@@ -1461,38 +2900,204 @@ pub fn eval(
                     let val = eval(lexer, e, env)?;
                     Ok(Scalar::Nested(Box::new(val)))
                 })
-                .collect::<Result<Vec<_>, (Span, &'static str)>>()?;
+                .collect::<Result<Vec<_>, Error>>()?;
             Ok(Val::vector(data))
         }
         Expr::Variable { span, name } => {
             debug!("Variable: {name}");
-            env.vars
-                .get(&name)
-                .cloned()
-                .ok_or((span, "Undefined variable"))
-        }
-        Expr::Omega { span } => env
-            .vars
-            .get("⍵")
-            .cloned()
-            .ok_or((span, "⍵ used outside of a dfn")),
-        Expr::Alpha { span } => env
-            .vars
-            .get("⍺")
-            .cloned()
-            .ok_or((span, "⍺ used outside of a dfn")),
+            if let Some(val) = env.get_var(&name) {
+                return Ok(val);
+            }
+            // A niladic tradfn is called by naming it
+            match env.get_fn(&name) {
+                Some(StoredFn::Tradfn(f)) if f.right.is_none() => tradfn::call(&f, None, None, env)
+                    .map_err(|(kind, msg)| (span, kind, msg.into())),
+                _ => Err((span, ErrorKind::Value, "Undefined variable".into())),
+            }
+        }
+        Expr::SysVar { span, name } => {
+            debug!("System variable: {name}");
+            match name.as_str() {
+                "⎕RL" => Ok(Val::scalar(Scalar::Integer(env.seed() as i64))),
+                "⎕EN" => Ok(Val::scalar(Scalar::Integer(env.last_error().0))),
+                "⎕DM" => {
+                    let data = env
+                        .last_error()
+                        .1
+                        .iter()
+                        .map(|line| {
+                            let chars = line.chars().map(Scalar::Char).collect();
+                            Scalar::Nested(Box::new(Val::vector(chars)))
+                        })
+                        .collect();
+                    Ok(Val::vector(data))
+                }
+                "⎕ARG" => {
+                    let data = env
+                        .args()
+                        .iter()
+                        .map(|arg| {
+                            let chars = arg.chars().map(Scalar::Char).collect();
+                            Scalar::Nested(Box::new(Val::vector(chars)))
+                        })
+                        .collect();
+                    Ok(Val::vector(data))
+                }
+                _ => Err((span, ErrorKind::Domain, "Unknown system name".into())),
+            }
+        }
+        Expr::SysAssign { span, name, rhs } => {
+            debug!("System assignment: {name}");
+            let val = eval(lexer, *rhs, env)?;
+            match name.as_str() {
+                "⎕RL" => {
+                    let seed = match val.data.as_slice() {
+                        [Scalar::Integer(i)] if *i >= 0 => *i as u64,
+                        _ => {
+                            return Err((
+                                span,
+                                ErrorKind::Domain,
+                                "⎕RL must be a non-negative integer".into(),
+                            ));
+                        }
+                    };
+                    env.set_seed(seed);
+                }
+                _ => return Err((span, ErrorKind::Domain, "Unknown system name".into())),
+            }
+            Ok(val)
+        }
+        Expr::SysMonadic { span, name, rhs } => {
+            debug!("System function: {name}");
+            let rhs = eval(lexer, *rhs, env)?;
+            match split_name(&name) {
+                (None, "⎕SIGNAL") => signal(span, None, rhs),
+                (None, "⎕NS") => new_namespace(span, Namespace::new(), rhs, env),
+                (None, "⎕NC") => {
+                    let names = names_in(&rhs).ok_or((
+                        span,
+                        ErrorKind::Domain,
+                        "⎕NC argument must be names".into(),
+                    ))?;
+                    let data: Vec<Scalar> = names
+                        .iter()
+                        .map(|name| Scalar::Integer(name_class(name, env)))
+                        .collect();
+                    match (data.as_slice(), rhs.data.first()) {
+                        ([class], Some(Scalar::Char(_))) => Ok(Val::scalar(class.clone())),
+                        _ => Ok(Val::vector(data)),
+                    }
+                }
+                (path, "⎕NL") => {
+                    let classes = rhs
+                        .data
+                        .iter()
+                        .map(|class| match class {
+                            Scalar::Integer(i) => Ok(*i),
+                            _ => Err((
+                                span,
+                                ErrorKind::Domain,
+                                "⎕NL classes must be integers".into(),
+                            )),
+                        })
+                        .collect::<Result<Vec<i64>, _>>()?;
+                    let wanted: Vec<i64> = classes.iter().map(|c| c.abs()).collect();
+                    let names = match path {
+                        None => {
+                            let (vars, fns) = env.visible();
+                            names_of_class(&vars, &fns, &wanted)
+                        }
+                        Some(path) => {
+                            let ns = env.namespace_at(path).map_err(at(span))?;
+                            let members = ns.lock();
+                            names_of_class(&members.vars, &members.fns, &wanted)
+                        }
+                    };
+                    Ok(name_list(names, classes.iter().any(|c| *c < 0)))
+                }
+                _ => Err((span, ErrorKind::Domain, "Unknown system function".into())),
+            }
+        }
+        Expr::SysDyadic {
+            span,
+            lhs,
+            name,
+            rhs,
+        } => {
+            debug!("Dyadic system function: {name}");
+            let lhs = eval(lexer, *lhs, env)?;
+            let rhs = eval(lexer, *rhs, env)?;
+            match name.as_str() {
+                "⎕SIGNAL" => signal(span, Some(lhs), rhs),
+                "⎕NS" => {
+                    let target = match names_in(&lhs).as_deref() {
+                        Some([target]) => target.clone(),
+                        _ => {
+                            return Err((
+                                span,
+                                ErrorKind::Domain,
+                                "⎕NS left argument must be a name".into(),
+                            ));
+                        }
+                    };
+                    let ns = match env.get_var(&target) {
+                        Some(val) => as_namespace(&val).ok_or((
+                            span,
+                            ErrorKind::Domain,
+                            "Not a namespace".into(),
+                        ))?,
+                        None => Namespace::new(),
+                    };
+                    let val = new_namespace(span, ns, rhs, env)?;
+                    env.set_var(&target, val.clone()).map_err(at(span))?;
+                    Ok(val)
+                }
+                _ => Err((span, ErrorKind::Domain, "Unknown system function".into())),
+            }
+        }
+        Expr::Zilde { .. } => Ok(Val::vector(vec![])),
+        Expr::DefaultAlpha { span, rhs } => {
+            debug!("Default Alpha");
+            // Only a dfn body has a right argument
+            if !env.vars.contains_key("⍵") {
+                return Err((span, ErrorKind::Syntax, "⍺← used outside of a dfn".into()));
+            }
+            if let Some(alpha) = env.vars.get("⍺") {
+                return Ok(alpha.clone());
+            }
+            let val = eval(lexer, *rhs, env)?;
+            Arc::make_mut(&mut env.vars).insert("⍺".to_string(), val.clone());
+            Ok(val)
+        }
+        Expr::IBeam { span, code, rhs } => {
+            debug!("I-Beam");
+            let code = eval(lexer, *code, env)?;
+            eval(lexer, *rhs, env)?;
+            match code.data.as_slice() {
+                // Was the dfn called without a left argument?
+                [Scalar::Integer(900)] => Ok(Val::scalar(Scalar::Integer(
+                    (!env.vars.contains_key("⍺")).into(),
+                ))),
+                _ => Err((span, ErrorKind::Domain, "Unsupported I-beam".into())),
+            }
+        }
+        Expr::Omega { span } => env.vars.get("⍵").cloned().ok_or((
+            span,
+            ErrorKind::Value,
+            "⍵ used outside of a dfn".into(),
+        )),
+        Expr::Alpha { span } => env.vars.get("⍺").cloned().ok_or((
+            span,
+            ErrorKind::Value,
+            "⍺ used outside of a dfn".into(),
+        )),
         Expr::MonadicDfn { span, body, rhs } => {
             debug!("Monadic Dfn");
             let rhs_val = eval(lexer, *rhs, env)?;
-            let body_rc = Rc::new(*body);
-            let stored = StoredDfn {
-                body: Rc::clone(&body_rc),
-                source: lexer.span_str(span).to_string(),
-            };
-            let mut dfn_env = env.clone();
-            dfn_env.vars.insert("⍵".to_string(), rhs_val);
-            dfn_env.fns.insert("∇".to_string(), stored);
-            eval(lexer, (*body_rc).clone(), &mut dfn_env)
+            let body_arc = Arc::new(*body);
+            let stored = StoredDfn::new(Arc::clone(&body_arc), lexer, span);
+            let mut dfn_env = env.call_scope(None, Some(&stored), None, rhs_val);
+            eval(lexer, (*body_arc).clone(), &mut dfn_env)
         }
         Expr::RankOp {
             span,
@@ -1502,133 +3107,25 @@ pub fn eval(
         } => {
             debug!("Rank Operator");
             let rank_val = eval(lexer, *rank, env)?;
-            let k: usize = rank_val.data[0]
-                .clone()
-                .try_into()
-                .map_err(|_| (span, "Rank must be a non-negative integer"))?;
+            let k: usize = rank_val.data[0].clone().try_into().map_err(|_| {
+                (
+                    span,
+                    ErrorKind::Domain,
+                    "Rank must be a non-negative integer".into(),
+                )
+            })?;
             let arg_val = eval(lexer, *arg, env)?;
-            let n = arg_val.shape.len();
-            let body_rc = Rc::new(*body);
-            if k >= n {
-                // Apply to entire array
-                let stored = StoredDfn {
-                    body: Rc::clone(&body_rc),
-                    source: lexer.span_str(span).to_string(),
-                };
-                let mut dfn_env = env.clone();
-                dfn_env.vars.insert("⍵".to_string(), arg_val);
-                dfn_env.fns.insert("∇".to_string(), stored);
-                return eval(lexer, (*body_rc).clone(), &mut dfn_env);
-            }
-            let frame_shape = arg_val.shape[..n - k].to_vec();
-            let cell_shape = arg_val.shape[n - k..].to_vec();
-            let cell_size: usize = cell_shape.iter().product();
-            let num_cells: usize = frame_shape.iter().product();
-            let mut results = Vec::new();
-            let mut result_cell_shape: Option<Vec<usize>> = None;
-            for i in 0..num_cells {
-                let start = i * cell_size;
-                let cell_data = arg_val.data[start..start + cell_size].to_vec();
-                let cell = Val::new(cell_shape.clone(), cell_data);
-                let stored = StoredDfn {
-                    body: Rc::clone(&body_rc),
-                    source: lexer.span_str(span).to_string(),
-                };
-                let mut dfn_env = env.clone();
-                dfn_env.vars.insert("⍵".to_string(), cell);
-                dfn_env.fns.insert("∇".to_string(), stored);
-                let result = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
-                if result_cell_shape.is_none() {
-                    result_cell_shape = Some(result.shape.clone());
-                }
-                results.extend(result.data);
-            }
-            let rcs = result_cell_shape.unwrap_or_default();
-            let mut final_shape = frame_shape;
-            final_shape.extend_from_slice(&rcs);
-            Ok(Val::new(final_shape, results))
-        }
-        Expr::AtOp {
-            span,
-            body,
-            indices,
-            arg,
-        } => {
-            debug!("At Operator");
-            let idx_val = eval(lexer, *indices, env)?;
-            let mut arg_val = eval(lexer, *arg, env)?;
-            let body_rc = Rc::new(*body);
-
-            // Convert 1-based indices to 0-based
-            let idxs: Vec<usize> = idx_val
-                .data
-                .iter()
-                .map(|s| {
-                    let i: usize = s
-                        .clone()
-                        .try_into()
-                        .map_err(|_| (span, "At index must be integer"))?;
-                    if i < 1 || i > arg_val.data.len() {
-                        return Err((span, "At index out of bounds"));
-                    }
-                    Ok(i - 1)
+            let body_arc = Arc::new(*body);
+            let stored = StoredDfn::new(Arc::clone(&body_arc), lexer, span);
+            let (frame, cells) = cells(&arg_val, k);
+            let results = cells
+                .into_iter()
+                .map(|cell| {
+                    let mut dfn_env = env.call_scope(None, Some(&stored), None, cell);
+                    eval(lexer, (*body_arc).clone(), &mut dfn_env)
                 })
-                .collect::<Result<Vec<_>, _>>()?;
-
-            // Apply function to each indexed element
-            for &idx in &idxs {
-                let elem = Val::scalar(arg_val.data[idx].clone());
-                let stored = StoredDfn {
-                    body: Rc::clone(&body_rc),
-                    source: lexer.span_str(span).to_string(),
-                };
-                let mut dfn_env = env.clone();
-                dfn_env.vars.insert("⍵".to_string(), elem);
-                dfn_env.fns.insert("∇".to_string(), stored);
-                let result = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
-                arg_val.data[idx] = result.data[0].clone();
-            }
-
-            Ok(arg_val)
-        }
-        Expr::KeyOp { span, body, arg } => {
-            debug!("Key Operator");
-            let arg_val = eval(lexer, *arg, env)?;
-            let body_rc = Rc::new(*body);
-
-            // Find unique keys and their indices (1-based)
-            let mut keys: Vec<Scalar> = Vec::new();
-            let mut groups: Vec<Vec<Scalar>> = Vec::new();
-
-            for (i, s) in arg_val.data.iter().enumerate() {
-                if let Some(pos) = keys.iter().position(|k| k == s) {
-                    groups[pos].push(Scalar::Integer((i + 1) as i64));
-                } else {
-                    keys.push(s.clone());
-                    groups.push(vec![Scalar::Integer((i + 1) as i64)]);
-                }
-            }
-
-            // Apply f to each group
-            let mut results = Vec::new();
-            for (key, indices) in keys.iter().zip(groups.iter()) {
-                let stored = StoredDfn {
-                    body: Rc::clone(&body_rc),
-                    source: lexer.span_str(span).to_string(),
-                };
-                let mut dfn_env = env.clone();
-                dfn_env
-                    .vars
-                    .insert("⍺".to_string(), Val::scalar(key.clone()));
-                dfn_env
-                    .vars
-                    .insert("⍵".to_string(), Val::vector(indices.clone()));
-                dfn_env.fns.insert("∇".to_string(), stored);
-                let result = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
-                results.extend(result.data);
-            }
-
-            Ok(Val::vector(results))
+                .collect::<Result<_, _>>()?;
+            Ok(from_cells(frame, results))
         }
         Expr::PowerOp {
             span,
@@ -1638,21 +3135,19 @@ pub fn eval(
         } => {
             debug!("Power Operator (dfn)");
             let count_val = eval(lexer, *count, env)?;
-            let n: usize = count_val.data[0]
-                .clone()
-                .try_into()
-                .map_err(|_| (span, "Power operator count must be a non-negative integer"))?;
+            let n: usize = count_val.data[0].clone().try_into().map_err(|_| {
+                (
+                    span,
+                    ErrorKind::Domain,
+                    "Power operator count must be a non-negative integer".into(),
+                )
+            })?;
             let mut current = eval(lexer, *arg, env)?;
-            let body_rc = Rc::new(*body);
+            let body_arc = Arc::new(*body);
+            let stored = StoredDfn::new(Arc::clone(&body_arc), lexer, span);
             for _ in 0..n {
-                let stored = StoredDfn {
-                    body: Rc::clone(&body_rc),
-                    source: lexer.span_str(span).to_string(),
-                };
-                let mut dfn_env = env.clone();
-                dfn_env.vars.insert("⍵".to_string(), current);
-                dfn_env.fns.insert("∇".to_string(), stored);
-                current = eval(lexer, (*body_rc).clone(), &mut dfn_env)?;
+                let mut dfn_env = env.call_scope(None, Some(&stored), None, current);
+                current = eval(lexer, (*body_arc).clone(), &mut dfn_env)?;
             }
             Ok(current)
         }
@@ -1665,97 +3160,19 @@ pub fn eval(
             debug!("Dyadic Dfn");
             let lhs_val = eval(lexer, *lhs, env)?;
             let rhs_val = eval(lexer, *rhs, env)?;
-            let body_rc = Rc::new(*body);
-            let stored = StoredDfn {
-                body: Rc::clone(&body_rc),
-                source: lexer.span_str(span).to_string(),
-            };
-            let mut dfn_env = env.clone();
-            dfn_env.vars.insert("⍺".to_string(), lhs_val);
-            dfn_env.vars.insert("⍵".to_string(), rhs_val);
-            dfn_env.fns.insert("∇".to_string(), stored);
-            eval(lexer, (*body_rc).clone(), &mut dfn_env)
-        }
-        Expr::ComposeDfn { span: _, f, g, arg } => {
-            debug!("Compose (monadic)");
-            let arg_val = eval(lexer, *arg, env)?;
-            // First apply g monadically
-            let mut g_env = env.clone();
-            g_env.vars.insert("⍵".to_string(), arg_val);
-            let g_result = eval(lexer, *g, &mut g_env)?;
-            // Then apply f monadically
-            let mut f_env = env.clone();
-            f_env.vars.insert("⍵".to_string(), g_result);
-            eval(lexer, *f, &mut f_env)
-        }
-        Expr::ComposeDyadicDfn {
-            span: _,
-            lhs,
-            f,
-            g,
-            arg,
-        } => {
-            debug!("Compose (dyadic)");
-            let lhs_val = eval(lexer, *lhs, env)?;
-            let arg_val = eval(lexer, *arg, env)?;
-            // Apply g monadically to right arg
-            let mut g_env = env.clone();
-            g_env.vars.insert("⍵".to_string(), arg_val);
-            let g_result = eval(lexer, *g, &mut g_env)?;
-            // Apply f dyadically with left and g's result
-            let mut f_env = env.clone();
-            f_env.vars.insert("⍺".to_string(), lhs_val);
-            f_env.vars.insert("⍵".to_string(), g_result);
-            eval(lexer, *f, &mut f_env)
-        }
-        Expr::OverDfn { span: _, f, g, arg } => {
-            debug!("Over (monadic)");
-            let arg_val = eval(lexer, *arg, env)?;
-            // Apply g monadically to arg
-            let mut g_env = env.clone();
-            g_env.vars.insert("⍵".to_string(), arg_val);
-            let g_result = eval(lexer, *g, &mut g_env)?;
-            // Apply f monadically to g's result
-            let mut f_env = env.clone();
-            f_env.vars.insert("⍵".to_string(), g_result);
-            eval(lexer, *f, &mut f_env)
-        }
-        Expr::OverDyadicDfn {
-            span: _,
-            lhs,
-            f,
-            g,
-            arg,
-        } => {
-            debug!("Over (dyadic)");
-            let lhs_val = eval(lexer, *lhs, env)?;
-            let arg_val = eval(lexer, *arg, env)?;
-            // Apply g to BOTH arguments
-            let g_clone = (*g).clone();
-            let mut g_env_l = env.clone();
-            g_env_l.vars.insert("⍵".to_string(), lhs_val);
-            let g_lhs = eval(lexer, *g, &mut g_env_l)?;
-            let mut g_env_r = env.clone();
-            g_env_r.vars.insert("⍵".to_string(), arg_val);
-            let g_rhs = eval(lexer, g_clone, &mut g_env_r)?;
-            // Apply f dyadically to the two results
-            let mut f_env = env.clone();
-            f_env.vars.insert("⍺".to_string(), g_lhs);
-            f_env.vars.insert("⍵".to_string(), g_rhs);
-            eval(lexer, *f, &mut f_env)
+            let body_arc = Arc::new(*body);
+            let stored = StoredDfn::new(Arc::clone(&body_arc), lexer, span);
+            let mut dfn_env = env.call_scope(None, Some(&stored), Some(lhs_val), rhs_val);
+            eval(lexer, (*body_arc).clone(), &mut dfn_env)
         }
         Expr::SelfCall { span, arg } => {
             debug!("Self-reference ∇");
             let arg_val = eval(lexer, *arg, env)?;
-            let stored = env
-                .fns
-                .get("∇")
-                .cloned()
-                .ok_or((span, "∇ used outside of a dfn"))?;
-            let mut self_env = env.clone();
-            self_env.vars.insert("⍵".to_string(), arg_val);
-            eval_stored_dfn(&stored, &mut self_env)
-                .map_err(|(_span, msg)| (span, Box::leak(msg.into_boxed_str()) as &'static str))
+            let Some(StoredFn::Dfn(stored)) = env.fns.get("∇").cloned() else {
+                return Err((span, ErrorKind::Value, "∇ used outside of a dfn".into()));
+            };
+            let mut self_env = env.call_scope(None, Some(&stored), None, arg_val);
+            eval_stored_dfn(&stored, &mut self_env).map_err(|(_span, kind, msg)| (span, kind, msg))
         }
         Expr::DfnGuard {
             cond, result, rest, ..
@@ -1773,6 +3190,38 @@ pub fn eval(
                 eval(lexer, *rest, env)
             }
         }
+        Expr::ErrorGuard {
+            span,
+            codes,
+            handler,
+            rest,
+        } => {
+            debug!("Error Guard");
+            let codes = eval(lexer, *codes, env)?;
+            let codes = codes
+                .data
+                .iter()
+                .map(|code| match code {
+                    Scalar::Integer(i) => Ok(*i),
+                    _ => Err((
+                        span,
+                        ErrorKind::Domain,
+                        "Error guard codes must be integers".into(),
+                    )),
+                })
+                .collect::<Result<Vec<i64>, _>>()?;
+            match eval(lexer, *rest, env) {
+                Err((err_span, kind, msg)) => {
+                    let number = env.record_error(kind, &msg);
+                    if codes.contains(&0) || codes.contains(&number) {
+                        eval(lexer, *handler, env)
+                    } else {
+                        Err((err_span, kind, msg))
+                    }
+                }
+                ok => ok,
+            }
+        }
         Expr::DfnStatements { first, rest, .. } => {
             debug!("Dfn Statements");
             eval(lexer, *first, env)?;
@@ -1780,26 +3229,26 @@ pub fn eval(
         }
         Expr::AssignDfn { span, name, body } => {
             debug!("Assign Dfn");
-            let stored = StoredDfn {
-                body: Rc::new(*body),
-                source: lexer.span_str(span).to_string(),
-            };
-            env.fns.insert(name, stored);
+            let stored = StoredDfn::new(Arc::new(*body), lexer, span);
+            env.set_fn(&name, StoredFn::Dfn(stored)).map_err(at(span))?;
+            Ok(Val::scalar(Scalar::Integer(0)))
+        }
+        Expr::AssignFn { span, name, func } => {
+            debug!("Assign Fn");
+            let f = tacit_fn(lexer, span, *func, env)?;
+            env.set_fn(&name, f).map_err(at(span))?;
             Ok(Val::scalar(Scalar::Integer(0)))
         }
+        Expr::Function { span, .. } => {
+            Err((span, ErrorKind::Syntax, "A function is not a value".into()))
+        }
         Expr::NamedMonadic { span, name, rhs } => {
             debug!("Named Monadic: {name}");
-            let stored = env
-                .fns
-                .get(&name)
-                .cloned()
-                .ok_or((span, "Undefined function"))?;
+            let f =
+                env.get_fn(&name)
+                    .ok_or((span, ErrorKind::Value, "Undefined function".into()))?;
             let rhs_val = eval(lexer, *rhs, env)?;
-            let mut dfn_env = env.clone();
-            dfn_env.vars.insert("⍵".to_string(), rhs_val);
-            dfn_env.fns.insert("∇".to_string(), stored.clone());
-            eval_stored_dfn(&stored, &mut dfn_env)
-                .map_err(|(_span, msg)| (span, Box::leak(msg.into_boxed_str()) as &'static str))
+            call_fn(span, &name, &f, None, rhs_val, env)
         }
         Expr::NamedDyadic {
             span,
@@ -1808,19 +3257,30 @@ pub fn eval(
             rhs,
         } => {
             debug!("Named Dyadic: {name}");
-            let stored = env
-                .fns
-                .get(&name)
-                .cloned()
-                .ok_or((span, "Undefined function"))?;
+            let f =
+                env.get_fn(&name)
+                    .ok_or((span, ErrorKind::Value, "Undefined function".into()))?;
+            let lhs_val = eval(lexer, *lhs, env)?;
+            let rhs_val = eval(lexer, *rhs, env)?;
+            call_fn(span, &name, &f, Some(lhs_val), rhs_val, env)
+        }
+        Expr::DerivedMonadic { span, func, rhs } => {
+            debug!("Derived Monadic");
+            let rhs_val = eval(lexer, *rhs, env)?;
+            let f = fn_value(lexer, *func, env)?;
+            call_fn(span, "", &f, None, rhs_val, env)
+        }
+        Expr::DerivedDyadic {
+            span,
+            lhs,
+            func,
+            rhs,
+        } => {
+            debug!("Derived Dyadic");
             let lhs_val = eval(lexer, *lhs, env)?;
             let rhs_val = eval(lexer, *rhs, env)?;
-            let mut dfn_env = env.clone();
-            dfn_env.vars.insert("⍺".to_string(), lhs_val);
-            dfn_env.vars.insert("⍵".to_string(), rhs_val);
-            dfn_env.fns.insert("∇".to_string(), stored.clone());
-            eval_stored_dfn(&stored, &mut dfn_env)
-                .map_err(|(_span, msg)| (span, Box::leak(msg.into_boxed_str()) as &'static str))
+            let f = fn_value(lexer, *func, env)?;
+            call_fn(span, "", &f, Some(lhs_val), rhs_val, env)
         }
         Expr::Enclose { arg, .. } => {
             debug!("Monadic Enclose");
@@ -1841,124 +3301,29 @@ pub fn eval(
             debug!("Dyadic Partition");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
-
-            if lhs_eval.data.len() != rhs_eval.data.len() {
-                return Err((span, "Partition: arguments must have same length"));
-            }
-
-            let mut groups: Vec<Scalar> = Vec::new();
-            let mut current: Vec<Scalar> = Vec::new();
-            let mut in_group = false;
-
-            for (mask, val) in lhs_eval.data.iter().zip(rhs_eval.data.iter()) {
-                let m = match mask {
-                    Scalar::Integer(i) => *i,
-                    Scalar::Float(f) => *f as i64,
-                    _ => 0,
-                };
-                if m > 0 {
-                    if !in_group {
-                        if !current.is_empty() {
-                            groups.push(Scalar::Nested(Box::new(Val::vector(current))));
-                            current = Vec::new();
-                        }
-                        in_group = true;
-                    }
-                    current.push(val.clone());
-                } else {
-                    if !current.is_empty() {
-                        groups.push(Scalar::Nested(Box::new(Val::vector(current))));
-                        current = Vec::new();
-                    }
-                    in_group = false;
-                }
-            }
-            if !current.is_empty() {
-                groups.push(Scalar::Nested(Box::new(Val::vector(current))));
-            }
-            Ok(Val::vector(groups))
+            let rhs_eval = match rhs_eval.is_scalar() {
+                true => Val::vector(rhs_eval.data),
+                false => rhs_eval,
+            };
+            let last = rhs_eval.shape.len() - 1;
+            axis::along(&rhs_eval, last, |_, items| {
+                axis::partition(&lhs_eval.data, items)
+            })
+            .map_err(at(span))
         }
         Expr::PartitionedEnclose { span, lhs, rhs } => {
             debug!("Partitioned Enclose");
             let lhs_eval = eval(lexer, *lhs, env)?;
             let rhs_eval = eval(lexer, *rhs, env)?;
-
-            if lhs_eval.data.len() != rhs_eval.data.len() {
-                return Err((
-                    span,
-                    "Partitioned enclose: left and right must be same length",
-                ));
-            }
-
-            let mut partitions: Vec<Vec<Scalar>> = Vec::new();
-            let mut current: Option<Vec<Scalar>> = None;
-
-            for (mask, elem) in lhs_eval.data.iter().zip(rhs_eval.data.iter()) {
-                let m: f64 = mask.clone().into();
-                if m >= 1.0 {
-                    // Start new partition (save current if exists)
-                    if let Some(part) = current.take() {
-                        partitions.push(part);
-                    }
-                    current = Some(vec![elem.clone()]);
-                } else if m == 0.0 && current.is_some() {
-                    // Continue current partition
-                    current.as_mut().unwrap().push(elem.clone());
-                }
-                // If m == 0 and no current partition, element is dropped
-            }
-
-            // Don't forget the last partition
-            if let Some(part) = current {
-                partitions.push(part);
-            }
-
-            let data: Vec<Scalar> = partitions
-                .into_iter()
-                .map(|p| Scalar::Nested(Box::new(Val::vector(p))))
-                .collect();
-
-            Ok(Val::vector(data))
-        }
-        Expr::MonadicEach { span, func, arg } => {
-            debug!("Monadic Each: {func}");
-            let arg_eval = eval(lexer, *arg, env)?;
-
-            let apply_to_val = |v: &Val| -> Result<Val, (Span, &'static str)> {
-                match func.as_str() {
-                    "shape" => {
-                        let data: Vec<Scalar> =
-                            v.shape.iter().map(|&s| Scalar::Integer(s as i64)).collect();
-                        Ok(Val::vector(data))
-                    }
-                    "reverse" => {
-                        let mut data = v.data.clone();
-                        data.reverse();
-                        Ok(Val::new(v.shape.clone(), data))
-                    }
-                    "iota" => {
-                        if let Some(Scalar::Integer(n)) = v.data.first() {
-                            let data: Vec<Scalar> = (1..=*n).map(Scalar::Integer).collect();
-                            Ok(Val::vector(data))
-                        } else {
-                            Err((span, "Iota each: elements must be integers"))
-                        }
-                    }
-                    _ => Err((span, "Unknown each function")),
-                }
+            let rhs_eval = match rhs_eval.is_scalar() {
+                true => Val::vector(rhs_eval.data),
+                false => rhs_eval,
             };
-
-            let data: Vec<Scalar> = arg_eval
-                .data
-                .iter()
-                .map(|elem| match elem {
-                    Scalar::Nested(v) => apply_to_val(v).map(|r| Scalar::Nested(Box::new(r))),
-                    Scalar::Integer(n) => apply_to_val(&Val::scalar(Scalar::Integer(*n)))
-                        .map(|r| Scalar::Nested(Box::new(r))),
-                    _ => Err((span, "Each: unsupported element type")),
-                })
-                .collect::<Result<Vec<_>, _>>()?;
-            Ok(Val::vector(data))
+            let last = rhs_eval.shape.len() - 1;
+            axis::along(&rhs_eval, last, |_, items| {
+                axis::partitioned_enclose(&lhs_eval.data, items)
+            })
+            .map_err(at(span))
         }
         Expr::ReduceEach {
             span,
@@ -1975,7 +3340,13 @@ pub fn eval(
                 .map(|elem| {
                     let inner = match elem {
                         Scalar::Nested(v) => &v.data,
-                        _ => return Err((span, "Reduce each: elements must be nested")),
+                        _ => {
+                            return Err((
+                                span,
+                                ErrorKind::Domain,
+                                "Reduce each: elements must be nested".into(),
+                            ));
+                        }
                     };
                     inner
                         .iter()
@@ -1986,7 +3357,11 @@ pub fn eval(
                             Some(right) => op_fn(&n, &right).map(Some),
                         })
                         .flatten()
-                        .ok_or((span, "Reduce each: operation failed"))
+                        .ok_or((
+                            span,
+                            ErrorKind::Domain,
+                            "Reduce each: operation failed".into(),
+                        ))
                 })
                 .collect::<Result<Vec<_>, _>>()?;
             Ok(Val::vector(data))
@@ -2003,8 +3378,12 @@ pub fn eval(
             let op_fn = get_operator_fn(operator);
 
             // Element-wise application
-            let apply = |a: &Scalar, b: &Scalar| -> Result<Scalar, (Span, &'static str)> {
-                op_fn(a, b).ok_or((span, "Dyadic each: operation failed"))
+            let apply = |a: &Scalar, b: &Scalar| -> Result<Scalar, Error> {
+                op_fn(a, b).ok_or((
+                    span,
+                    ErrorKind::Domain,
+                    "Dyadic each: operation failed".into(),
+                ))
             };
 
             if lhs_eval.is_scalar() {
@@ -2034,6 +3413,20 @@ pub fn eval(
         Expr::Unique { arg, .. } => {
             debug!("Monadic Unique");
             let arg_eval = eval(lexer, *arg, env)?;
+            if arg_eval.shape.len() > 1 {
+                // The distinct major cells, as for the rows of a matrix
+                let (_, major) = cells(&arg_eval, arg_eval.shape.len() - 1);
+                let mut seen: Vec<Val> = Vec::new();
+                for cell in major {
+                    if !seen.iter().any(|s| s.data == cell.data) {
+                        seen.push(cell);
+                    }
+                }
+                let mut shape = vec![seen.len()];
+                shape.extend_from_slice(&arg_eval.shape[1..]);
+                let data = seen.into_iter().flat_map(|cell| cell.data).collect();
+                return Ok(Val::new(shape, data));
+            }
             let mut seen = Vec::new();
             for v in &arg_eval.data {
                 if !seen.contains(v) {
@@ -2098,7 +3491,11 @@ pub fn eval(
             let rhs_eval = eval(lexer, *rhs, env)?;
 
             if !lhs_eval.is_scalar() {
-                return Err((span, "Decode: left argument must be a scalar base"));
+                return Err((
+                    span,
+                    ErrorKind::Domain,
+                    "Decode: left argument must be a scalar base".into(),
+                ));
             }
             let base = f64::from(lhs_eval.data[0].clone());
             let result = rhs_eval
@@ -2113,7 +3510,11 @@ pub fn eval(
             let rhs_eval = eval(lexer, *rhs, env)?;
 
             if !rhs_eval.is_scalar() {
-                return Err((span, "Encode: right argument must be a scalar"));
+                return Err((
+                    span,
+                    ErrorKind::Domain,
+                    "Encode: right argument must be a scalar".into(),
+                ));
             }
             let mut n = f64::from(rhs_eval.data[0].clone()) as i64;
             let bases: Vec<i64> = lhs_eval
@@ -2147,13 +3548,23 @@ pub fn eval(
                 (1, 1) => {
                     // Vector inner product: +/ lhs × rhs
                     if lhs_eval.data.len() != rhs_eval.data.len() {
-                        return Err((span, "Inner product: lengths must match"));
+                        return Err((
+                            span,
+                            ErrorKind::Length,
+                            "Inner product: lengths must match".into(),
+                        ));
                     }
                     let products: Vec<Scalar> = lhs_eval
                         .data
                         .iter()
                         .zip(rhs_eval.data.iter())
-                        .map(|(a, b)| g_fn(a, b).ok_or((span, "Inner product g failed")))
+                        .map(|(a, b)| {
+                            g_fn(a, b).ok_or((
+                                span,
+                                ErrorKind::Domain,
+                                "Inner product g failed".into(),
+                            ))
+                        })
                         .collect::<Result<Vec<_>, _>>()?;
                     let result = products
                         .iter()
@@ -2164,7 +3575,7 @@ pub fn eval(
                             Some(right) => f_fn(&n, &right).map(Some),
                         })
                         .flatten()
-                        .ok_or((span, "Inner product f failed"))?;
+                        .ok_or((span, ErrorKind::Domain, "Inner product f failed".into()))?;
                     Ok(Val::scalar(result))
                 }
                 (2, 2) => {
@@ -2173,7 +3584,11 @@ pub fn eval(
                     let k = lhs_eval.shape[1];
                     let n = rhs_eval.shape[1];
                     if k != rhs_eval.shape[0] {
-                        return Err((span, "Inner product: inner dimensions must match"));
+                        return Err((
+                            span,
+                            ErrorKind::Length,
+                            "Inner product: inner dimensions must match".into(),
+                        ));
                     }
                     let mut data = Vec::with_capacity(m * n);
                     for i in 0..m {
@@ -2181,20 +3596,32 @@ pub fn eval(
                             let products: Vec<Scalar> = (0..k)
                                 .map(|p| {
                                     g_fn(&lhs_eval.data[i * k + p], &rhs_eval.data[p * n + j])
-                                        .ok_or((span, "Inner product g failed"))
+                                        .ok_or((
+                                            span,
+                                            ErrorKind::Domain,
+                                            "Inner product g failed".into(),
+                                        ))
                                 })
                                 .collect::<Result<Vec<_>, _>>()?;
                             let result = products
                                 .iter()
                                 .cloned()
                                 .reduce(|a, b| f_fn(&a, &b).unwrap_or(a))
-                                .ok_or((span, "Inner product f failed"))?;
+                                .ok_or((
+                                    span,
+                                    ErrorKind::Domain,
+                                    "Inner product f failed".into(),
+                                ))?;
                             data.push(result);
                         }
                     }
                     Ok(Val::new(vec![m, n], data))
                 }
-                _ => Err((span, "Inner product: only rank 1 and 2 supported")),
+                _ => Err((
+                    span,
+                    ErrorKind::Rank,
+                    "Inner product: only rank 1 and 2 supported".into(),
+                )),
             }
         }
         Expr::Index { span, lhs, rhs } => {
@@ -2208,7 +3635,7 @@ pub fn eval(
                 .map(|idx| {
                     let i = f64::from(idx.clone()) as usize;
                     if i == 0 || i > rhs_eval.data.len() {
-                        Err((span, "Index out of bounds"))
+                        Err((span, ErrorKind::Index, "Index out of bounds".into()))
                     } else {
                         Ok(rhs_eval.data[i - 1].clone())
                     }
@@ -2220,11 +3647,35 @@ pub fn eval(
             debug!("Matrix Inverse");
             let arg_eval = eval(lexer, *arg, env)?;
             if arg_eval.shape.len() != 2 {
-                return Err((span, "Matrix inverse requires a rank-2 array"));
+                return Err((
+                    span,
+                    ErrorKind::Rank,
+                    "Matrix inverse requires a rank-2 array".into(),
+                ));
             }
             let n = arg_eval.shape[0];
-            if n != arg_eval.shape[1] {
-                return Err((span, "Matrix inverse requires a square matrix"));
+            let cols = arg_eval.shape[1];
+            if n < cols {
+                return Err((
+                    span,
+                    ErrorKind::Length,
+                    "Matrix inverse: more columns than rows".into(),
+                ));
+            }
+            if n > cols {
+                // Pseudo-inverse: the least-squares solution of A X = I
+                let a: Vec<f64> = arg_eval.data.iter().map(|s| f64::from(s.clone())).collect();
+                let mut identity = vec![0.0_f64; n * n];
+                for i in 0..n {
+                    identity[i * n + i] = 1.0;
+                }
+                let x = least_squares(a, n, cols, identity, n).ok_or((
+                    span,
+                    ErrorKind::Domain,
+                    "Matrix inverse: rank-deficient matrix".into(),
+                ))?;
+                let data = x.into_iter().map(Scalar::Float).collect();
+                return Ok(Val::new(vec![cols, n], data));
             }
             // Gauss-Jordan elimination
             let mut m: Vec<f64> = arg_eval.data.iter().map(|s| f64::from(s.clone())).collect();
@@ -2243,7 +3694,7 @@ pub fn eval(
                     })
                     .unwrap();
                 if m[pivot_row * n + col].abs() < 1e-12 {
-                    return Err((span, "Matrix is singular"));
+                    return Err((span, ErrorKind::Domain, "Matrix is singular".into()));
                 }
                 for j in 0..n {
                     m.swap(col * n + j, pivot_row * n + j);
@@ -2274,14 +3725,22 @@ pub fn eval(
             let a_eval = eval(lexer, *rhs, env)?;
 
             if a_eval.shape.len() != 2 {
-                return Err((span, "Matrix divide: right argument must be a matrix"));
+                return Err((
+                    span,
+                    ErrorKind::Rank,
+                    "Matrix divide: right argument must be a matrix".into(),
+                ));
             }
             let n = a_eval.shape[0];
-            if n != a_eval.shape[1] {
-                return Err((span, "Matrix divide: right argument must be square"));
+            let cols = a_eval.shape[1];
+            if n < cols {
+                return Err((
+                    span,
+                    ErrorKind::Length,
+                    "Matrix divide: right argument has more columns than rows".into(),
+                ));
             }
 
-            // Build augmented matrix [A | B]
             let b_cols = if b_eval.shape.len() == 2 {
                 b_eval.shape[1]
             } else if b_eval.shape.len() <= 1 {
@@ -2289,10 +3748,35 @@ pub fn eval(
             } else {
                 return Err((
                     span,
-                    "Matrix divide: left argument must be vector or matrix",
+                    ErrorKind::Rank,
+                    "Matrix divide: left argument must be vector or matrix".into(),
                 ));
             };
             let b_data: Vec<f64> = b_eval.data.iter().map(|s| f64::from(s.clone())).collect();
+
+            if n > cols {
+                // Overdetermined: the least-squares solution
+                if b_eval.shape.first().copied().unwrap_or(1) != n {
+                    return Err((
+                        span,
+                        ErrorKind::Length,
+                        "Matrix divide: lengths must match".into(),
+                    ));
+                }
+                let a: Vec<f64> = a_eval.data.iter().map(|s| f64::from(s.clone())).collect();
+                let x = least_squares(a, n, cols, b_data, b_cols).ok_or((
+                    span,
+                    ErrorKind::Domain,
+                    "Matrix divide: rank-deficient matrix".into(),
+                ))?;
+                let data: Vec<Scalar> = x.into_iter().map(Scalar::Float).collect();
+                return match b_eval.shape.len() {
+                    2 => Ok(Val::new(vec![cols, b_cols], data)),
+                    _ => Ok(Val::vector(data)),
+                };
+            }
+
+            // Build augmented matrix [A | B]
             let mut aug: Vec<f64> = vec![0.0; n * (n + b_cols)];
             for i in 0..n {
                 for j in 0..n {
@@ -2322,7 +3806,11 @@ pub fn eval(
                     })
                     .unwrap();
                 if aug[pivot_row * w + col].abs() < 1e-12 {
-                    return Err((span, "Matrix divide: singular matrix"));
+                    return Err((
+                        span,
+                        ErrorKind::Domain,
+                        "Matrix divide: singular matrix".into(),
+                    ));
                 }
                 for j in 0..w {
                     aug.swap(col * w + j, pivot_row * w + j);
@@ -2503,26 +3991,10 @@ pub fn eval(
                 Ok(Val::vector(data))
             }
         }
-        Expr::Mix { span, arg } => {
+        Expr::Mix { arg, .. } => {
             debug!("Monadic Mix");
             let arg_eval = eval(lexer, *arg, env)?;
-            let cells: Vec<Val> = arg_eval
-                .data
-                .iter()
-                .filter_map(|s| match s {
-                    Scalar::Nested(v) => Some((**v).clone()),
-                    _ => None,
-                })
-                .collect();
-            if cells.is_empty() {
-                return Ok(arg_eval);
-            }
-            let cell_shape = cells[0].shape.clone();
-            let mut shape = vec![cells.len()];
-            shape.extend_from_slice(&cell_shape);
-            let data = cells.into_iter().flat_map(|v| v.data).collect();
-            let _ = span;
-            Ok(Val::new(shape, data))
+            Ok(mix(arg_eval))
         }
         Expr::ScalarFloat { span, .. } => {
             debug!("Scalar Float");
@@ -2531,7 +4003,13 @@ pub fn eval(
                 .replace('¯', "-")
                 .parse::<f64>()
                 .map(|num| Val::scalar(Scalar::Float(num)))
-                .map_err(|_| (span, "cannot be represented as a valid number"))
+                .map_err(|_| {
+                    (
+                        span,
+                        ErrorKind::Domain,
+                        "cannot be represented as a valid number".into(),
+                    )
+                })
         }
         Expr::ScalarInteger { span, .. } => {
             debug!("Scalar Integer");
@@ -2540,20 +4018,26 @@ pub fn eval(
                 .replace('¯', "-")
                 .parse::<i64>()
                 .map(|num| Val::scalar(Scalar::Integer(num)))
-                .map_err(|_| (span, "cannot be represented as a valid number"))
+                .map_err(|_| {
+                    (
+                        span,
+                        ErrorKind::Domain,
+                        "cannot be represented as a valid number".into(),
+                    )
+                })
         }
         Expr::Vector { span, elements } => {
             debug!("Vector");
             debug!(?elements, "Vector elements");
 
-            let results: Vec<Result<Val, (Span, &'static str)>> = elements
+            let results: Vec<Result<Val, Error>> = elements
                 .into_iter()
                 .map(|elem| eval(lexer, elem, env))
                 .collect();
 
             if let Some(err) = results.iter().find_map(|r| r.as_ref().err()) {
                 error!(?span, "Error in vector evaluation at span: {:?}", err);
-                return Err(*err);
+                return Err(err.clone());
             }
 
             let data: Vec<Scalar> = results
@@ -1,13 +1,30 @@
+mod axis;
+pub mod errors;
 pub mod eval;
+pub mod namespace;
+pub mod tradfn;
 pub mod val;
 
+use std::sync::{Arc, LazyLock};
+
+use cfgrammar::NewlineCache;
 use cfgrammar::Span;
-use lrlex::{DefaultLexerTypes, lrlex_mod};
-use lrpar::{Lexeme, Lexer, NonStreamingLexer, lrpar_mod};
+use lrlex::{
+    DefaultLexeme, DefaultLexerTypes, LRNonStreamingLexer, LRNonStreamingLexerDef, lrlex_mod,
+};
+use lrpar::{LexError, LexParseError, Lexeme, Lexer, NonStreamingLexer, lrpar_mod};
 
 lrlex_mod!("apiel.l");
 lrpar_mod!("apiel.y");
 
+static LEXERDEF: LazyLock<LRNonStreamingLexerDef<DefaultLexerTypes<u32>>> =
+    LazyLock::new(apiel_l::lexerdef);
+
+/// The lexer definition, built once since compiling its rules is slow.
+pub(crate) fn lexerdef() -> &'static LRNonStreamingLexerDef<DefaultLexerTypes<u32>> {
+    &LEXERDEF
+}
+
 pub use eval::Env;
 use val::{Scalar, Val};
 
@@ -20,338 +37,273 @@ pub fn parse_and_evaluate_with_env(line: &str, env: &mut Env) -> Result<Vec<f64>
     eval_to_val(line, env).map(|val| val.data.into_iter().map(f64::from).collect())
 }
 
-// --- Token-level train rewriting ---
-
-/// A token with its byte span and text, extracted from the lexer.
-struct Tok<'a> {
-    start: usize,
-    end: usize,
-    text: &'a str,
-}
-
-/// Is this token text a primitive dyadic operator (usable in trains)?
-fn is_operator_tok(t: &str) -> bool {
-    matches!(
-        t,
-        "+" | "-"
-            | "×"
-            | "÷"
-            | "*"
-            | "⍟"
-            | "○"
-            | "!"
-            | "?"
-            | "|"
-            | "⌈"
-            | "⌊"
-            | "="
-            | "≠"
-            | "<"
-            | ">"
-            | "≤"
-            | "≥"
-            | "∧"
-            | "∨"
-            | "⍲"
-            | "⍱"
-            | ","
-    )
-}
-
-/// Is this token text a monadic-only function (usable in trains)?
-fn is_monadic_fn_tok(t: &str) -> bool {
-    matches!(
-        t,
-        "⍴" | "⌽"
-            | "⍳"
-            | "⍋"
-            | "⍒"
-            | "≢"
-            | "≡"
-            | "∪"
-            | "⊃"
-            | "⊂"
-            | "⍉"
-            | "~"
-            | "⊣"
-            | "⊢"
-            | "⌹"
-            | "⍸"
-            | "⍷"
-            | "↑"
-            | "↓"
-            | "∊"
-            | "⊆"
-            | "⌷"
-    )
-}
-
-/// Is this token text a pre-composed reduction (single lexer token)?
-fn is_builtin_reduce_tok(t: &str) -> bool {
-    matches!(t, "⌈/" | "⌊/")
-}
-
-/// Is this token text a NAME (identifier)?
-fn is_name_tok(t: &str) -> bool {
-    let mut chars = t.chars();
-    match chars.next() {
-        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
-            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
-        }
-        _ => false,
-    }
-}
-
-/// Is this token text a value (NOT function-like)?
-fn is_value_tok(t: &str) -> bool {
-    // INT, FLOAT, VEC, STRING, OMEGA, ALPHA, braces, brackets, assignment, etc.
-    if t == "⍵" || t == "⍺" || t == "←" || t == "⋄" || t == ":" {
-        return true;
-    }
-    if t.starts_with('\'') {
-        return true; // STRING
-    }
-    if t == "{" || t == "}" || t == "[" || t == "]" {
-        return true;
-    }
-    // Numeric: starts with digit or ¯ followed by digit
-    let first = t.chars().next().unwrap_or(' ');
-    if first.is_ascii_digit() {
-        return true;
-    }
-    if first == '¯' && t.len() > 1 {
-        return true;
-    }
-    false
-}
-
-/// A function reference parsed from the token stream.
-enum TrainFn {
-    /// A primitive operator or monadic function: "+", "≢", "⍴", etc.
-    Simple(String),
-    /// A derived function (reduce/scan): "+/", "×\", "⌈/", etc.
-    Derived(String),
-    /// A named user-defined function
-    Named(String),
-}
-
-impl TrainFn {
-    /// Build monadic application string: `f⍵`
-    fn apply_monadic(&self) -> String {
-        match self {
-            TrainFn::Simple(f) | TrainFn::Derived(f) => format!("{f}⍵"),
-            TrainFn::Named(f) => format!("{f} ⍵"),
-        }
-    }
-
-    /// Return the text of this function reference (for dyadic use between results).
-    fn text(&self) -> &str {
-        match self {
-            TrainFn::Simple(f) | TrainFn::Derived(f) | TrainFn::Named(f) => f,
+/// Scan input for brackets, quotes and comments. Returns the input with
+/// comments and the newlines that don't separate dfn statements (blank or
+/// comment-only lines, newlines right after `{` or before `}`, and newlines
+/// inside `(` or `[`) replaced by spaces, so byte offsets are unchanged, and
+/// whether a bracket or quote is still open.
+fn scan_lines(text: &str) -> (String, bool) {
+    let mut out = String::with_capacity(text.len());
+    let mut open: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut has_content = false;
+    let mut pending_newline: Option<usize> = None;
+
+    for c in text.chars() {
+        if in_comment && c != '\n' {
+            out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+            continue;
         }
-    }
-}
-
-/// Try to parse a sequence of tokens (between parens) as train function references.
-/// Returns None if any token is value-like or the count isn't 2 or 3.
-fn try_parse_train(tokens: &[Tok]) -> Option<Vec<TrainFn>> {
-    if tokens.is_empty() {
-        return None;
-    }
-
-    // Reject if any value-like token is present
-    if tokens.iter().any(|t| is_value_tok(t.text)) {
-        return None;
-    }
-
-    // Reject if nested parens/braces are present
-    if tokens
-        .iter()
-        .any(|t| matches!(t.text, "(" | ")" | "{" | "}"))
-    {
-        return None;
-    }
-
-    let mut fns: Vec<TrainFn> = Vec::new();
-    let mut i = 0;
-
-    while i < tokens.len() {
-        let t = tokens[i].text;
-
-        // Built-in reductions that are single tokens (⌈/ ⌊/)
-        if is_builtin_reduce_tok(t) {
-            fns.push(TrainFn::Derived(t.to_string()));
-            i += 1;
+        in_comment = false;
+        if in_string {
+            in_string = c != '\'';
+            out.push(c);
             continue;
         }
-
-        // Operator possibly followed by / or \ or ⌿ or ⍀ (reduce/scan variants)
-        if is_operator_tok(t) {
-            if i + 1 < tokens.len() && matches!(tokens[i + 1].text, "/" | "\\" | "⌿" | "⍀") {
-                fns.push(TrainFn::Derived(format!("{}{}", t, tokens[i + 1].text)));
-                i += 2;
+        match c {
+            '\n' => {
+                if open.last() == Some(&'{') && has_content {
+                    pending_newline = Some(out.len());
+                    has_content = false;
+                    out.push('\n');
+                } else {
+                    out.push(' ');
+                }
                 continue;
             }
-            fns.push(TrainFn::Simple(t.to_string()));
-            i += 1;
-            continue;
+            '⍝' => {
+                in_comment = true;
+                out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+                continue;
+            }
+            c if c.is_whitespace() => {
+                out.push(c);
+                continue;
+            }
+            _ => {}
         }
-
-        // Monadic-only function
-        if is_monadic_fn_tok(t) {
-            fns.push(TrainFn::Simple(t.to_string()));
-            i += 1;
-            continue;
+        // A statement separator with nothing after it is not needed
+        if let (Some(at), '}' | '⋄') = (pending_newline, c) {
+            out.replace_range(at..at + 1, " ");
         }
-
-        // NAME (user-defined function)
-        if is_name_tok(t) {
-            fns.push(TrainFn::Named(t.to_string()));
-            i += 1;
-            continue;
+        pending_newline = None;
+        has_content = !matches!(c, '{' | '⋄');
+        match c {
+            '\'' => in_string = true,
+            '{' | '(' | '[' => open.push(c),
+            '}' | ')' | ']' => {
+                open.pop();
+            }
+            _ => {}
         }
-
-        // Unrecognized token -> not a train
-        return None;
+        out.push(c);
     }
+    (out, in_string || !open.is_empty())
+}
 
-    if fns.len() == 2 || fns.len() == 3 {
-        Some(fns)
+/// Is this input complete, or is a `{`, `(`, `[` or `'` still open, or a
+/// `∇` definition not yet closed, so that more lines are needed? Front-ends use this to show a continuation prompt.
+pub fn is_complete(text: &str) -> bool {
+    if tradfn::is_definition(text) {
+        tradfn::is_closed(text)
     } else {
-        None
+        !scan_lines(text).1
     }
 }
 
-/// Build a monadic dfn string from train function references.
-fn build_train_dfn_monadic(fns: &[TrainFn]) -> String {
-    if fns.len() == 3 {
-        // Fork: (f g h) -> {(f⍵)g(h⍵)}
-        let f_app = fns[0].apply_monadic();
-        let g = fns[1].text();
-        let h_app = fns[2].apply_monadic();
-        format!("{{({f_app}){g}({h_app})}}")
-    } else {
-        // Atop: (f g) -> {f(g⍵)}
-        let f = fns[0].text();
-        let g_app = fns[1].apply_monadic();
-        format!("{{{f}({g_app})}}")
-    }
+/// A failed evaluation, with the 1-based position in the input it refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+    pub kind: EvalErrorKind,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
 }
 
-/// Build a dyadic dfn string from train function references.
-fn build_train_dfn_dyadic(fns: &[TrainFn]) -> String {
-    if fns.len() == 3 {
-        // Fork: ⍺(f g h)⍵ -> {(⍺ f ⍵)g(⍺ h ⍵)}
-        let f = fns[0].text();
-        let g = fns[1].text();
-        let h = fns[2].text();
-        format!("{{(⍺{f}⍵){g}(⍺{h}⍵)}}")
-    } else {
-        // Atop: ⍺(f g)⍵ -> {f(⍺ g ⍵)}
-        let f = fns[0].text();
-        let g = fns[1].text();
-        format!("{{{f}(⍺{g}⍵)}}")
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalErrorKind {
+    Parse,
+    Eval,
 }
 
-/// Check if a token text represents a value (could be left arg of a dyadic train).
-fn is_left_arg_tok(t: &str) -> bool {
-    if t == ")" || t == "]" || t == "⍵" || t == "⍺" {
-        return true;
-    }
-    if t.starts_with('\'') {
-        return true; // STRING
-    }
-    let first = t.chars().next().unwrap_or(' ');
-    if first.is_ascii_digit() || first == '¯' {
-        return true;
-    }
-    // NAME (identifier) — could be variable as left arg
-    if is_name_tok(t) {
-        return true;
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            EvalErrorKind::Parse => write!(f, "{}", self.message),
+            EvalErrorKind::Eval => write!(
+                f,
+                "Evaluation error at line {} column {}: {}",
+                self.line, self.column, self.message
+            ),
+        }
     }
-    false
 }
 
-/// Rewrite train patterns using token-level analysis.
-///
-/// Tokenizes the input with the lexer, identifies parenthesized groups containing
-/// only function-like tokens (operators, monadic functions, derived functions, names),
-/// and rewrites them as dfn expressions.
-fn rewrite_trains(input: &str) -> String {
-    let lexerdef = apiel_l::lexerdef();
-    let lexer = lexerdef.lexer(input);
-
-    // Collect tokens with byte spans
-    let tokens: Vec<Tok> = lexer
-        .iter()
-        .filter_map(|r| r.ok())
-        .map(|tok| {
-            let s = tok.span();
-            Tok {
-                start: s.start(),
-                end: s.end(),
-                text: &input[s.start()..s.end()],
-            }
-        })
-        .collect();
-
-    // Find parenthesized groups and check for trains
-    // Collect (paren_open_byte, paren_close_byte_end, replacement_string)
-    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
-
-    let mut i = 0;
-    while i < tokens.len() {
-        if tokens[i].text == "(" {
-            // Find matching ) at depth 0
-            let mut depth = 1;
-            let mut j = i + 1;
-            while j < tokens.len() && depth > 0 {
-                match tokens[j].text {
-                    "(" => depth += 1,
-                    ")" => depth -= 1,
-                    _ => {}
-                }
-                if depth > 0 {
-                    j += 1;
+impl std::error::Error for EvalError {}
+
+pub fn eval_to_val(line: &str, env: &mut Env) -> Result<Val, String> {
+    eval_line(line, env).map_err(|err| err.to_string())
+}
+
+/// Like [`eval_to_val`], but reports failures as a structured [`EvalError`].
+pub fn eval_line(line: &str, env: &mut Env) -> Result<Val, EvalError> {
+    eval_statement(line, env).map(|(val, _)| val)
+}
+
+/// Retag tokens whose role depends on what `env` defines, as the grammar
+/// can only tell them apart by token. Names of dops become operator tokens,
+/// except a name being assigned so it can be redefined. The `←` of an
+/// assignment of a function, such as `avg←+/÷≢`, becomes `FNASSIGN`, and
+/// the `(` of a parenthesised function, such as the train `(+/÷≢)`,
+/// becomes `FNGROUP`. A function name with a primitive operator, such as
+/// `f¨` or `f/`, becomes `FNNAME`, as a name before `/` is otherwise a
+/// value.
+fn retag<'lexer, 'input>(
+    lexer: LRNonStreamingLexer<'lexer, 'input, DefaultLexerTypes<u32>>,
+    line: &'input str,
+    env: &Env,
+) -> LRNonStreamingLexer<'lexer, 'input, DefaultLexerTypes<u32>> {
+    let Ok(mut lexemes) = lexer.iter().collect::<Result<Vec<_>, _>>() else {
+        return lexer;
+    };
+    let mut changed = false;
+    for i in 0..lexemes.len() {
+        let assigned = lexemes
+            .get(i + 1)
+            .is_some_and(|next| next.tok_id() == apiel_l::T_ASSIGN);
+        if lexemes[i].tok_id() != apiel_l::T_NAME || assigned {
+            continue;
+        }
+        let span = lexemes[i].span();
+        let tok_id = match env.get_fn(&line[span.start()..span.end()]) {
+            Some(eval::StoredFn::Dfn(dfn)) if dfn.is_dyadic_operator() => apiel_l::T_DYADICOP,
+            Some(f) if f.is_operator() => apiel_l::T_MONADICOP,
+            Some(eval::StoredFn::Tradfn(f)) if f.right.is_none() => continue,
+            Some(_) if operand_of_primitive(&lexemes, i, line) => apiel_l::T_FNNAME,
+            _ => continue,
+        };
+        lexemes[i] = DefaultLexeme::new(tok_id, span.start(), span.len());
+        changed = true;
+    }
+    // Right to left, so a parenthesised function inside another is
+    // already retagged when the outer one is tried
+    for i in (0..lexemes.len()).rev() {
+        let span = lexemes[i].span();
+        let rest = &lexemes[i + 1..];
+        let end = statement_end(rest, line);
+        let tok_id = match (lexemes[i].tok_id(), &line[span.start()..span.end()]) {
+            // `name←` followed by a function, rather than a dfn or a value
+            (tok, _) if tok == apiel_l::T_ASSIGN => {
+                let named = i > 0 && lexemes[i - 1].tok_id() == apiel_l::T_NAME;
+                let lone_dfn = rest.first().is_some_and(|first| {
+                    &line[first.span().start()..first.span().end()] == "{"
+                        && statement_end(&rest[1..], line) + 2 == end
+                });
+                if !named || lone_dfn || !is_function(&rest[..end], line, env) {
+                    continue;
                 }
+                apiel_l::T_FNASSIGN
             }
-            if depth == 0 && j > i + 1 {
-                // Inner tokens: i+1 .. j (exclusive of parens)
-                let inner = &tokens[i + 1..j];
-                if let Some(fns) = try_parse_train(inner) {
-                    // Check if previous token is a value (dyadic context)
-                    let is_dyadic = i > 0 && is_left_arg_tok(tokens[i - 1].text);
-                    let replacement = if is_dyadic {
-                        build_train_dfn_dyadic(&fns)
-                    } else {
-                        build_train_dfn_monadic(&fns)
-                    };
-                    replacements.push((tokens[i].start, tokens[j].end, replacement));
-                    i = j + 1;
+            // `(` of a parenthesised function expression, such as a train
+            (_, "(") => {
+                let closed = rest
+                    .get(end)
+                    .is_some_and(|close| &line[close.span().start()..close.span().end()] == ")");
+                if !closed || !is_function(&rest[..end], line, env) {
                     continue;
                 }
+                apiel_l::T_FNGROUP
             }
-        }
-        i += 1;
+            _ => continue,
+        };
+        lexemes[i] = DefaultLexeme::new(tok_id, span.start(), span.len());
+        changed = true;
     }
+    if !changed {
+        return lexer;
+    }
+    let mut newlines = NewlineCache::new();
+    newlines.feed(line);
+    LRNonStreamingLexer::new(line, lexemes.into_iter().map(Ok).collect(), newlines)
+}
 
-    if replacements.is_empty() {
-        return input.to_string();
+/// Is the name at `i` the operand of a primitive operator, as in `f¨`,
+/// `f/` or `∘.f`?
+fn operand_of_primitive(lexemes: &[DefaultLexeme<u32>], i: usize, line: &str) -> bool {
+    use apiel_l::*;
+    let text = |lexeme: &DefaultLexeme<u32>| &line[lexeme.span().start()..lexeme.span().end()];
+    let before = i.checked_sub(1).map(|j| &lexemes[j]);
+    lexemes.get(i + 1).is_some_and(|next| {
+        [T_EACH, T_REDUCEFIRST, T_SCANFIRST, T_COMMUTE, T_DOT].contains(&next.tok_id())
+            || matches!(text(next), "/" | "\\")
+    }) || before.is_some_and(|prev| [T_OUTERPRODUCT, T_DOT].contains(&prev.tok_id()))
+}
+
+/// How many of `tokens` there are before the end of the statement or an
+/// unmatched `)`, `}` or `]`.
+fn statement_end(tokens: &[DefaultLexeme<u32>], line: &str) -> usize {
+    let mut depth = 0;
+    for (i, lexeme) in tokens.iter().enumerate() {
+        let tok = lexeme.tok_id();
+        match &line[lexeme.span().start()..lexeme.span().end()] {
+            "(" | "{" | "[" => depth += 1,
+            ")" | "}" | "]" if depth == 0 => return i,
+            ")" | "}" | "]" => depth -= 1,
+            _ if depth == 0 && (tok == apiel_l::T_DIAMOND || tok == apiel_l::T_NEWLINE) => {
+                return i;
+            }
+            _ => {}
+        }
     }
+    tokens.len()
+}
 
-    // Apply replacements in reverse order so byte offsets stay valid
-    let mut result = input.to_string();
-    for (start, end, replacement) in replacements.into_iter().rev() {
-        result.replace_range(start..end, &replacement);
+/// Do `tokens` form a function expression, such as `+/÷≢`, `2∘+` or
+/// `{⍵×2} twice`? The grammar decides, parsing them after an `FNEXPR`
+/// token. Only names depend on `env`: outside of dfn bodies, a name that
+/// isn't a function is taken as a value.
+fn is_function(tokens: &[DefaultLexeme<u32>], line: &str, env: &Env) -> bool {
+    use apiel_l::*;
+    let mut braces = 0;
+    let mut probe = vec![DefaultLexeme::new(T_FNEXPR, 0, 0)];
+    for lexeme in tokens {
+        let span = lexeme.span();
+        let text = &line[span.start()..span.end()];
+        match text {
+            "{" => braces += 1,
+            "}" => braces -= 1,
+            _ => {}
+        }
+        let value = braces == 0
+            && lexeme.tok_id() == T_NAME
+            && match env.get_fn(text) {
+                Some(eval::StoredFn::Tradfn(f)) => f.right.is_none(),
+                Some(_) => false,
+                None => true,
+            };
+        probe.push(match value {
+            true => DefaultLexeme::new(T_ZILDE, span.start(), span.len()),
+            false => *lexeme,
+        });
     }
-    result
+    let mut newlines = NewlineCache::new();
+    newlines.feed(line);
+    let lexer = LRNonStreamingLexer::new(line, probe.into_iter().map(Ok).collect(), newlines);
+    let (res, errs) = apiel_y::parse(&lexer);
+    errs.is_empty() && matches!(res, Some(Ok(_)))
 }
 
-pub fn eval_to_val(line: &str, env: &mut Env) -> Result<Val, String> {
-    let line = &rewrite_trains(line);
-    let lexerdef = apiel_l::lexerdef();
-    let lexer = lexerdef.lexer(line);
+/// Parse one statement, with the functions and operators defined in `env`.
+/// Returns the text its spans refer to, `source` with comments blanked out,
+/// and its syntax tree.
+pub(crate) fn parse_statement(
+    source: &str,
+    env: &Env,
+) -> Result<(String, apiel_y::Expr), EvalError> {
+    let (line, _) = scan_lines(source);
+    let lexerdef = lexerdef();
+    let lexer = retag(lexerdef.lexer(&line), &line, env);
 
     {
         let mut tokens = String::new();
@@ -369,23 +321,170 @@ pub fn eval_to_val(line: &str, env: &mut Env) -> Result<Val, String> {
 
     let (res, errs) = apiel_y::parse(&lexer);
 
-    if !errs.is_empty() {
-        return Err(format!("Parse error: {:?}", errs));
+    if let Some(first) = errs.first() {
+        let span = match first {
+            LexParseError::LexError(e) => e.span(),
+            LexParseError::ParseError(e) => e.lexeme().span(),
+        };
+        let (line, column) = line_col(source, span.start());
+        return Err(EvalError {
+            kind: EvalErrorKind::Parse,
+            line,
+            column,
+            message: format!("Parse error: {:?}", errs),
+        });
+    }
+
+    match res {
+        Some(Ok(expr)) => Ok((line, expr)),
+        _ => Err(EvalError {
+            kind: EvalErrorKind::Parse,
+            line: 1,
+            column: 1,
+            message: "Failed to evaluate expression".to_string(),
+        }),
+    }
+}
+
+/// Evaluate one statement, also reporting whether its result is shy (an
+/// assignment or definition whose value APL does not display).
+fn eval_statement(source: &str, env: &mut Env) -> Result<(Val, bool), EvalError> {
+    if tradfn::is_definition(source) {
+        let f = tradfn::define(source, env)?;
+        Arc::make_mut(&mut env.fns).insert(f.name.clone(), eval::StoredFn::Tradfn(Arc::new(f)));
+        return Ok((Val::scalar(Scalar::Integer(0)), true));
     }
 
-    if let Some(Ok(r)) = res {
-        eval::eval(&lexer, r, env).map_err(|(span, msg)| {
-            let ((line, col), _) = lexer.line_col(span);
-            format!(
-                "Evaluation error at line {} column {}: '{}', {}.",
+    let (line, r) = parse_statement(source, env)?;
+    let lexerdef = lexerdef();
+    let lexer = lexerdef.lexer(&line);
+    // Calling a tradfn that has no result displays nothing
+    let no_result = |name: &String| matches!(env.fns.get(name), Some(eval::StoredFn::Tradfn(f)) if f.result.is_none());
+    let shy = match &r {
+        apiel_y::Expr::Assign { .. }
+        | apiel_y::Expr::AssignDfn { .. }
+        | apiel_y::Expr::AssignFn { .. }
+        | apiel_y::Expr::ModifiedAssign { .. }
+        | apiel_y::Expr::IndexedAssign { .. }
+        | apiel_y::Expr::SysAssign { .. } => true,
+        apiel_y::Expr::SysDyadic { name, .. } => name == "⎕NS",
+        apiel_y::Expr::Variable { name, .. }
+        | apiel_y::Expr::NamedMonadic { name, .. }
+        | apiel_y::Expr::NamedDyadic { name, .. } => no_result(name),
+        _ => false,
+    };
+    eval::eval(&lexer, r, env)
+        .map(|val| (val, shy))
+        .map_err(|(span, kind, msg)| {
+            env.record_error(kind, &msg);
+            let (line, column) = line_col(source, span.start());
+            EvalError {
+                kind: EvalErrorKind::Eval,
                 line,
-                col,
-                lexer.span_str(span),
-                msg
-            )
+                column,
+                message: format!("'{}', {}.", lexer.span_str(span), msg),
+            }
         })
+}
+
+/// Parse a function definition, `name←{...}`, a tacit `name←+/÷≢` or a `∇`
+/// tradfn, without defining it. Operators used in it are looked up in `env`.
+pub(crate) fn define_fn(source: &str, env: &Env) -> Result<eval::StoredFn, EvalError> {
+    if tradfn::is_definition(source) {
+        return Ok(eval::StoredFn::Tradfn(Arc::new(tradfn::define(
+            source, env,
+        )?)));
+    }
+    match parse_statement(source, env)? {
+        (line, apiel_y::Expr::AssignDfn { span, body, .. }) => {
+            let lexerdef = lexerdef();
+            let lexer = lexerdef.lexer(&line);
+            Ok(eval::StoredFn::Dfn(eval::StoredDfn::new(
+                Arc::new(*body),
+                &lexer,
+                span,
+            )))
+        }
+        (line, apiel_y::Expr::AssignFn { span, func, .. }) => {
+            let lexerdef = lexerdef();
+            let lexer = lexerdef.lexer(&line);
+            eval::tacit_fn(&lexer, span, *func, env).map_err(|(span, _, msg)| {
+                let (line, column) = line_col(source, span.start());
+                EvalError {
+                    kind: EvalErrorKind::Eval,
+                    line,
+                    column,
+                    message: msg.to_string(),
+                }
+            })
+        }
+        _ => Err(EvalError {
+            kind: EvalErrorKind::Parse,
+            line: 1,
+            column: 1,
+            message: "Not a function definition".to_string(),
+        }),
+    }
+}
+
+/// 1-based line and column of a byte offset. Spans index the scanned text,
+/// which has the same byte offsets as `source` but fewer newlines.
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = source.get(..offset).unwrap_or(source);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Run an APL script in `env`, passing each displayable result to `output`.
+/// Statements end at the end of a line unless a bracket or quote is still
+/// open, so dfns can span several lines. Blank lines, comment lines and a
+/// leading `#!` line are skipped. On failure, the error's `line` is the line
+/// number within the script.
+pub fn run_script(
+    source: &str,
+    env: &mut Env,
+    mut output: impl FnMut(&Val),
+) -> Result<(), EvalError> {
+    let mut statement = String::new();
+    let mut first_line = 0;
+    for (index, line) in source.lines().enumerate() {
+        if statement.is_empty() {
+            let code = line.trim();
+            if code.is_empty() || code.starts_with('⍝') || (index == 0 && line.starts_with("#!"))
+            {
+                continue;
+            }
+            first_line = index;
+        } else {
+            statement.push('\n');
+        }
+        statement.push_str(line);
+        if !is_complete(&statement) {
+            continue;
+        }
+        match eval_statement(&std::mem::take(&mut statement), env) {
+            Ok((_, true)) => {}
+            Ok((val, false)) => output(&val),
+            Err(err) => {
+                return Err(EvalError {
+                    line: first_line + err.line,
+                    ..err
+                });
+            }
+        }
+    }
+    if statement.is_empty() {
+        Ok(())
     } else {
-        Err("Failed to evaluate expression".to_string())
+        Err(EvalError {
+            kind: EvalErrorKind::Parse,
+            line: first_line + 1,
+            column: 1,
+            message: "Unterminated statement: missing closing bracket or quote".to_string(),
+        })
     }
 }
 
@@ -408,6 +507,7 @@ pub fn format_val(val: &Val) -> String {
                 Scalar::Float(f) => format!("{f}"),
                 Scalar::Char(c) => format!("{c}"),
                 Scalar::Nested(v) => format!("({})", format_val(v)),
+                Scalar::Namespace(_) => "#.[Namespace]".to_string(),
             })
             .collect::<Vec<_>>()
             .join(" ")
@@ -0,0 +1,289 @@
+//! Namespaces: shared, mutable containers of variables and functions,
+//! created by `⎕NS` and reached with dotted names such as `cfg.rate`, and
+//! the scopes names are looked up in.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::Env;
+use super::errors::ErrorKind;
+use super::eval::StoredFn;
+use super::val::{Scalar, Val};
+
+/// A reference to a namespace. Namespaces are first-class values held in a
+/// [`Scalar::Namespace`]; cloning the reference shares the members, so an
+/// assignment through one reference is seen through every other.
+#[derive(Debug, Clone, Default)]
+pub struct Namespace(Arc<Mutex<Members>>);
+
+/// The contents of a namespace.
+#[derive(Debug, Default)]
+pub struct Members {
+    pub vars: HashMap<String, Val>,
+    pub fns: HashMap<String, StoredFn>,
+}
+
+impl Namespace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Members> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Are both references to the same namespace?
+    pub fn same(&self, other: &Namespace) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// An address identifying the namespace, for detecting shared references.
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+
+    /// This namespace as a scalar value.
+    pub fn to_val(&self) -> Val {
+        Val::scalar(Scalar::Namespace(self.clone()))
+    }
+}
+
+/// `⎕NL`: sorted names of variables (class 2), functions (3), operators (4)
+/// and namespaces (9). Names such as `⍵` and `∇` are not listed.
+pub fn names_of_class(
+    vars: &HashMap<String, Val>,
+    fns: &HashMap<String, StoredFn>,
+    classes: &[i64],
+) -> Vec<String> {
+    let mut names: Vec<String> = vars
+        .iter()
+        .filter(|(_, val)| {
+            let class = if as_namespace(val).is_some() { 9 } else { 2 };
+            classes.contains(&class)
+        })
+        .map(|(name, _)| name.clone())
+        .chain(
+            fns.iter()
+                .filter(|(_, f)| classes.contains(&if f.is_operator() { 4 } else { 3 }))
+                .map(|(name, _)| name.clone()),
+        )
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+/// The names of a caller, seen by the functions it calls: its own names and
+/// the namespace it runs in, then those of its callers.
+#[derive(Debug)]
+pub(crate) struct Scope {
+    vars: Arc<HashMap<String, Val>>,
+    fns: Arc<HashMap<String, StoredFn>>,
+    ns: Option<Namespace>,
+    outer: Option<Arc<Scope>>,
+}
+
+/// One level of a scope chain: its variables, functions and namespace.
+type Level<'a> = (
+    &'a HashMap<String, Val>,
+    &'a HashMap<String, StoredFn>,
+    Option<&'a Namespace>,
+);
+
+/// The namespace held by a scalar value, if any.
+pub fn as_namespace(val: &Val) -> Option<Namespace> {
+    match val.data.as_slice() {
+        [Scalar::Namespace(ns)] if val.shape.is_empty() => Some(ns.clone()),
+        _ => None,
+    }
+}
+
+/// Split `a.b.c` into the namespace path `a.b` and the member name `c`.
+pub(crate) fn split_name(name: &str) -> (Option<&str>, &str) {
+    match name.rsplit_once('.') {
+        Some((path, member)) => (Some(path), member),
+        None => (None, name),
+    }
+}
+
+impl Env {
+    /// Create an empty namespace and assign it to `name`, which may itself
+    /// be dotted to nest it inside an existing namespace.
+    pub fn create_namespace(&mut self, name: &str) -> Result<Namespace, String> {
+        let ns = Namespace::new();
+        self.set_var(name, ns.to_val())
+            .map_err(|(_, msg)| msg.to_string())?;
+        Ok(ns)
+    }
+
+    /// A scope for a function called from this one, running in `ns`, or in
+    /// this one's namespace if `None`. It starts with no names of its own,
+    /// and sees this scope's names without copying them.
+    pub(crate) fn scope(&self, ns: Option<Namespace>) -> Env {
+        let mut env = self.fork();
+        env.outer = Some(Arc::new(Scope {
+            vars: std::mem::take(&mut env.vars),
+            fns: std::mem::take(&mut env.fns),
+            ns: env.ns.clone(),
+            outer: env.outer.take(),
+        }));
+        if ns.is_some() {
+            env.ns = ns;
+        }
+        env
+    }
+
+    /// The levels names are looked up in, innermost first.
+    fn levels(&self) -> impl Iterator<Item = Level<'_>> {
+        let outer = std::iter::successors(self.outer.as_deref(), |scope| scope.outer.as_deref());
+        std::iter::once((&*self.vars, &*self.fns, self.ns.as_ref()))
+            .chain(outer.map(|scope| (&*scope.vars, &*scope.fns, scope.ns.as_ref())))
+    }
+
+    /// Every variable and function visible here, as for `⎕NL`.
+    pub(crate) fn visible(&self) -> (HashMap<String, Val>, HashMap<String, StoredFn>) {
+        let levels: Vec<Level> = self.levels().collect();
+        let (mut vars, mut fns) = (HashMap::new(), HashMap::new());
+        for (level_vars, level_fns, ns) in levels.into_iter().rev() {
+            if let Some(ns) = ns {
+                let members = ns.lock();
+                vars.extend(members.vars.clone());
+                fns.extend(members.fns.clone());
+            }
+            vars.extend(level_vars.clone());
+            fns.extend(level_fns.clone());
+        }
+        (vars, fns)
+    }
+
+    /// The namespace named by a dotted path such as `a.b`.
+    pub(crate) fn namespace_at(&self, path: &str) -> Result<Namespace, (ErrorKind, &'static str)> {
+        let mut segments = path.split('.');
+        let first = segments.next().unwrap_or_default();
+        let mut ns = self
+            .get_var(first)
+            .ok_or((ErrorKind::Value, "Undefined namespace"))
+            .and_then(|val| as_namespace(&val).ok_or((ErrorKind::Domain, "Not a namespace")))?;
+        for segment in segments {
+            let next = ns
+                .lock()
+                .vars
+                .get(segment)
+                .ok_or((ErrorKind::Value, "Undefined namespace"))
+                .and_then(|val| as_namespace(val).ok_or((ErrorKind::Domain, "Not a namespace")))?;
+            ns = next;
+        }
+        Ok(ns)
+    }
+
+    /// The value of a variable, which may be a dotted name.
+    pub fn get_var(&self, name: &str) -> Option<Val> {
+        match split_name(name) {
+            (None, name) => self.levels().find_map(|(vars, _, ns)| {
+                vars.get(name)
+                    .cloned()
+                    .or_else(|| ns?.lock().vars.get(name).cloned())
+            }),
+            (Some(path), member) => self
+                .namespace_at(path)
+                .ok()?
+                .lock()
+                .vars
+                .get(member)
+                .cloned(),
+        }
+    }
+
+    /// Assign a variable, which may be a dotted name.
+    pub fn set_var(&mut self, name: &str, val: Val) -> Result<(), (ErrorKind, &'static str)> {
+        match split_name(name) {
+            (None, name) => {
+                Arc::make_mut(&mut self.vars).insert(name.to_string(), val);
+            }
+            (Some(path), member) => {
+                self.namespace_at(path)?
+                    .lock()
+                    .vars
+                    .insert(member.to_string(), val);
+            }
+        }
+        Ok(())
+    }
+
+    /// Erase a variable, which may be a dotted name, returning its value.
+    pub fn remove_var(&mut self, name: &str) -> Option<Val> {
+        match split_name(name) {
+            (None, name) => match self.vars.contains_key(name) {
+                true => Arc::make_mut(&mut self.vars).remove(name),
+                false => None,
+            },
+            (Some(path), member) => self.namespace_at(path).ok()?.lock().vars.remove(member),
+        }
+    }
+
+    /// A named function, which may be a dotted name.
+    pub fn get_fn(&self, name: &str) -> Option<StoredFn> {
+        match split_name(name) {
+            (None, name) => self.levels().find_map(|(_, fns, ns)| {
+                fns.get(name)
+                    .cloned()
+                    .or_else(|| ns?.lock().fns.get(name).cloned())
+            }),
+            (Some(path), member) => self
+                .namespace_at(path)
+                .ok()?
+                .lock()
+                .fns
+                .get(member)
+                .cloned(),
+        }
+    }
+
+    /// Define a function, which may be a dotted name.
+    pub fn set_fn(&mut self, name: &str, f: StoredFn) -> Result<(), (ErrorKind, &'static str)> {
+        match split_name(name) {
+            (None, name) => {
+                Arc::make_mut(&mut self.fns).insert(name.to_string(), f);
+            }
+            (Some(path), member) => {
+                self.namespace_at(path)?
+                    .lock()
+                    .fns
+                    .insert(member.to_string(), f);
+            }
+        }
+        Ok(())
+    }
+
+    /// Erase a function, which may be a dotted name, returning it.
+    pub fn remove_fn(&mut self, name: &str) -> Option<StoredFn> {
+        match split_name(name) {
+            (None, name) => match self.fns.contains_key(name) {
+                true => Arc::make_mut(&mut self.fns).remove(name),
+                false => None,
+            },
+            (Some(path), member) => self.namespace_at(path).ok()?.lock().fns.remove(member),
+        }
+    }
+
+    /// Assign a variable that already exists, as `x+←1` does. A member of
+    /// the namespace the function runs in is changed there, not shadowed
+    /// by a local.
+    pub(crate) fn update_var(
+        &mut self,
+        name: &str,
+        val: Val,
+    ) -> Result<(), (ErrorKind, &'static str)> {
+        if let (None, member) = split_name(name)
+            && !self.vars.contains_key(member)
+            && let Some(ns) = &self.ns
+        {
+            let mut members = ns.lock();
+            if let Some(slot) = members.vars.get_mut(member) {
+                *slot = val;
+                return Ok(());
+            }
+        }
+        self.set_var(name, val)
+    }
+}
@@ -0,0 +1,463 @@
+//! Traditional functions: a `∇` header naming the result, arguments and
+//! localized names, a body of statements and control structures, and a
+//! closing `∇`.
+//!
+//! ```text
+//! ∇ r←a Foo b;tmp
+//!   :If a>b
+//!       r←a
+//!   :Else
+//!       tmp←b
+//!       r←tmp
+//!   :EndIf
+//! ∇
+//! ```
+//!
+//! Unlike dfns, tradfns run in the caller's environment with dynamic
+//! scoping: the header names are saved on entry and restored on exit, and
+//! any other name the body assigns is visible to the caller.
+
+use std::sync::Arc;
+
+use lrpar::NonStreamingLexer;
+
+use super::apiel_y::Expr;
+use super::errors::ErrorKind;
+use super::eval::{Env, eval};
+use super::val::{Scalar, Val};
+use super::{EvalError, EvalErrorKind, lexerdef, parse_statement, scan_lines};
+
+/// A parsed traditional function.
+#[derive(Debug)]
+pub struct Tradfn {
+    pub name: String,
+    pub result: Option<String>,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub locals: Vec<String>,
+    /// The full definition, from the opening `∇` to the closing one.
+    pub source: Arc<str>,
+    body: Vec<Stmt>,
+}
+
+/// A body line parsed as an APL statement. `number` is the line number in
+/// the definition, counting the header as line 0 as APL does.
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    text: String,
+    expr: Expr,
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Expr(Line),
+    If {
+        branches: Vec<(Line, Vec<Stmt>)>,
+        otherwise: Vec<Stmt>,
+    },
+    For {
+        var: String,
+        values: Line,
+        body: Vec<Stmt>,
+    },
+    While {
+        cond: Line,
+        body: Vec<Stmt>,
+    },
+    Repeat {
+        body: Vec<Stmt>,
+        until: Option<Line>,
+    },
+    Return,
+}
+
+/// A body line classified by its leading control word. Expression arguments
+/// keep their columns, with the control word blanked out.
+enum Control {
+    If(String),
+    ElseIf(String),
+    Else,
+    End(String),
+    For(String, String),
+    While(String),
+    Repeat,
+    Until(String),
+    Return,
+    Statement,
+}
+
+enum Flow {
+    Next,
+    Return,
+}
+
+/// Does this input start a tradfn definition?
+pub(crate) fn is_definition(text: &str) -> bool {
+    text.trim_start().starts_with('∇')
+}
+
+/// Has a tradfn definition reached its closing `∇` line?
+pub(crate) fn is_closed(text: &str) -> bool {
+    text.lines().skip(1).any(|line| line.trim() == "∇")
+}
+
+/// Parse a complete definition, from the `∇` header to the closing `∇`.
+/// Operators used in the body are looked up in `env`.
+pub(crate) fn define(source: &str, env: &Env) -> Result<Tradfn, EvalError> {
+    let lines: Vec<&str> = source.lines().collect();
+    let close = lines
+        .iter()
+        .skip(1)
+        .position(|line| line.trim() == "∇")
+        .map(|i| i + 1)
+        .ok_or_else(|| error(lines.len(), "Missing closing ∇"))?;
+    if let Some(extra) = lines[close + 1..].iter().position(|l| !l.trim().is_empty()) {
+        return Err(error(close + extra + 1, "Unexpected text after closing ∇"));
+    }
+
+    let header = scan_lines(lines[0]).0;
+    let header = header.trim_start().trim_start_matches('∇');
+    let mut parts = header.split(';');
+    let signature = parts.next().unwrap_or_default();
+    let locals: Vec<String> = parts.map(|name| name.trim().to_string()).collect();
+
+    let (result, call) = match signature.split_once('←') {
+        Some((result, call)) => (Some(unbrace(result)), call),
+        None => (None, signature),
+    };
+    let words: Vec<String> = call.split_whitespace().map(unbrace).collect();
+    let (left, name, right) = match words.as_slice() {
+        [name] => (None, name.clone(), None),
+        [name, right] => (None, name.clone(), Some(right.clone())),
+        [left, name, right] => (Some(left.clone()), name.clone(), Some(right.clone())),
+        _ => return Err(error(0, "Malformed tradfn header")),
+    };
+    let names = [&result, &left, &right]
+        .into_iter()
+        .flatten()
+        .chain(&locals)
+        .chain([&name]);
+    if let Some(bad) = names.into_iter().find(|n| !is_name(n)) {
+        return Err(error(0, &format!("Invalid name '{bad}' in tradfn header")));
+    }
+
+    let numbered: Vec<(usize, &str)> = (1..close).map(|i| (i, lines[i])).collect();
+    let mut iter = numbered.iter();
+    let body = match parse_block(&mut iter, env)? {
+        (body, None) => body,
+        (_, Some((number, _))) => return Err(error(number, "Unexpected control word")),
+    };
+
+    Ok(Tradfn {
+        name,
+        result,
+        left,
+        right,
+        locals,
+        source: source.trim_end().into(),
+        body,
+    })
+}
+
+/// Call a tradfn in `env`. Errors name the function and line, `Foo[2]`.
+pub(crate) fn call(
+    f: &Tradfn,
+    left: Option<Val>,
+    right: Option<Val>,
+    env: &mut Env,
+) -> Result<Val, (ErrorKind, String)> {
+    let valence = |msg: &str| Err((ErrorKind::Syntax, format!("{} {msg}", f.name)));
+    if left.is_some() && f.left.is_none() {
+        return valence("does not take a left argument");
+    }
+    if right.is_none() && f.right.is_some() {
+        return valence("requires an argument");
+    }
+    if right.is_some() && f.right.is_none() {
+        return valence("does not take an argument");
+    }
+
+    let mut localized: Vec<&String> = [&f.result, &f.left, &f.right]
+        .into_iter()
+        .flatten()
+        .chain(&f.locals)
+        .collect();
+    localized.sort_unstable();
+    localized.dedup();
+    let saved: Vec<(&String, Option<Val>)> = localized
+        .into_iter()
+        .map(|name| (name, Arc::make_mut(&mut env.vars).remove(name)))
+        .collect();
+
+    for (name, val) in [(&f.left, left), (&f.right, right)] {
+        if let (Some(name), Some(val)) = (name, val) {
+            Arc::make_mut(&mut env.vars).insert(name.clone(), val);
+        }
+    }
+    let outcome =
+        exec(f, &f.body, env).map(|_| f.result.as_ref().map(|name| env.vars.get(name).cloned()));
+
+    for (name, val) in saved {
+        match val {
+            Some(val) => Arc::make_mut(&mut env.vars).insert(name.clone(), val),
+            None => Arc::make_mut(&mut env.vars).remove(name),
+        };
+    }
+
+    match outcome? {
+        Some(Some(val)) => Ok(val),
+        Some(None) => Err((
+            ErrorKind::Value,
+            format!("Result of {} was not set", f.name),
+        )),
+        None => Ok(Val::vector(Vec::new())),
+    }
+}
+
+fn exec(f: &Tradfn, body: &[Stmt], env: &mut Env) -> Result<Flow, (ErrorKind, String)> {
+    for stmt in body {
+        match stmt {
+            Stmt::Expr(line) => {
+                eval_line(f, line, env)?;
+            }
+            Stmt::If {
+                branches,
+                otherwise,
+            } => {
+                let mut chosen = otherwise;
+                for (cond, branch) in branches {
+                    if eval_cond(f, cond, env)? {
+                        chosen = branch;
+                        break;
+                    }
+                }
+                if let Flow::Return = exec(f, chosen, env)? {
+                    return Ok(Flow::Return);
+                }
+            }
+            Stmt::For { var, values, body } => {
+                let values = eval_line(f, values, env)?;
+                for item in values.data {
+                    let item = match item {
+                        Scalar::Nested(v) => *v,
+                        s => Val::scalar(s),
+                    };
+                    Arc::make_mut(&mut env.vars).insert(var.clone(), item);
+                    if let Flow::Return = exec(f, body, env)? {
+                        return Ok(Flow::Return);
+                    }
+                }
+            }
+            Stmt::While { cond, body } => {
+                while eval_cond(f, cond, env)? {
+                    if let Flow::Return = exec(f, body, env)? {
+                        return Ok(Flow::Return);
+                    }
+                }
+            }
+            Stmt::Repeat { body, until } => loop {
+                if let Flow::Return = exec(f, body, env)? {
+                    return Ok(Flow::Return);
+                }
+                if let Some(until) = until
+                    && eval_cond(f, until, env)?
+                {
+                    break;
+                }
+            },
+            Stmt::Return => return Ok(Flow::Return),
+        }
+    }
+    Ok(Flow::Next)
+}
+
+fn eval_line(f: &Tradfn, line: &Line, env: &mut Env) -> Result<Val, (ErrorKind, String)> {
+    let lexerdef = lexerdef();
+    let lexer = lexerdef.lexer(&line.text);
+    eval(&lexer, line.expr.clone(), env).map_err(|(span, kind, msg)| {
+        let at = lexer.span_str(span);
+        (kind, format!("{}[{}] '{at}', {msg}", f.name, line.number))
+    })
+}
+
+fn eval_cond(f: &Tradfn, line: &Line, env: &mut Env) -> Result<bool, (ErrorKind, String)> {
+    let val = eval_line(f, line, env)?;
+    match val.data.as_slice() {
+        [Scalar::Integer(0)] => Ok(false),
+        [Scalar::Integer(1)] => Ok(true),
+        [Scalar::Float(b)] if *b == 0.0 || *b == 1.0 => Ok(*b == 1.0),
+        _ => Err((
+            ErrorKind::Domain,
+            format!(
+                "{}[{}] condition must be a single 0 or 1",
+                f.name, line.number
+            ),
+        )),
+    }
+}
+
+type Lines<'a, 'b> = std::slice::Iter<'a, (usize, &'b str)>;
+
+/// A parsed block and the numbered control line that ended it, if any.
+type Block = (Vec<Stmt>, Option<(usize, Control)>);
+
+/// Parse statements up to a control word that ends the block (`:Else`,
+/// `:EndIf`, `:Until`, ...) or the end of the body, returning the block and
+/// the line that ended it.
+fn parse_block(lines: &mut Lines, env: &Env) -> Result<Block, EvalError> {
+    let mut stmts = Vec::new();
+    while let Some(&(number, text)) = lines.next() {
+        let stmt = match classify(number, text)? {
+            Control::Statement if scan_lines(text).0.trim().is_empty() => continue,
+            Control::Statement => Stmt::Expr(parse_line(number, text, env)?),
+            Control::If(cond) => parse_if(number, &cond, lines, env)?,
+            Control::For(var, values) => {
+                let values = parse_line(number, &values, env)?;
+                let body = parse_body(lines, number, &["endfor", "end"], env)?;
+                Stmt::For { var, values, body }
+            }
+            Control::While(cond) => {
+                let cond = parse_line(number, &cond, env)?;
+                let body = parse_body(lines, number, &["endwhile", "end"], env)?;
+                Stmt::While { cond, body }
+            }
+            Control::Repeat => match parse_block(lines, env)? {
+                (body, Some((_, Control::Until(cond)))) => Stmt::Repeat {
+                    body,
+                    until: Some(parse_line(number, &cond, env)?),
+                },
+                (body, Some((_, Control::End(word)))) if word == "endrepeat" || word == "end" => {
+                    Stmt::Repeat { body, until: None }
+                }
+                _ => return Err(error(number, ":Repeat without :Until or :EndRepeat")),
+            },
+            Control::Return => Stmt::Return,
+            end => return Ok((stmts, Some((number, end)))),
+        };
+        stmts.push(stmt);
+    }
+    Ok((stmts, None))
+}
+
+/// A block that must be closed by one of the `ends` control words.
+fn parse_body(
+    lines: &mut Lines,
+    start: usize,
+    ends: &[&str],
+    env: &Env,
+) -> Result<Vec<Stmt>, EvalError> {
+    match parse_block(lines, env)? {
+        (body, Some((_, Control::End(word)))) if ends.contains(&word.as_str()) => Ok(body),
+        (_, Some((number, _))) => Err(error(number, "Unexpected control word")),
+        (_, None) => Err(error(start, &format!("Missing :{}", ends[0]))),
+    }
+}
+
+fn parse_if(number: usize, cond: &str, lines: &mut Lines, env: &Env) -> Result<Stmt, EvalError> {
+    let mut branches = Vec::new();
+    let mut cond = parse_line(number, cond, env)?;
+    loop {
+        match parse_block(lines, env)? {
+            (body, Some((next, Control::ElseIf(next_cond)))) => {
+                branches.push((cond, body));
+                cond = parse_line(next, &next_cond, env)?;
+            }
+            (body, Some((_, Control::Else))) => {
+                branches.push((cond, body));
+                let otherwise = parse_body(lines, number, &["endif", "end"], env)?;
+                return Ok(Stmt::If {
+                    branches,
+                    otherwise,
+                });
+            }
+            (body, Some((_, Control::End(word)))) if word == "endif" || word == "end" => {
+                branches.push((cond, body));
+                return Ok(Stmt::If {
+                    branches,
+                    otherwise: Vec::new(),
+                });
+            }
+            (_, Some((next, _))) => return Err(error(next, "Unexpected control word")),
+            (_, None) => return Err(error(number, "Missing :EndIf")),
+        }
+    }
+}
+
+fn classify(number: usize, text: &str) -> Result<Control, EvalError> {
+    let Some(rest) = text.trim().strip_prefix(':') else {
+        return Ok(Control::Statement);
+    };
+    let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let (word, arg) = rest.split_at(word_end);
+    let word = word.to_lowercase();
+    // Pad an argument at `offset` in `arg` back to its position in the line
+    let arg_start = text.len() - text.trim_start().len() + 1 + word_end;
+    let padded = |offset: usize, name: &str| {
+        let tail = &arg[offset..];
+        match tail.trim().is_empty() {
+            true => Err(error(number, &format!(":{name} needs an argument"))),
+            false => Ok(format!("{}{tail}", " ".repeat(arg_start + offset))),
+        }
+    };
+    Ok(match word.as_str() {
+        "if" => Control::If(padded(0, "If")?),
+        "elseif" => Control::ElseIf(padded(0, "ElseIf")?),
+        "else" => Control::Else,
+        "endif" | "endfor" | "endwhile" | "endrepeat" | "end" => Control::End(word),
+        "for" => {
+            let at = arg
+                .match_indices(':')
+                .map(|(at, _)| at)
+                .find(|&at| {
+                    arg[at..]
+                        .get(..3)
+                        .is_some_and(|w| w.eq_ignore_ascii_case(":in"))
+                })
+                .ok_or_else(|| error(number, ":For without :In"))?;
+            let var = arg[..at].trim();
+            if !is_name(var) {
+                return Err(error(number, ":For needs a variable name"));
+            }
+            Control::For(var.to_string(), padded(at + 3, "In")?)
+        }
+        "while" => Control::While(padded(0, "While")?),
+        "repeat" => Control::Repeat,
+        "until" => Control::Until(padded(0, "Until")?),
+        "return" => Control::Return,
+        _ => return Err(error(number, &format!("Unknown control word :{word}"))),
+    })
+}
+
+fn parse_line(number: usize, text: &str, env: &Env) -> Result<Line, EvalError> {
+    let (text, expr) = parse_statement(text, env).map_err(|err| EvalError {
+        line: number + 1,
+        ..err
+    })?;
+    Ok(Line { number, text, expr })
+}
+
+/// An error at `line` of the definition (0 is the header).
+fn error(line: usize, message: &str) -> EvalError {
+    EvalError {
+        kind: EvalErrorKind::Parse,
+        line: line + 1,
+        column: 1,
+        message: message.to_string(),
+    }
+}
+
+fn unbrace(name: &str) -> String {
+    name.trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .to_string()
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -2,12 +2,15 @@ use conv::ConvUtil;
 use eyre::Result;
 use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedNeg, CheckedSub};
 
+use super::namespace::Namespace;
+
 #[derive(Debug, Clone)]
 pub enum Scalar {
     Float(f64),
     Integer(i64),
     Char(char),
     Nested(Box<Val>),
+    Namespace(Namespace),
 }
 
 impl Scalar {
@@ -22,6 +25,21 @@ impl Scalar {
     }
 }
 
+impl Scalar {
+    /// The prototype of this item: zero for numbers, a blank for chars,
+    /// and the same structure filled with prototypes for nested values.
+    pub fn prototype(&self) -> Scalar {
+        match self {
+            Scalar::Char(_) => Scalar::Char(' '),
+            Scalar::Nested(v) => Scalar::Nested(Box::new(Val::new(
+                v.shape.clone(),
+                v.data.iter().map(Scalar::prototype).collect(),
+            ))),
+            _ => Scalar::Integer(0),
+        }
+    }
+}
+
 impl TryFrom<Scalar> for usize {
     type Error = &'static str;
 
@@ -40,6 +58,7 @@ impl TryFrom<Scalar> for usize {
             }
             Scalar::Char(_) => Err("Cannot convert char to usize"),
             Scalar::Nested(_) => Err("Cannot convert nested value to usize"),
+            Scalar::Namespace(_) => Err("Cannot convert namespace to usize"),
         }
     }
 }
@@ -50,7 +69,7 @@ impl From<Scalar> for f64 {
             Scalar::Integer(val) => val as f64,
             Scalar::Float(val) => val,
             Scalar::Char(c) => c as u32 as f64,
-            Scalar::Nested(_) => 0.0,
+            Scalar::Nested(_) | Scalar::Namespace(_) => 0.0,
         }
     }
 }
@@ -63,6 +82,7 @@ impl PartialEq for Scalar {
             (Scalar::Integer(i), Scalar::Float(f)) => *i as f64 == *f,
             (Scalar::Float(f), Scalar::Integer(i)) => *f == *i as f64,
             (Scalar::Char(a), Scalar::Char(b)) => a == b,
+            (Scalar::Namespace(a), Scalar::Namespace(b)) => a.same(b),
             _ => false,
         }
     }
@@ -117,6 +137,20 @@ impl Ord for Scalar {
     }
 }
 
+impl Scalar {
+    /// Compare in the total array ordering: numbers before characters, and
+    /// an enclosed array by its contents, after a simple scalar it equals.
+    pub fn tao_cmp(&self, other: &Scalar) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        match (self, other) {
+            (Scalar::Nested(a), Scalar::Nested(b)) => a.tao_cmp(b),
+            (Scalar::Nested(a), b) => a.tao_cmp(&Val::scalar(b.clone())).then(Ordering::Greater),
+            (a, Scalar::Nested(b)) => Val::scalar(a.clone()).tao_cmp(b).then(Ordering::Less),
+            (a, b) => a.cmp(b),
+        }
+    }
+}
+
 impl std::ops::Add for Scalar {
     type Output = Self;
 
@@ -228,7 +262,7 @@ impl CheckedPow for Scalar {
             Scalar::Integer(i) => i.checked_pow(other as u32).map(Scalar::Integer),
             Scalar::Float(f) => Some(Scalar::Float(num_traits::pow::pow(*f, other))),
             Scalar::Char(_) => None,
-            Scalar::Nested(_) => None,
+            Scalar::Nested(_) | Scalar::Namespace(_) => None,
         }
     }
 
@@ -237,7 +271,7 @@ impl CheckedPow for Scalar {
             Scalar::Integer(i) => Some(Scalar::Float((*i as f64).powf(other))),
             Scalar::Float(f) => Some(Scalar::Float(f.powf(other))),
             Scalar::Char(_) => None,
-            Scalar::Nested(_) => None,
+            Scalar::Nested(_) | Scalar::Namespace(_) => None,
         }
     }
 }
@@ -249,7 +283,8 @@ pub trait Log: Sized {
 impl Log for Scalar {
     fn log(&self, base: &Self) -> Option<Self> {
         match (self, base) {
-            (Scalar::Nested(_), _) | (_, Scalar::Nested(_)) => None,
+            (Scalar::Nested(_) | Scalar::Namespace(_), _)
+            | (_, Scalar::Nested(_) | Scalar::Namespace(_)) => None,
             _ => Some(Scalar::Float(
                 f64::from(self.clone()).log(f64::from(base.clone())),
             )),
@@ -263,7 +298,7 @@ impl CheckedNeg for Scalar {
             Scalar::Integer(i) => i.checked_neg().map(Scalar::Integer),
             Scalar::Float(f) => Some(Scalar::Float(-f)),
             Scalar::Char(_) => None,
-            Scalar::Nested(_) => None,
+            Scalar::Nested(_) | Scalar::Namespace(_) => None,
         }
     }
 }
@@ -294,10 +329,35 @@ impl Val {
         Val { shape, data }
     }
 
+    /// The fill element for padding this array: the prototype of its
+    /// first item, or zero if it is empty.
+    pub fn fill(&self) -> Scalar {
+        self.data
+            .first()
+            .map_or(Scalar::Integer(0), Scalar::prototype)
+    }
+
     pub fn is_scalar(&self) -> bool {
         self.shape.is_empty()
     }
 
+    /// Compare in the total array ordering: item by item in ravel order,
+    /// then the shorter, the lower rank and the smaller shape first.
+    pub fn tao_cmp(&self, other: &Val) -> std::cmp::Ordering {
+        self.data
+            .iter()
+            .zip(&other.data)
+            .map(|(a, b)| a.tao_cmp(b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| {
+                self.data
+                    .len()
+                    .cmp(&other.data.len())
+                    .then(self.shape.len().cmp(&other.shape.len()))
+                    .then_with(|| self.shape.cmp(&other.shape))
+            })
+    }
+
     pub fn depth(&self) -> usize {
         if self.is_scalar() {
             match &self.data[0] {
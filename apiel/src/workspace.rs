@@ -0,0 +1,295 @@
+//! Saving and loading an [`Env`] as a versioned text workspace file.
+//!
+//! The format is line based. The first line is the header
+//! `apiel workspace <version>`, followed by one entry per definition:
+//!
+//! ```text
+//! apiel workspace 2
+//! var x [ 2 3 ; i1 i2 i3 i4 i5 i6 ]
+//! var s [ 2 ; c104 c105 ]
+//! var n [ 2 ; [ ; f2.5 ] [ 1 ; c97 ] ]
+//! var cfg [ ; n1 ]
+//! fn double 1
+//! double←{⍵×2}
+//! ns 1
+//! var rate [ ; f0.05 ]
+//! end
+//! ```
+//!
+//! A variable is its shape followed by typed elements: `i` integers, `f`
+//! floats, `c` characters as code points, bracketed nested values, and `n`
+//! references to namespaces. A function is stored as the source of its
+//! definition, preceded by its line count, and is re-parsed on load.
+//!
+//! Each namespace referenced is written once, as the entries between
+//! `ns <id>` and `end`, so values sharing a namespace still share it after
+//! loading. Version 1 files, which have no namespaces, still load.
+
+use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+
+use crate::Env;
+use crate::parse::eval::StoredFn;
+use crate::parse::namespace::Namespace;
+use crate::parse::val::{Scalar, Val};
+use crate::parse::{define_fn, eval_to_val};
+
+const HEADER: &str = "apiel workspace";
+const VERSION: u32 = 2;
+
+/// Names brought in by [`load_workspace`], and functions whose source failed
+/// to parse, with the error.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub loaded: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Serialize every variable and named function in `env`, sorted by name.
+pub fn save_workspace(env: &Env) -> String {
+    let mut out = format!("{HEADER} {VERSION}\n");
+    let mut spaces = Spaces::default();
+    write_entries(&env.vars, &env.fns, &mut out, &mut spaces);
+
+    // Writing a namespace can reference further namespaces
+    let mut next = 0;
+    while let Some(ns) = spaces.order.get(next).cloned() {
+        next += 1;
+        out.push_str(&format!("ns {next}\n"));
+        let members = ns.lock();
+        write_entries(&members.vars, &members.fns, &mut out, &mut spaces);
+        out.push_str("end\n");
+    }
+    out
+}
+
+/// Namespaces met while saving, numbered from 1 in order of appearance.
+#[derive(Default)]
+struct Spaces {
+    ids: HashMap<usize, usize>,
+    order: Vec<Namespace>,
+}
+
+impl Spaces {
+    fn id(&mut self, ns: &Namespace) -> usize {
+        *self.ids.entry(ns.id()).or_insert_with(|| {
+            self.order.push(ns.clone());
+            self.order.len()
+        })
+    }
+}
+
+fn write_entries(
+    vars: &HashMap<String, Val>,
+    fns: &HashMap<String, StoredFn>,
+    out: &mut String,
+    spaces: &mut Spaces,
+) {
+    let mut vars: Vec<_> = vars.iter().collect();
+    vars.sort_unstable_by_key(|(name, _)| *name);
+    for (name, val) in vars {
+        let mut text = String::new();
+        write_val(val, &mut text, spaces);
+        out.push_str(&format!("var {name} {text}\n"));
+    }
+
+    // Rust functions can't be saved; the host registers them again
+    let mut fns: Vec<_> = fns
+        .iter()
+        .filter(|(_, f)| !matches!(f, StoredFn::Native(_)))
+        .collect();
+    fns.sort_unstable_by_key(|(name, _)| *name);
+    for (name, f) in fns {
+        let lines = f.source().lines().count();
+        out.push_str(&format!("fn {name} {lines}\n{}\n", f.source()));
+    }
+}
+
+/// Read a workspace into `env`, merging with what is already there. With
+/// `only`, just those names are copied.
+///
+/// Malformed files are an error and leave `env` unchanged. Functions that
+/// fail to parse are reported in [`LoadReport::failed`] and skipped.
+pub fn load_workspace(
+    text: &str,
+    env: &mut Env,
+    only: Option<&[&str]>,
+) -> Result<LoadReport, String> {
+    let wanted = |name: &str| only.is_none_or(|names| names.contains(&name));
+    let mut lines = text.lines().enumerate();
+
+    let header = lines.next().map(|(_, line)| line).unwrap_or_default();
+    let version = header
+        .strip_prefix(HEADER)
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .ok_or("not an apiel workspace file")?;
+    if version > VERSION {
+        return Err(format!("unsupported workspace version {version}"));
+    }
+
+    // Entries are collected first, so a malformed file changes nothing.
+    // Those inside an `ns` block belong to that namespace.
+    let mut vars = Vec::new();
+    let mut fns = Vec::new();
+    let mut spaces: HashMap<usize, Namespace> = HashMap::new();
+    let mut block: Option<Namespace> = None;
+    while let Some((index, line)) = lines.next() {
+        let bad_line = || format!("malformed workspace at line {}", index + 1);
+        let mut words = line.splitn(3, ' ');
+        match (words.next(), words.next(), words.next()) {
+            (Some("var"), Some(name), Some(rest)) => {
+                let mut tokens = rest.split_whitespace().peekable();
+                let val = read_val(&mut tokens, &mut spaces).ok_or_else(bad_line)?;
+                if tokens.next().is_some() {
+                    return Err(bad_line());
+                }
+                vars.push((block.clone(), name.to_string(), val));
+            }
+            (Some("fn"), Some(name), Some(count)) => {
+                let count: usize = count.parse().map_err(|_| bad_line())?;
+                let source: Vec<&str> = lines.by_ref().take(count).map(|(_, l)| l).collect();
+                if source.len() != count {
+                    return Err(bad_line());
+                }
+                fns.push((block.clone(), name.to_string(), source.join("\n")));
+            }
+            (Some("ns"), Some(id), None) if block.is_none() => {
+                let id = id.parse().map_err(|_| bad_line())?;
+                block = Some(spaces.entry(id).or_default().clone());
+            }
+            (Some("end"), None, None) if block.is_some() => block = None,
+            (Some(""), None, None) => {}
+            _ => return Err(bad_line()),
+        }
+    }
+    if block.is_some() {
+        return Err("malformed workspace: missing end of namespace".to_string());
+    }
+
+    let mut report = LoadReport::default();
+    for (block, name, val) in vars {
+        match block {
+            Some(ns) => {
+                ns.lock().vars.insert(name, val);
+            }
+            None if wanted(&name) => match env.set_var(&name, val) {
+                Ok(()) => report.loaded.push(name),
+                Err((_, err)) => report.failed.push((name, err.to_string())),
+            },
+            None => {}
+        }
+    }
+    let (members, fns): (Vec<_>, Vec<_>) = fns.into_iter().partition(|(block, ..)| block.is_some());
+    for (ns, name, source) in members
+        .into_iter()
+        .filter_map(|(ns, n, s)| Some((ns?, n, s)))
+    {
+        match define_fn(&source, env) {
+            Ok(f) => {
+                ns.lock().fns.insert(name, f);
+            }
+            Err(err) => report.failed.push((name, err.to_string())),
+        }
+    }
+    // A function can use operators and functions saved after it, so those
+    // that fail are retried for as long as others still load
+    let mut pending: Vec<_> = fns
+        .into_iter()
+        .filter(|(_, name, _)| wanted(name))
+        .map(|(_, name, source)| (name, source))
+        .collect();
+    loop {
+        let before = report.loaded.len();
+        let mut failed = Vec::new();
+        for (name, source) in std::mem::take(&mut pending) {
+            let mut scratch = env.clone();
+            scratch.remove_fn(&name);
+            match eval_to_val(&source, &mut scratch) {
+                Ok(_) => match scratch.remove_fn(&name).map(|f| env.set_fn(&name, f)) {
+                    Some(Ok(())) => report.loaded.push(name),
+                    Some(Err((_, err))) => failed.push((name, source, err.to_string())),
+                    None => failed.push((name, source, "source does not define it".to_string())),
+                },
+                Err(err) => failed.push((name, source, err)),
+            }
+        }
+        if failed.is_empty() || report.loaded.len() == before {
+            report
+                .failed
+                .extend(failed.into_iter().map(|(name, _, err)| (name, err)));
+            break;
+        }
+        pending = failed
+            .into_iter()
+            .map(|(name, source, _)| (name, source))
+            .collect();
+    }
+    Ok(report)
+}
+
+/// Names in `only` that the report does not mention, i.e. were not in the file.
+pub fn missing_names<'a>(report: &LoadReport, only: &[&'a str]) -> Vec<&'a str> {
+    let found: HashSet<&str> = report
+        .loaded
+        .iter()
+        .chain(report.failed.iter().map(|(name, _)| name))
+        .map(String::as_str)
+        .collect();
+    only.iter()
+        .copied()
+        .filter(|name| !found.contains(name))
+        .collect()
+}
+
+fn write_val(val: &Val, out: &mut String, spaces: &mut Spaces) {
+    out.push('[');
+    for dim in &val.shape {
+        out.push_str(&format!(" {dim}"));
+    }
+    out.push_str(" ;");
+    for scalar in &val.data {
+        out.push(' ');
+        match scalar {
+            Scalar::Integer(i) => out.push_str(&format!("i{i}")),
+            Scalar::Float(f) => out.push_str(&format!("f{f}")),
+            Scalar::Char(c) => out.push_str(&format!("c{}", *c as u32)),
+            Scalar::Nested(v) => write_val(v, out, spaces),
+            Scalar::Namespace(ns) => out.push_str(&format!("n{}", spaces.id(ns))),
+        }
+    }
+    out.push_str(" ]");
+}
+
+fn read_val(
+    tokens: &mut Peekable<SplitWhitespace>,
+    spaces: &mut HashMap<usize, Namespace>,
+) -> Option<Val> {
+    if tokens.next()? != "[" {
+        return None;
+    }
+    let mut shape = Vec::new();
+    loop {
+        match tokens.next()? {
+            ";" => break,
+            dim => shape.push(dim.parse().ok()?),
+        }
+    }
+    let mut data = Vec::new();
+    loop {
+        if *tokens.peek()? == "[" {
+            data.push(Scalar::Nested(Box::new(read_val(tokens, spaces)?)));
+            continue;
+        }
+        let scalar = match tokens.next()?.split_at_checked(1)? {
+            ("]", "") => break,
+            ("i", i) => Scalar::Integer(i.parse().ok()?),
+            ("f", f) => Scalar::Float(f.parse().ok()?),
+            ("c", c) => Scalar::Char(char::from_u32(c.parse().ok()?)?),
+            ("n", id) => Scalar::Namespace(spaces.entry(id.parse().ok()?).or_default().clone()),
+            _ => return None,
+        };
+        data.push(scalar);
+    }
+    (shape.iter().product::<usize>() == data.len()).then(|| Val::new(shape, data))
+}
@@ -1,6 +1,6 @@
 //! Integration tests verified against Dyalog APL 19.0.
 
-use apiel::parse::{eval_to_val, format_val};
+use apiel::parse::{eval_to_val, format_val, is_complete, run_script};
 use apiel::{Env, apl};
 
 fn assert_apl(expr: &str, expected: &[f64], desc: &str) {
@@ -19,6 +19,7 @@ fn assert_apl(expr: &str, expected: &[f64], desc: &str) {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn reference_tests() {
     let e = std::f64::consts::E;
     let pi = std::f64::consts::PI;
@@ -841,6 +842,20 @@ fn variables_and_assignment() {
         &[0.0],
         "train with two named fns: (double 5)-(double 5) = 0",
     );
+
+    // From Rust
+    let val = env.get_var("b").unwrap();
+    env.set_var("d", val).unwrap();
+    assert_apl_env("d", &mut env, &[1.0, 2.0, 3.0], "set from Rust");
+    assert!(env.remove_var("d").is_some());
+    assert!(!env.vars().contains_key("d"));
+    let f = env.remove_fn("double").unwrap();
+    assert!(eval_to_val("double 5", &mut env).is_err());
+    env.set_fn("twice", f).unwrap();
+    assert_apl_env("twice 5", &mut env, &[10.0], "function set from Rust");
+    assert!(env.fns().contains_key("twice"));
+    let one = eval_to_val("1", &mut Env::new()).unwrap();
+    assert!(env.set_var("nope.x", one).is_err(), "no such namespace");
 }
 
 #[test]
@@ -954,7 +969,7 @@ fn nested_arrays() {
     assert_eq!(format_val(&val), "(1 2 3) (1 2 3 4) (1 2 3 4 5)");
 
     let val = eval_to_val("⌽¨ (⊂ 1 2 3) , (⊂ 4 5) , (⊂ 6)", &mut env).unwrap();
-    assert_eq!(format_val(&val), "(3 2 1) (5 4) (6)");
+    assert_eq!(format_val(&val), "(3 2 1) (5 4) 6");
 }
 
 #[test]
@@ -1056,3 +1071,1338 @@ fn indexed_assignment() {
         "index assign multiple",
     );
 }
+
+#[test]
+fn env_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Env>();
+
+    let mut env = Env::new();
+    eval_to_val("double←{⍵×2}", &mut env).unwrap();
+    eval_to_val("base←10", &mut env).unwrap();
+
+    let shared = std::sync::Arc::new(env);
+    let handles: Vec<_> = (1..=4)
+        .map(|i| {
+            let shared = std::sync::Arc::clone(&shared);
+            std::thread::spawn(move || {
+                let mut env = (*shared).clone();
+                let val = eval_to_val(&format!("base + double {i}"), &mut env).unwrap();
+                format_val(&val)
+            })
+        })
+        .collect();
+    let results: Vec<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(results, vec!["12", "14", "16", "18"]);
+
+    // Each clone has its own ⎕EN
+    let mut first = (*shared).clone();
+    let mut second = (*shared).clone();
+    assert!(eval_to_val("1 2+1 2 3", &mut first).is_err());
+    assert_eq!(first.last_error().0, 5);
+    assert_eq!(second.last_error().0, 0);
+    assert_eq!(shared.last_error().0, 0);
+    let val = eval_to_val("⎕EN", &mut second).unwrap();
+    assert_eq!(format_val(&val), "0");
+}
+
+#[test]
+fn seeded_random() {
+    // Same seed, same results
+    assert_eq!(apl!("10 ? 100", seed: 42), apl!("10 ? 100", seed: 42));
+    assert_eq!(apl!("? 6 6 6 6", seed: 7), apl!("? 6 6 6 6", seed: 7));
+
+    // Deal picks distinct values from ⍳n
+    let mut dealt = apl!("20 ? 20", seed: 1).unwrap();
+    dealt.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(dealt, (1..=20).map(f64::from).collect::<Vec<_>>());
+    assert!(apl!("5 ? 3").is_err(), "deal more than available");
+
+    // Roll stays within ⍳n, ?0 is a float in (0,1)
+    let rolls = apl!("? 100 ⍴ 6", seed: 3).unwrap();
+    assert!(
+        rolls
+            .iter()
+            .all(|&r| (1.0..=6.0).contains(&r) && r.fract() == 0.0)
+    );
+    let floats = apl!("? 0 0 0", seed: 3).unwrap();
+    assert!(floats.iter().all(|&f| f > 0.0 && f < 1.0));
+
+    // ⎕RL reads and reseeds; dfn calls advance the shared stream
+    let mut env = Env::with_seed(5);
+    assert_apl_env("⎕RL", &mut env, &[5.0], "⎕RL reads seed");
+    let first = apl!("{? 1000} 0", &mut env).unwrap();
+    let second = apl!("{? 1000} 0", &mut env).unwrap();
+    assert_apl_env("⎕RL←5", &mut env, &[5.0], "⎕RL reseeds");
+    assert_eq!(apl!("{? 1000} 0", &mut env).unwrap(), first);
+    assert_eq!(apl!("{? 1000} 0", &mut env).unwrap(), second);
+
+    env.set_seed(9);
+    assert_eq!(env.seed(), 9);
+    assert!(apl!("⎕RL←¯1", &mut env).is_err());
+}
+
+#[test]
+fn glyph_keyboard() {
+    use apiel::glyphs::{GLYPHS, glyph_for_key};
+
+    assert_eq!(glyph_for_key('r'), Some('⍴'));
+    assert_eq!(glyph_for_key('i'), Some('⍳'));
+    assert_eq!(glyph_for_key('`'), Some('⋄'));
+    assert_eq!(glyph_for_key(';'), Some('⍎'));
+    assert_eq!(glyph_for_key('\''), Some('⍕'));
+    assert_eq!(glyph_for_key('}'), Some('⍬'));
+    assert_eq!(glyph_for_key('{'), Some('⍞'));
+    assert_eq!(glyph_for_key('!'), Some('⌶'));
+    assert_eq!(glyph_for_key('@'), Some('⍫'));
+    assert_eq!(glyph_for_key('>'), Some('⍙'));
+    assert_eq!(glyph_for_key('?'), Some('⍠'));
+    assert_eq!(glyph_for_key('f'), None);
+
+    for (i, g) in GLYPHS.iter().enumerate() {
+        assert!(
+            GLYPHS[i + 1..]
+                .iter()
+                .all(|h| h.key != g.key && h.glyph != g.glyph && h.name != g.name),
+            "duplicate entry for {}",
+            g.glyph
+        );
+    }
+}
+
+#[test]
+fn completion() {
+    use apiel::complete::complete;
+
+    let mut env = Env::new();
+    eval_to_val("total←10", &mut env).unwrap();
+    eval_to_val("tally_up←{≢⍵}", &mut env).unwrap();
+
+    let replacements = |line: &str| {
+        let (start, candidates) = complete(&env, line, line.len());
+        let words: Vec<String> = candidates.into_iter().map(|c| c.replacement).collect();
+        (start, words)
+    };
+    assert_eq!(replacements("1+to"), (2, vec!["total".to_string()]));
+    assert_eq!(
+        replacements("ta"),
+        (
+            0,
+            vec!["tally_up".to_string(), "≢".to_string(), "⍪".to_string()]
+        )
+    );
+    assert_eq!(replacements("rho"), (0, vec!["⍴".to_string()]));
+    assert_eq!(replacements("⍴ io"), (4, vec!["⍳".to_string()]));
+    assert_eq!(replacements("1 2").1, Vec::<String>::new());
+}
+
+#[test]
+fn system_commands() {
+    use apiel::commands::{CommandResult, is_command, run_command};
+
+    let output = |text: &str| Ok(CommandResult::Output(text.to_string()));
+    let mut env = Env::new();
+    env.set_args(["arg"]);
+    eval_to_val("b←2", &mut env).unwrap();
+    eval_to_val("a←1", &mut env).unwrap();
+    eval_to_val("f←{⍵}", &mut env).unwrap();
+
+    assert!(is_command(" )vars"));
+    assert!(!is_command("(1 2)"));
+    assert_eq!(run_command(")vars", &mut env), output("a b"));
+    assert_eq!(run_command(")FNS", &mut env), output("f"));
+    assert_eq!(
+        run_command(")erase a f zz", &mut env),
+        output("not found: zz")
+    );
+    assert_eq!(run_command(")vars", &mut env), output("b"));
+    assert_eq!(run_command(")fns", &mut env), output(""));
+
+    assert_eq!(run_command(")clear", &mut env), output("clear ws"));
+    assert!(env.vars().is_empty());
+    assert_eq!(env.args(), ["arg"]);
+
+    assert_eq!(run_command(")off", &mut env), Ok(CommandResult::Off));
+    assert!(run_command(")vars x", &mut env).is_err());
+    assert!(run_command(")bogus", &mut env).is_err());
+}
+
+#[test]
+fn workspace_save_and_load() {
+    use apiel::commands::{CommandResult, run_command};
+    use apiel::workspace::{load_workspace, save_workspace};
+
+    let mut env = Env::new();
+    eval_to_val("m←2 3⍴⍳6", &mut env).unwrap();
+    eval_to_val("s←'hi'", &mut env).unwrap();
+    eval_to_val("n←(⊂ 1 2 3) , (⊂ 'ab') , 2.5", &mut env).unwrap();
+    eval_to_val("double←{⍵×2}", &mut env).unwrap();
+    let text = save_workspace(&env);
+    assert!(text.starts_with("apiel workspace 2\n"));
+
+    let mut loaded = Env::new();
+    let report = load_workspace(&text, &mut loaded, None).unwrap();
+    assert!(report.failed.is_empty());
+    for name in ["m", "s", "n"] {
+        assert_eq!(
+            loaded.vars()[name].shape,
+            env.vars()[name].shape,
+            "{name} shape"
+        );
+        assert_eq!(
+            format_val(&loaded.vars()[name]),
+            format_val(&env.vars()[name]),
+            "{name} data"
+        );
+    }
+    assert_apl_env(
+        "double m",
+        &mut loaded,
+        &[2.0, 4.0, 6.0, 8.0, 10.0, 12.0],
+        "loaded fn",
+    );
+    assert_eq!(save_workspace(&loaded), text, "save is stable");
+
+    // Functions that fail to re-parse are reported, the rest still load
+    let broken = "apiel workspace 1\nvar x [ ; i5 ]\nfn bad 1\nbad←{⍵+\nfn good 1\ngood←{⍵}\n";
+    let mut env = Env::new();
+    let report = load_workspace(broken, &mut env, None).unwrap();
+    assert_eq!(report.loaded, ["x", "good"]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, "bad");
+
+    // Selected names only
+    let mut env = Env::new();
+    let report = load_workspace(&text, &mut env, Some(&["s", "double"])).unwrap();
+    assert_eq!(report.loaded, ["s", "double"]);
+    assert!(!env.vars().contains_key("m"));
+
+    assert!(load_workspace("apiel workspace 99\n", &mut env, None).is_err());
+    assert!(load_workspace("hello", &mut env, None).is_err());
+    assert!(load_workspace("apiel workspace 1\nvar x [ 2 ; i1 ]\n", &mut env, None).is_err());
+
+    // Through the system commands
+    let path = std::env::temp_dir().join(format!("apiel-ws-{}.apws", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut env = Env::new();
+    eval_to_val("a←1 2 3", &mut env).unwrap();
+    eval_to_val("b←4", &mut env).unwrap();
+    run_command(&format!(")save {path}"), &mut env).unwrap();
+    eval_to_val("a←0", &mut env).unwrap();
+    eval_to_val("c←9", &mut env).unwrap();
+    run_command(&format!(")copy {path} a", path = path), &mut env).unwrap();
+    assert_apl_env(
+        "a+c",
+        &mut env,
+        &[10.0, 11.0, 12.0],
+        ")copy merges selected names",
+    );
+    run_command(&format!(")load {path}"), &mut env).unwrap();
+    assert!(
+        !env.vars().contains_key("c"),
+        ")load replaces the workspace"
+    );
+    assert_eq!(
+        run_command(&format!(")copy {path} zz"), &mut env),
+        Ok(CommandResult::Output(format!(
+            "copied {path}\nnot found: zz"
+        )))
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn multi_line_dfns() {
+    assert!(!is_complete("f←{"));
+    assert!(!is_complete("f←{\n  ⍵×2"));
+    assert!(is_complete("f←{\n  ⍵×2\n}"));
+    assert!(!is_complete("(1 2"));
+    assert!(!is_complete("'abc"));
+    assert!(is_complete("'{(' ⍝ a comment with { and '"));
+
+    // Newlines separate statements like ⋄; blank and comment lines are ignored
+    let mut env = Env::new();
+    eval_to_val(
+        "sign←{ ⍝ signum\n    ⍵<0: ¯1\n\n    ⍵=0: 0\n    1\n}",
+        &mut env,
+    )
+    .unwrap();
+    assert_apl_env("sign ¯4", &mut env, &[-1.0], "multi-line guards");
+    assert_apl_env("sign 0", &mut env, &[0.0], "multi-line guards");
+    assert_apl_env("sign 9", &mut env, &[1.0], "multi-line guards");
+    assert_apl_env(
+        "{\n  a←⍵+1\n  a×2\n} 3",
+        &mut env,
+        &[8.0],
+        "inline multi-line dfn",
+    );
+    assert_apl_env("{⍵\n+1} 2", &mut env, &[1.0], "each line is a statement");
+    assert_apl_env(
+        "(1 2\n 3)",
+        &mut env,
+        &[1.0, 2.0, 3.0],
+        "newline inside parens",
+    );
+
+    let mut env = Env::new();
+    let mut results = Vec::new();
+    let script = "stats←{\n  n←≢⍵\n  (+/⍵)÷n\n}\nstats 1 2 3 4\n{\n  ⍵ + nope\n} 1\n";
+    let err = run_script(script, &mut env, |val| results.push(format_val(val))).unwrap_err();
+    assert_eq!(results, ["2.5"]);
+    assert_eq!(
+        (err.line, err.column),
+        (7, 7),
+        "error position inside a multi-line dfn"
+    );
+
+    let err = run_script("1\ng←{\n  ⍵\n", &mut env, |_| {}).unwrap_err();
+    assert_eq!(err.line, 2, "unterminated dfn reported where it starts");
+}
+
+#[test]
+fn tradfns() {
+    let mut env = Env::new();
+    let mut results = Vec::new();
+    let script = "\
+∇ r←a Max b
+  :If a>b
+      r←a
+  :Else
+      r←b
+  :EndIf
+∇
+3 Max 5
+∇ r←Sum v;x
+  r←0
+  :For x :In v
+      r←r+x
+  :EndFor
+∇
+Sum 1 2 3 4
+∇ r←Steps n
+  r←0
+  :While n>1
+      n←n÷2
+      r←r+1
+  :EndWhile
+∇
+Steps 64
+";
+    run_script(script, &mut env, |val| results.push(format_val(val))).unwrap();
+    assert_eq!(results, ["5", "10", "6"]);
+    assert!(!env.vars().contains_key("r"), "header names are localized");
+    assert!(
+        !env.vars().contains_key("x"),
+        "declared locals are localized"
+    );
+
+    // Dynamic scoping: a called tradfn sees its caller's locals, and
+    // unlocalized assignments reach the caller
+    eval_to_val("∇ r←Outer;secret\n  secret←7\n  r←Inner\n∇", &mut env).unwrap();
+    eval_to_val("∇ r←Inner\n  seen←secret\n  r←secret×2\n∇", &mut env).unwrap();
+    assert_apl_env("Outer", &mut env, &[14.0], "callee sees caller locals");
+    assert_apl_env("seen", &mut env, &[7.0], "global assignment persists");
+    assert!(!env.vars().contains_key("secret"));
+
+    // :Repeat with :Until and :Return
+    eval_to_val(
+        "∇ r←FirstOver n;i\n  i←0\n  :Repeat\n    i←i+1\n    :If n<i×i\n      r←i\n      :Return\n    :EndIf\n  :Until i≥100\n  r←¯1\n∇",
+        &mut env,
+    )
+    .unwrap();
+    assert_apl_env("FirstOver 50", &mut env, &[8.0], ":Return leaves early");
+
+    // Tradfns and dfns share Env::fns and can call each other
+    eval_to_val("double←{⍵×2}", &mut env).unwrap();
+    eval_to_val("∇ r←Quad x\n  r←2×double x\n∇", &mut env).unwrap();
+    assert_apl_env("{Quad ⍵} 3", &mut env, &[12.0], "dfn calls tradfn");
+
+    // Tradfns round-trip through workspace files
+    let text = apiel::workspace::save_workspace(&env);
+    let mut loaded = Env::new();
+    let report = apiel::workspace::load_workspace(&text, &mut loaded, None).unwrap();
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    assert_apl_env("Quad 3", &mut loaded, &[12.0], "loaded tradfn");
+
+    // Errors name the function and line
+    eval_to_val("∇ r←Bad x\n  r←x\n  r←r+nope\n∇", &mut env).unwrap();
+    let err = eval_to_val("Bad 1", &mut env).unwrap_err();
+    assert!(err.contains("Bad[2]"), "{err}");
+    assert!(eval_to_val("1 Bad 2", &mut env).is_err(), "not dyadic");
+
+    let err = run_script("1\n∇ r←F x\n  :If x\n    r←1\n∇\n", &mut env, |_| {}).unwrap_err();
+    assert_eq!(err.line, 3, "missing :EndIf reported at the :If");
+    let err = run_script("∇ r←F x\n  :Bogus\n∇\n", &mut env, |_| {}).unwrap_err();
+    assert_eq!(err.line, 2);
+
+    // Control words that need an argument
+    let bodies = [
+        ":If\n  r←1\n  :EndIf",
+        ":If x\n  :ElseIf\n  :EndIf",
+        ":While\n  :EndWhile",
+        ":Repeat\n  :Until",
+    ];
+    for body in bodies {
+        let script = format!("∇ r←F x\n  {body}\n∇\n");
+        let err = run_script(&script, &mut env, |_| {}).unwrap_err();
+        assert!(err.message.contains("needs an argument"), "{body}: {err:?}");
+    }
+    let err = run_script("∇ r←F x\n  :For i :In\n  :EndFor\n∇\n", &mut env, |_| {});
+    assert!(err.is_err(), ":For without an array");
+
+    // :In is found in the argument as written, in any case
+    eval_to_val(
+        "∇ r←Sum v\n  r←0\n  :FOR x :IN v\n    r+←x\n  :ENDFOR\n∇",
+        &mut env,
+    )
+    .unwrap();
+    assert_apl_env("Sum 1 2 3", &mut env, &[6.0], "upper case :For");
+    let err = run_script(
+        "∇ r←F x\n  :For İ :In 1 2\n  :EndFor\n∇\n",
+        &mut env,
+        |_| {},
+    );
+    assert!(err.is_err(), "not a name");
+}
+
+#[test]
+fn error_guards() {
+    let mut env = Env::new();
+    // The formatted result, or the error message
+    let mut show = |expr: &str| match eval_to_val(expr, &mut env) {
+        Ok(val) => format_val(&val),
+        Err(err) => err,
+    };
+
+    // 0 catches everything; other codes catch only their error class
+    show("safe←{0::'caught' ⋄ 1 2+1 2 3}");
+    assert_eq!(show("safe 0"), "caught");
+    assert_eq!(show("⎕EN"), "5");
+    show("at←{3::¯1 ⋄ (1 2 3)[⍵]}");
+    assert_eq!(show("at 2"), "2");
+    assert_eq!(show("at 7"), "-1");
+    show("len←{5 11::'guarded' ⋄ (1 2 3)[⍵]}");
+    assert!(
+        show("len 7").contains("Index out of bounds"),
+        "INDEX ERROR not listed"
+    );
+    assert_eq!(show("⎕EN"), "3", "uncaught errors also set ⎕EN");
+
+    // Guards cover later statements, including functions they call
+    show("inner←{'my error' ⎕SIGNAL 500}");
+    show("outer←{⍵\n  500::⊃⎕DM\n  inner ⍵\n}");
+    assert_eq!(show("outer 1"), "my error");
+    assert_eq!(show("⎕EN"), "500");
+    show("dom←{11::⊃⎕DM ⋄ ⎕SIGNAL 11}");
+    assert_eq!(show("dom 0"), "DOMAIN ERROR");
+    show("val←{6::⎕EN ⋄ ⍵+nope}");
+    assert_eq!(show("val 1"), "6");
+
+    assert!(show("⎕SIGNAL 0").contains("1 to 999"));
+
+    // The class of an error is its own, not that of an earlier signal
+    show("q←{0::⎕EN ⋄ 'e' ⎕SIGNAL 7}");
+    assert_eq!(show("q 1"), "7");
+    assert_eq!(show("{0::⎕EN ⋄ (⍳3)[9]} 0"), "3");
+    show("'shape' ⎕SIGNAL 500");
+    assert_eq!(show("{0::⎕EN ⋄ 1 2+1 2 3} 0"), "5");
+    show("m←2 2⍴⍳4");
+    assert_eq!(show("{0::⎕EN ⋄ m[1]} 0"), "4", "RANK ERROR");
+
+    // )reset keeps the seed and the last error
+    env.set_seed(42);
+    let last = env.last_error();
+    apiel::commands::run_command(")reset", &mut env).unwrap();
+    assert_eq!(env.last_error(), last);
+    assert_eq!(env.seed(), 42);
+    env.clear_error();
+    assert_eq!(env.last_error(), (0, vec![]));
+}
+
+#[test]
+fn namespaces() {
+    let mut env = Env::new();
+    let mut show = |expr: &str| match eval_to_val(expr, &mut env) {
+        Ok(val) => format_val(&val),
+        Err(err) => err,
+    };
+
+    // Dotted access and assignment
+    show("cfg←⎕NS ''");
+    show("cfg.rate←0.05");
+    assert_eq!(show("cfg.rate×100"), "5");
+    show("cfg.rate+←1");
+    assert_eq!(show("cfg.rate"), "1.05");
+    show("cfg.xs←1 2 3");
+    show("cfg.xs[2]←20");
+    assert_eq!(show("cfg.xs"), "1 20 3");
+
+    // Functions in a namespace see its other members
+    show("cfg.double←{⍵×2}");
+    show("cfg.scale←{⍵×rate}");
+    assert_eq!(show("cfg.double 21"), "42");
+    assert_eq!(show("cfg.scale 100"), "105");
+    assert_eq!(show("3 {⍺+cfg.double ⍵} 4"), "11");
+
+    // Namespaces are references, and can be stored in arrays
+    show("alias←cfg");
+    show("alias.rate←2");
+    assert_eq!(show("cfg.rate"), "2");
+    assert_eq!(show("cfg≡alias"), "1");
+    assert_eq!(show("≢cfg,alias,⎕NS ''"), "3");
+    show("cfg.inner←⎕NS ''");
+    show("cfg.inner.x←5");
+    assert_eq!(show("cfg.inner.x+1"), "6");
+
+    // ⎕NL lists names by class; a negative class gives a nested vector
+    assert_eq!(show("cfg.⎕NL ¯2"), "(rate) (xs)");
+    assert_eq!(show("cfg.⎕NL ¯3"), "(double) (scale)");
+    assert_eq!(show("cfg.⎕NL ¯9"), "(inner)");
+    assert_eq!(show("⍴cfg.⎕NL 2 9"), "3 5");
+    assert_eq!(show("⎕NL ¯9"), "(alias) (cfg)");
+
+    // ⎕NS copies named objects; 'name' ⎕NS creates or extends a namespace
+    show("'app' ⎕NS 'cfg'");
+    assert_eq!(show("app.cfg≡cfg"), "1");
+    show("copy←⎕NS 'cfg.double' 'cfg.rate'");
+    assert_eq!(show("copy.double copy.rate"), "4");
+    assert!(show("nope.x").contains("Undefined variable"));
+    assert!(show("cfg.rate.x←1").contains("Not a namespace"));
+
+    // Modified assignment in a namespace's function changes its member,
+    // while plain assignment makes a local
+    show("cfg.count←0");
+    show("cfg.bump←{count+←⍵ ⋄ count}");
+    show("cfg.bump 1");
+    assert_eq!(show("cfg.bump 2"), "3");
+    assert_eq!(show("cfg.count"), "3");
+    show("cfg.set←{rate←⍵ ⋄ rate}");
+    assert_eq!(show("cfg.set 9"), "9");
+    assert_eq!(show("cfg.rate"), "2");
+
+    // Namespaces from Rust
+    let host = env.create_namespace("host").unwrap();
+    let answer = eval_to_val("42", &mut Env::new()).unwrap();
+    host.lock().vars.insert("answer".to_string(), answer);
+    let result = eval_to_val("host.answer÷2", &mut env).unwrap();
+    assert_eq!(format_val(&result), "21");
+
+    // Saving keeps shared and self references
+    eval_to_val("cfg.self←cfg", &mut env).unwrap();
+    let text = apiel::workspace::save_workspace(&env);
+    let mut loaded = Env::new();
+    let report = apiel::workspace::load_workspace(&text, &mut loaded, None).unwrap();
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    let mut show = |expr: &str| format_val(&eval_to_val(expr, &mut loaded).unwrap());
+    assert_eq!(show("cfg≡alias"), "1");
+    assert_eq!(show("cfg.self.self≡app.cfg"), "1");
+    assert_eq!(show("cfg.double cfg.inner.x"), "10");
+    assert_eq!(show("cfg.scale 10"), "20");
+}
+
+#[test]
+fn default_left_argument() {
+    let mut env = Env::new();
+    eval_to_val("inc←{⍺←1 ⋄ ⍺+⍵}", &mut env).unwrap();
+    assert_apl_env("inc 5", &mut env, &[6.0], "monadic uses the default");
+    assert_apl_env("10 inc 5", &mut env, &[15.0], "dyadic ignores it");
+    assert_apl_env("2 {⍺←⍵ ⋄ ⍺×⍵} 3", &mut env, &[6.0], "anonymous dfn");
+    assert_apl_env("{⍺←⍵ ⋄ ⍺×⍵} 3", &mut env, &[9.0], "default from ⍵");
+
+    // A monadic call from inside a dyadic dfn does not see the caller's ⍺
+    eval_to_val("outer←{inc ⍵}", &mut env).unwrap();
+    assert_apl_env("7 outer 1", &mut env, &[2.0], "⍺ is not inherited");
+    assert_apl_env("7 {{⍺←1 ⋄ ⍺+⍵} ⍵} 1", &mut env, &[2.0], "nor inline dfns");
+
+    // A default needs a dfn to give it to
+    let err = eval_to_val("⍺←5", &mut env).unwrap_err();
+    assert!(err.contains("outside of a dfn"), "{err}");
+    assert!(!env.vars().contains_key("⍺"));
+
+    // Testing the valence
+    eval_to_val("valence←{900⌶⍬: 1 ⋄ 2}", &mut env).unwrap();
+    assert_apl_env("valence 0", &mut env, &[1.0], "900⌶ monadic");
+    assert_apl_env("0 valence 0", &mut env, &[2.0], "900⌶ dyadic");
+    eval_to_val("nc←{⎕NC'⍺'}", &mut env).unwrap();
+    assert_apl_env("nc 0", &mut env, &[0.0], "⎕NC'⍺' unbound");
+    assert_apl_env("0 nc 0", &mut env, &[2.0], "⎕NC'⍺' bound");
+
+    eval_to_val("x←3", &mut env).unwrap();
+    assert_apl_env("⎕NC 'x' 'inc' 'nope'", &mut env, &[2.0, 3.0, 0.0], "⎕NC");
+    assert_apl_env("⍴⍬", &mut env, &[0.0], "zilde");
+    assert!(eval_to_val("1⌶⍬", &mut env).is_err());
+}
+
+#[test]
+fn user_defined_operators() {
+    let mut env = Env::new();
+    eval_to_val("twice←{⍺⍺ ⍺⍺ ⍵}", &mut env).unwrap();
+    eval_to_val("dbl←{⍵×2}", &mut env).unwrap();
+    assert_apl_env("(+/)twice 2 3⍴⍳6", &mut env, &[21.0], "derived operand");
+    assert_apl_env("{⍵×2}twice 3", &mut env, &[12.0], "dfn operand");
+    assert_apl_env("dbl twice 5", &mut env, &[20.0], "named operand");
+    assert_apl_env("⌽twice 1 2 3", &mut env, &[1.0, 2.0, 3.0], "primitive");
+    assert_apl_env("dbl twice twice 1", &mut env, &[16.0], "chained");
+
+    // Left argument and right operand
+    eval_to_val("twice2←{⍺ ⍺⍺ ⍺ ⍺⍺ ⍵}", &mut env).unwrap();
+    assert_apl_env("10 + twice2 1", &mut env, &[21.0], "dyadic derived fn");
+    eval_to_val("then←{⍵⍵ ⍺⍺ ⍵}", &mut env).unwrap();
+    assert_apl_env("+/ then dbl ⍳4", &mut env, &[20.0], "dyadic operator");
+    assert_apl_env(
+        "(dbl twice) twice 1",
+        &mut env,
+        &[16.0],
+        "derived as operand",
+    );
+
+    assert_apl_env("⎕NC 'twice' 'dbl'", &mut env, &[4.0, 3.0], "operator class");
+    assert!(eval_to_val("twice 3", &mut env).is_err());
+    assert!(eval_to_val("{⍺⍺ ⍵} 3", &mut env).is_err());
+
+    // A dfn defined inside a dfn keeps its own source text
+    assert_apl_env("{g←{⍵+10} ⋄ g ⍵} 5", &mut env, &[15.0], "nested definition");
+
+    // Only operands at the dfn's own level make it a dop
+    eval_to_val("g←{s←'⍺⍺' ⋄ ⍵+1}", &mut env).unwrap();
+    assert_apl_env("g 3", &mut env, &[4.0], "⍺⍺ in a string");
+    eval_to_val("g←{op←{⍺⍺ ⍵} ⋄ ⍵+1}", &mut env).unwrap();
+    assert_apl_env("g 3", &mut env, &[4.0], "⍺⍺ in a nested dop");
+    assert_apl_env("⎕NC 'g'", &mut env, &[3.0], "still a function");
+}
+
+#[test]
+fn tacit_functions() {
+    let mut env = Env::new();
+    eval_to_val("sum←+/", &mut env).unwrap();
+    eval_to_val("avg←+/÷≢", &mut env).unwrap();
+    eval_to_val("f←⍳", &mut env).unwrap();
+    assert_apl_env("sum 1 2 3", &mut env, &[6.0], "derived function");
+    assert_apl_env("avg 1 2 3 4", &mut env, &[2.5], "fork");
+    assert_apl_env("f 3", &mut env, &[1.0, 2.0, 3.0], "primitive");
+    assert_apl_env("2 3 4 f 3", &mut env, &[2.0], "dyadic primitive");
+
+    eval_to_val("dbl←{⍵×2}", &mut env).unwrap();
+    eval_to_val("g←dbl ⌽", &mut env).unwrap();
+    assert_apl_env("g 1 2 3", &mut env, &[6.0, 4.0, 2.0], "atop");
+    eval_to_val("h←- , ÷", &mut env).unwrap();
+    assert_apl_env("2 h 4", &mut env, &[-2.0, 0.5], "dyadic fork");
+    eval_to_val("k←⌽ ⍳ , ⍳", &mut env).unwrap();
+    assert_apl_env("k 2", &mut env, &[2.0, 1.0, 2.0, 1.0], "four-train");
+    eval_to_val("sq←×⍨", &mut env).unwrap();
+    assert_apl_env("sq 5", &mut env, &[25.0], "commute");
+    eval_to_val("ip←+.×", &mut env).unwrap();
+    assert_apl_env("1 2 ip 3 4", &mut env, &[11.0], "inner product");
+
+    // Named tacit functions are accepted wherever a function is
+    eval_to_val("twice←{⍺⍺ ⍺⍺ ⍵}", &mut env).unwrap();
+    assert_apl_env("avg twice 16", &mut env, &[16.0], "operand");
+    assert_apl_env("(avg sum) 1 2 3", &mut env, &[6.0], "in a train");
+    assert_apl_env("⎕NC 'avg'", &mut env, &[3.0], "function class");
+
+    // Values are still assigned as values
+    eval_to_val("x←3", &mut env).unwrap();
+    eval_to_val("y←x", &mut env).unwrap();
+    eval_to_val("r←avg 1 2 3", &mut env).unwrap();
+    assert_apl_env("y+r", &mut env, &[5.0], "value assignment");
+
+    // They survive a workspace round trip, even when saved before the
+    // functions they use
+    eval_to_val("post←{⍵+100}", &mut env).unwrap();
+    eval_to_val("pre←post ⌽", &mut env).unwrap();
+    use apiel::workspace::{load_workspace, save_workspace};
+    let text = save_workspace(&env);
+    assert!(text.contains("fn avg 1\navg←+/÷≢\n"), "{text}");
+    let mut loaded = Env::new();
+    let report = load_workspace(&text, &mut loaded, None).unwrap();
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    assert_apl_env("avg 2 4", &mut loaded, &[3.0], "reloaded");
+    assert_apl_env(
+        "pre 1 2",
+        &mut loaded,
+        &[102.0, 101.0],
+        "loaded out of order",
+    );
+}
+
+#[test]
+fn function_trains() {
+    let mut env = Env::new();
+    eval_to_val("dbl←{⍵×2}", &mut env).unwrap();
+    eval_to_val("twice←{⍺⍺ ⍺⍺ ⍵}", &mut env).unwrap();
+    let cases: &[(&str, &[f64], &str)] = &[
+        ("(- , ⊢ , + , ×) 4", &[-4.0, 4.0, 4.0, 1.0], "five-train"),
+        ("(⌽ - , ⊢) 4", &[4.0, -4.0], "four-train"),
+        ("(≢ , (+/ ÷ ≢)) 2 4", &[2.0, 3.0], "nested train"),
+        ("(×⍨ , -) 3", &[9.0, -3.0], "with commute"),
+        ("((dbl twice) , -) 3", &[12.0, -3.0], "with a dop"),
+        ("(+/⍤1) 2 3⍴⍳6", &[6.0, 15.0], "rank"),
+        (
+            "(⌽⍤1) 2 3⍴⍳6",
+            &[3.0, 2.0, 1.0, 6.0, 5.0, 4.0],
+            "rank of primitive",
+        ),
+        ("1 2 (,⍤0) 3 4", &[1.0, 3.0, 2.0, 4.0], "dyadic rank"),
+        ("({⍵+1}⍣3) 5", &[8.0], "power"),
+        ("2 (+ , -) 3", &[5.0, -1.0], "dyadic fork"),
+        ("(+/) 1 2 3", &[6.0], "parenthesised function"),
+        ("(dbl) 4", &[8.0], "parenthesised name"),
+        ("(+/∘⍳) 4", &[10.0], "parenthesised compose"),
+        ("(1 + ⊢) 4", &[5.0], "array left tine"),
+        ("(2 × ⊢ , ⊢) 3", &[6.0, 6.0], "array tine in a longer train"),
+        (
+            "2 ('ab' , ,) 3",
+            &[97.0, 98.0, 2.0, 3.0],
+            "string left tine",
+        ),
+    ];
+    for (input, expected, desc) in cases {
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    eval_to_val("x←2", &mut env).unwrap();
+    assert_apl_env(
+        "x (+ , -) 3",
+        &mut env,
+        &[5.0, -1.0],
+        "variable left argument",
+    );
+    assert_apl_env("(x) (+ , -) 3", &mut env, &[5.0, -1.0], "after parentheses");
+
+    eval_to_val("inc←1 + ⊢", &mut env).unwrap();
+    assert_apl_env("inc 4", &mut env, &[5.0], "named fork with an array tine");
+
+    // Parentheses around values are still values
+    eval_to_val("y←3", &mut env).unwrap();
+    assert_apl_env("(y) + (⍳2)", &mut env, &[4.0, 5.0], "values");
+    assert_apl_env("(1 + 2) × 3", &mut env, &[9.0], "no function on the right");
+
+    // Errors point at the train in the original text
+    let err = apiel::parse::eval_line("1+(⍳ , ⍳) 2.5", &mut env).unwrap_err();
+    assert_eq!(err.column, 3, "{err}");
+    assert!(err.message.starts_with("'(⍳ , ⍳) 2.5'"), "{err}");
+}
+
+#[test]
+fn primitive_operators_take_any_function() {
+    let mut env = Env::new();
+    eval_to_val("sq←{⍵×⍵}", &mut env).unwrap();
+    eval_to_val("cat←{⍺,⍵}", &mut env).unwrap();
+    eval_to_val("words←'ab' 'cde' 'f'", &mut env).unwrap();
+    let cases: &[(&str, &[f64], &str)] = &[
+        ("≢¨ words", &[2.0, 3.0, 1.0], "primitive each"),
+        ("+/ ≢¨ words", &[6.0], "each result is simple"),
+        ("sq¨ 1 2 3", &[1.0, 4.0, 9.0], "named each"),
+        ("{⍺+⍵}\\ 1 2 3 4", &[1.0, 3.0, 6.0, 10.0], "dfn scan"),
+        ("sq\\ 1 2 3", &[1.0, 4.0, 81.0], "named scan"),
+        ("{⍺-⍵}/ 2 3⍴⍳6", &[2.0, 5.0], "reduce last axis"),
+        ("{⍺+⍵}⌿ 2 3⍴⍳6", &[5.0, 7.0, 9.0], "reduce first axis"),
+        (
+            "{⍺-⍵}⍀ 2 3⍴⍳6",
+            &[1.0, 2.0, 3.0, -3.0, -3.0, -3.0],
+            "scan first axis",
+        ),
+        (
+            "⊃∪/ (⊂1 2 3),(⊂2 3 4)",
+            &[1.0, 2.0, 3.0, 4.0],
+            "union reduction",
+        ),
+        ("3 ⍴⍨ 2", &[3.0, 3.0], "commute"),
+        ("{⍵/⍨⍵>0} 1 ¯2 3", &[1.0, 3.0], "replicate commute"),
+        (
+            "(2 2⍴1 2 3 4) +.{⍺×⍵} 2 2⍴5 6 7 8",
+            &[19.0, 22.0, 43.0, 50.0],
+            "dfn inner product",
+        ),
+        (
+            "(2 2⍴1 2 3 4) {⍺+⍵}.× 2 2⍴5 6 7 8",
+            &[19.0, 22.0, 43.0, 50.0],
+            "dfn inner product left",
+        ),
+        ("(≢¨ , ⍴) words", &[2.0, 3.0, 1.0, 3.0], "in a train"),
+    ];
+    for (input, expected, desc) in cases {
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    let val = eval_to_val("1 2 3 ⍴¨ 4 5 6", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(4) (5 5) (6 6 6)");
+    let val = eval_to_val("⊃,/ words", &mut env).unwrap();
+    assert_eq!(format_val(&val), "abcdef");
+    let val = eval_to_val("{⍵/⍨⍵>0}¨ (⊂1 ¯2 3),(⊂¯1 4)", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(1 3) (4)");
+    let val = eval_to_val("1 2 ∘.cat 3 4 5", &mut env).unwrap();
+    assert_eq!(val.shape, vec![2, 3]);
+    assert_eq!(format_val(&val), "(1 3) (1 4) (1 5) (2 3) (2 4) (2 5)");
+
+    // Derived functions can be named
+    eval_to_val("lens←≢¨", &mut env).unwrap();
+    assert_apl_env("lens words", &mut env, &[2.0, 3.0, 1.0], "named derived");
+
+    assert!(eval_to_val("1 2 {⍺+⍵}/ 3", &mut env).is_err());
+    assert!(eval_to_val("{⍺+⍵}/ ⍬", &mut env).is_err());
+}
+
+#[test]
+fn operands_on_large_arguments() {
+    let mut env = Env::new();
+    eval_to_val("sq←{⍵×⍵}", &mut env).unwrap();
+    assert_apl_env("{⍺+⍵}/ ⍳10000", &mut env, &[50005000.0], "dfn reduce");
+    assert_apl_env("+/ sq¨ ⍳1000", &mut env, &[333833500.0], "named each");
+    assert_apl_env("⊃⌽ {⍺+⍵}\\ ⍳300", &mut env, &[45150.0], "dfn scan");
+}
+
+#[test]
+fn registered_rust_functions() {
+    use apiel::parse::val::{Scalar, Val};
+    let mut env = Env::new();
+    // ⍵'s sum, scaled by ⍺
+    env.register_fn("total", |alpha, omega| {
+        let sum: f64 = omega.data.into_iter().map(f64::from).sum();
+        let scale = alpha.map_or(1.0, |a| f64::from(a.data[0].clone()));
+        Ok(Val::scalar(Scalar::Float(sum * scale)))
+    });
+    env.register_fn("fail", |_, _| Err("no".to_string()));
+    let cases: &[(&str, &[f64], &str)] = &[
+        ("total 1 2 3", &[6.0], "monadic"),
+        ("2 total 1 2 3", &[12.0], "dyadic"),
+        ("total¨ (⊂1 2),(⊂3 4 5)", &[3.0, 12.0], "each"),
+        ("total/ 2 3 4", &[24.0], "reduce"),
+        ("(total , ≢) 4 5", &[9.0, 2.0], "in a train"),
+        ("{1+total ⍵} 1 2", &[4.0], "called from a dfn"),
+    ];
+    for (input, expected, desc) in cases {
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+    let err = eval_to_val("fail 1", &mut env).unwrap_err();
+    assert!(err.contains("no"), "{err}");
+    assert_apl_env("{11::¯1 ⋄ fail ⍵} 1", &mut env, &[-1.0], "domain error");
+
+    // They aren't saved with the workspace
+    let text = apiel::workspace::save_workspace(&env);
+    assert!(!text.contains("total"), "{text}");
+}
+
+#[test]
+fn compose_over_at_and_key() {
+    let mut env = Env::new();
+    eval_to_val("h←+/∘⍳", &mut env).unwrap();
+    eval_to_val("g←{⍵×2}∘{⍵+1}", &mut env).unwrap();
+    eval_to_val("c←2∘+", &mut env).unwrap();
+    eval_to_val("o←{⍺+⍵}⍥{⍵×⍵}", &mut env).unwrap();
+    eval_to_val("a←{⍵×10}@2 4", &mut env).unwrap();
+    eval_to_val("k←{≢⍵}⌸", &mut env).unwrap();
+    eval_to_val("neg←-∘⌽", &mut env).unwrap();
+    let cases: &[(&str, &[f64], &str)] = &[
+        ("h 4", &[10.0], "compose with a derived function"),
+        ("g 3", &[8.0], "compose dfns"),
+        ("c 3", &[5.0], "bound left argument"),
+        ("3 o 4", &[25.0], "over"),
+        ("a 1 2 3 4", &[1.0, 20.0, 3.0, 40.0], "at"),
+        ("k 1 1 2 3 3 3", &[2.0, 1.0, 3.0], "key"),
+        ("neg 1 2 3", &[-3.0, -2.0, -1.0], "compose primitives"),
+        ("({⍵×2}∘{⍵+1}) 3", &[8.0], "parenthesised compose"),
+        ("(2∘+) 3", &[5.0], "parenthesised bound function"),
+        ("2∘+ 3", &[5.0], "bound function applied"),
+        ("2 ×∘- 3", &[-6.0], "dyadic compose"),
+        ("(c , g) 1", &[3.0, 4.0], "in a train"),
+        ("+/¨ 2∘⍴¨ 5 6", &[10.0, 12.0], "bound function with each"),
+    ];
+    for (input, expected, desc) in cases {
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+    assert!(eval_to_val("1 c 2", &mut env).is_err());
+}
+
+#[test]
+fn multi_axis_take_and_drop() {
+    let mut env = Env::new();
+    eval_to_val("m←3 4⍴⍳12", &mut env).unwrap();
+    let cases: &[(&str, &[usize], &[f64], &str)] = &[
+        ("2 3↑m", &[2, 3], &[1.0, 2.0, 3.0, 5.0, 6.0, 7.0], "take"),
+        (
+            "¯2 ¯3↑m",
+            &[2, 3],
+            &[6.0, 7.0, 8.0, 10.0, 11.0, 12.0],
+            "take from the end",
+        ),
+        ("1↑m", &[1, 4], &[1.0, 2.0, 3.0, 4.0], "leading axis only"),
+        (
+            "4 ¯5↑2 2⍴⍳4",
+            &[4, 5],
+            &[
+                0.0, 0.0, 0.0, 1.0, 2.0, 0.0, 0.0, 0.0, 3.0, 4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+                0.0, 0.0, 0.0, 0.0,
+            ],
+            "overtake",
+        ),
+        ("1 1↓m", &[2, 3], &[6.0, 7.0, 8.0, 10.0, 11.0, 12.0], "drop"),
+        (
+            "¯1 ¯2↓m",
+            &[2, 2],
+            &[1.0, 2.0, 5.0, 6.0],
+            "drop from the end",
+        ),
+        (
+            "1↓m",
+            &[2, 4],
+            &[5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0],
+            "drop rows",
+        ),
+        ("5↓m", &[0, 4], &[], "drop everything"),
+        (
+            "2 2↑7",
+            &[2, 2],
+            &[7.0, 0.0, 0.0, 0.0],
+            "take from a scalar",
+        ),
+    ];
+    for (input, shape, expected, desc) in cases {
+        let val = eval_to_val(input, &mut env).unwrap();
+        assert_eq!(&val.shape, shape, "{desc}");
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    // Fills follow the prototype of the argument
+    let val = eval_to_val("5↑'abc'", &mut env).unwrap();
+    assert_eq!(format_val(&val), "abc  ");
+    let val = eval_to_val("¯3↑(⊂1 2),(⊂'ab')", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(0 0) (1 2) (ab)");
+
+    assert!(eval_to_val("1 2 3↑1 2", &mut env).is_err());
+    assert!(eval_to_val("1.5↓1 2", &mut env).is_err());
+}
+
+#[test]
+fn multi_axis_indexing() {
+    let mut env = Env::new();
+    eval_to_val("m←3 4⍴⍳12", &mut env).unwrap();
+    eval_to_val("c←2 3 4⍴⍳24", &mut env).unwrap();
+    eval_to_val("v←10 20 30", &mut env).unwrap();
+    let cases: &[(&str, &[usize], &[f64], &str)] = &[
+        ("m[2;]", &[4], &[5.0, 6.0, 7.0, 8.0], "row"),
+        (
+            "m[;1 3]",
+            &[3, 2],
+            &[1.0, 3.0, 5.0, 7.0, 9.0, 11.0],
+            "columns",
+        ),
+        ("m[2;3]", &[], &[7.0], "element"),
+        ("m[2 3;2 3]", &[2, 2], &[6.0, 7.0, 10.0, 11.0], "submatrix"),
+        (
+            "m[,2;]",
+            &[1, 4],
+            &[5.0, 6.0, 7.0, 8.0],
+            "vector index keeps axis",
+        ),
+        ("c[1;;2]", &[3], &[2.0, 6.0, 10.0], "elided middle axis"),
+        ("v[2]", &[], &[20.0], "scalar index"),
+        (
+            "v[2 2⍴1 2 3 1]",
+            &[2, 2],
+            &[10.0, 20.0, 30.0, 10.0],
+            "index shape",
+        ),
+        ("v[]", &[3], &[10.0, 20.0, 30.0], "elided index"),
+    ];
+    for (input, shape, expected, desc) in cases {
+        let val = eval_to_val(input, &mut env).unwrap();
+        assert_eq!(&val.shape, shape, "{desc}");
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    eval_to_val("m[2;]←0", &mut env).unwrap();
+    eval_to_val("m[;1]←100 200 300", &mut env).unwrap();
+    eval_to_val("m[1 3;2 4]←2 2⍴7 8 9 10", &mut env).unwrap();
+    assert_apl_env(
+        "m",
+        &mut env,
+        &[
+            100.0, 7.0, 3.0, 8.0, 200.0, 0.0, 0.0, 0.0, 300.0, 9.0, 11.0, 10.0,
+        ],
+        "assign rows, columns and submatrices",
+    );
+
+    assert!(eval_to_val("m[5]", &mut env).is_err());
+    assert!(eval_to_val("v[4]", &mut env).is_err());
+    assert!(eval_to_val("m[1;2]←1 2", &mut env).is_err());
+}
+
+#[test]
+fn axis_specification() {
+    let mut env = Env::new();
+    eval_to_val("m←3 4⍴⍳12", &mut env).unwrap();
+    eval_to_val("sum←{⍺+⍵}", &mut env).unwrap();
+    let cases: &[(&str, &[usize], &[f64], &str)] = &[
+        (
+            "⌽m",
+            &[3, 4],
+            &[
+                4.0, 3.0, 2.0, 1.0, 8.0, 7.0, 6.0, 5.0, 12.0, 11.0, 10.0, 9.0,
+            ],
+            "reverse rows",
+        ),
+        (
+            "⌽[1]m",
+            &[3, 4],
+            &[
+                9.0, 10.0, 11.0, 12.0, 5.0, 6.0, 7.0, 8.0, 1.0, 2.0, 3.0, 4.0,
+            ],
+            "reverse first axis",
+        ),
+        (
+            "1⌽[1]m",
+            &[3, 4],
+            &[
+                5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 1.0, 2.0, 3.0, 4.0,
+            ],
+            "rotate first axis",
+        ),
+        (
+            "0 1 2⌽m",
+            &[3, 4],
+            &[
+                1.0, 2.0, 3.0, 4.0, 6.0, 7.0, 8.0, 5.0, 11.0, 12.0, 9.0, 10.0,
+            ],
+            "rotate each row",
+        ),
+        (
+            "m,[1]100",
+            &[4, 4],
+            &[
+                1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 100.0, 100.0, 100.0,
+                100.0,
+            ],
+            "catenate first axis",
+        ),
+        (
+            "m,0",
+            &[3, 5],
+            &[
+                1.0, 2.0, 3.0, 4.0, 0.0, 5.0, 6.0, 7.0, 8.0, 0.0, 9.0, 10.0, 11.0, 12.0, 0.0,
+            ],
+            "catenate last axis",
+        ),
+        (
+            "1 2 3,[0.5]4 5 6",
+            &[2, 3],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            "laminate before",
+        ),
+        (
+            "1 2 3,[1.5]4 5 6",
+            &[3, 2],
+            &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0],
+            "laminate after",
+        ),
+        (
+            ",[1 2]m",
+            &[12],
+            &[
+                1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0,
+            ],
+            "ravel axes",
+        ),
+        (
+            "+/[1]m",
+            &[4],
+            &[15.0, 18.0, 21.0, 24.0],
+            "reduce first axis",
+        ),
+        ("+⌿[2]m", &[3], &[10.0, 26.0, 42.0], "reduce last axis"),
+        (
+            "+\\[1]m",
+            &[3, 4],
+            &[
+                1.0, 2.0, 3.0, 4.0, 6.0, 8.0, 10.0, 12.0, 15.0, 18.0, 21.0, 24.0,
+            ],
+            "scan first axis",
+        ),
+        (
+            "+\\m",
+            &[3, 4],
+            &[
+                1.0, 3.0, 6.0, 10.0, 5.0, 11.0, 18.0, 26.0, 9.0, 19.0, 30.0, 42.0,
+            ],
+            "scan rows",
+        ),
+        (
+            "{⍺+⍵}/[1]m",
+            &[4],
+            &[15.0, 18.0, 21.0, 24.0],
+            "dfn reduce first axis",
+        ),
+        (
+            "{⍺+⍵}⌿[2]m",
+            &[3],
+            &[10.0, 26.0, 42.0],
+            "dfn reduce last axis",
+        ),
+        ("sum/[2]m", &[3], &[10.0, 26.0, 42.0], "named reduce"),
+        (
+            "sum\\[1]m",
+            &[3, 4],
+            &[
+                1.0, 2.0, 3.0, 4.0, 6.0, 8.0, 10.0, 12.0, 15.0, 18.0, 21.0, 24.0,
+            ],
+            "named scan first axis",
+        ),
+        (
+            "{⍺-⍵}⍀[2]m",
+            &[3, 4],
+            &[
+                1.0, -1.0, 2.0, -2.0, 5.0, -1.0, 6.0, -2.0, 9.0, -1.0, 10.0, -2.0,
+            ],
+            "dfn scan last axis",
+        ),
+        (
+            "2↑[2]m",
+            &[3, 2],
+            &[1.0, 2.0, 5.0, 6.0, 9.0, 10.0],
+            "take on one axis",
+        ),
+        (
+            "1↓[1]m",
+            &[2, 4],
+            &[5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0],
+            "drop on one axis",
+        ),
+        ("2 1↑[2 1]m", &[1, 2], &[1.0, 2.0], "take on named axes"),
+        (
+            "↑[1](⊂1 2 3),(⊂4 5 6)",
+            &[3, 2],
+            &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0],
+            "mix first",
+        ),
+        (
+            "↑[0.5](⊂1 2 3),(⊂4 5 6)",
+            &[3, 2],
+            &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0],
+            "mix before",
+        ),
+        ("∪2 2⍴1 2 1 2", &[1, 2], &[1.0, 2.0], "unique rows"),
+    ];
+    for (input, shape, expected, desc) in cases {
+        let val = eval_to_val(input, &mut env).unwrap();
+        assert_eq!(&val.shape, shape, "{desc}");
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    let val = eval_to_val("↓[1]m", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(1 5 9) (2 6 10) (3 7 11) (4 8 12)");
+    let val = eval_to_val("⊂[1]m", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(1 5 9) (2 6 10) (3 7 11) (4 8 12)");
+    let val = eval_to_val("1 0 1⊂[1]m", &mut env).unwrap();
+    assert_eq!(val.shape, vec![2, 4]);
+    let val = eval_to_val("1 1 0⊆[1]m", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(1 5) (2 6) (3 7) (4 8)");
+
+    // As a function value
+    eval_to_val("f←⌽[1]", &mut env).unwrap();
+    assert_apl_env(
+        "f m",
+        &mut env,
+        &[
+            9.0, 10.0, 11.0, 12.0, 5.0, 6.0, 7.0, 8.0, 1.0, 2.0, 3.0, 4.0,
+        ],
+        "named",
+    );
+    assert_apl_env("(+⌿[1] ÷ ≢) m", &mut env, &[5.0, 6.0, 7.0, 8.0], "train");
+    eval_to_val("g←{⍺×⍵}/[1]", &mut env).unwrap();
+    assert_apl_env(
+        "g m",
+        &mut env,
+        &[45.0, 120.0, 231.0, 384.0],
+        "named dfn reduce",
+    );
+
+    assert!(eval_to_val("⌽[3]m", &mut env).is_err());
+    assert!(eval_to_val("m,[2]1 2", &mut env).is_err());
+    assert!(eval_to_val("1 2+/[1]m", &mut env).is_err());
+    assert!(eval_to_val("{⍺+⍵}/[3]m", &mut env).is_err());
+    assert!(eval_to_val("1 2 sum/[1]m", &mut env).is_err());
+}
+
+#[test]
+fn table_and_catenate_first() {
+    let mut env = Env::new();
+    eval_to_val("m←2 3⍴⍳6", &mut env).unwrap();
+    let cases: &[(&str, &[usize], &[f64], &str)] = &[
+        ("⍪1 2 3", &[3, 1], &[1.0, 2.0, 3.0], "column of a vector"),
+        ("⍪5", &[1, 1], &[5.0], "table of a scalar"),
+        (
+            "⍪m",
+            &[2, 3],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            "table of a matrix",
+        ),
+        (
+            "m⍪10 20 30",
+            &[3, 3],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 10.0, 20.0, 30.0],
+            "add a row",
+        ),
+        (
+            "0⍪m",
+            &[3, 3],
+            &[0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            "scalar row",
+        ),
+        (
+            "m⍪m",
+            &[4, 3],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            "stack matrices",
+        ),
+        ("1 2⍪3", &[3], &[1.0, 2.0, 3.0], "vectors"),
+        (
+            "m,[0.5]m",
+            &[2, 2, 3],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            "laminate a new leading axis",
+        ),
+        (
+            "(⍪1 2),⍪3 4",
+            &[2, 2],
+            &[1.0, 3.0, 2.0, 4.0],
+            "columns side by side",
+        ),
+    ];
+    for (input, shape, expected, desc) in cases {
+        let val = eval_to_val(input, &mut env).unwrap();
+        assert_eq!(&val.shape, shape, "{desc}");
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    assert!(eval_to_val("m⍪1 2", &mut env).is_err());
+    assert!(eval_to_val("m⍪2 2 2⍴0", &mut env).is_err());
+}
+
+#[test]
+fn grade_major_cells() {
+    let mut env = Env::new();
+    eval_to_val("m←3 2⍴3 1 1 2 1 1", &mut env).unwrap();
+    eval_to_val("names←'bob' 'al' 'carl' 'alice'", &mut env).unwrap();
+    let cases: &[(&str, &[f64], &str)] = &[
+        ("⍋m", &[3.0, 2.0, 1.0], "rows up"),
+        ("⍒m", &[1.0, 2.0, 3.0], "rows down"),
+        ("⍒3 1 3 2", &[1.0, 3.0, 4.0, 2.0], "ties keep their order"),
+        ("⍋names", &[2.0, 4.0, 1.0, 3.0], "names"),
+        ("⍋3,'a',1", &[3.0, 1.0, 2.0], "numbers before characters"),
+        (
+            "⍋(⊂1 2),(⊂1),(⊂,1)",
+            &[2.0, 3.0, 1.0],
+            "shorter and lower rank first",
+        ),
+        (
+            "'nab'⍋'banana'",
+            &[3.0, 5.0, 2.0, 4.0, 6.0, 1.0],
+            "collation",
+        ),
+        (
+            "'nab'⍒'banana'",
+            &[1.0, 2.0, 4.0, 6.0, 3.0, 5.0],
+            "collation down",
+        ),
+        (
+            "'ab'⍋'xbax'",
+            &[3.0, 2.0, 1.0, 4.0],
+            "missing characters last",
+        ),
+        (
+            "(2 3⍴'abcABC')⍋3 3⍴'BabaBcAbc'",
+            &[2.0, 3.0, 1.0],
+            "case folded by a matrix collation",
+        ),
+    ];
+    for (input, expected, desc) in cases {
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    let val = eval_to_val("names[⍋names]", &mut env).unwrap();
+    assert_eq!(format_val(&val), "(al) (alice) (bob) (carl)");
+    assert!(eval_to_val("'ab'⍋1 2", &mut env).is_err());
+}
+
+#[test]
+fn transpose_any_rank() {
+    let mut env = Env::new();
+    eval_to_val("c←2 3 4⍴⍳24", &mut env).unwrap();
+    let cases: &[(&str, &[usize], &[f64], &str)] = &[
+        (
+            "⍉c",
+            &[4, 3, 2],
+            &[
+                1.0, 13.0, 5.0, 17.0, 9.0, 21.0, 2.0, 14.0, 6.0, 18.0, 10.0, 22.0, 3.0, 15.0, 7.0,
+                19.0, 11.0, 23.0, 4.0, 16.0, 8.0, 20.0, 12.0, 24.0,
+            ],
+            "reverse the axes",
+        ),
+        (
+            "3 1 2⍉c",
+            &[3, 4, 2],
+            &[
+                1.0, 13.0, 2.0, 14.0, 3.0, 15.0, 4.0, 16.0, 5.0, 17.0, 6.0, 18.0, 7.0, 19.0, 8.0,
+                20.0, 9.0, 21.0, 10.0, 22.0, 11.0, 23.0, 12.0, 24.0,
+            ],
+            "permute",
+        ),
+        ("1 1⍉3 3⍴⍳9", &[3], &[1.0, 5.0, 9.0], "main diagonal"),
+        ("1 1⍉2 3⍴⍳6", &[2], &[1.0, 5.0], "diagonal of a rectangle"),
+        (
+            "1 2 1⍉c",
+            &[2, 3],
+            &[1.0, 5.0, 9.0, 14.0, 18.0, 22.0],
+            "diagonal of two axes",
+        ),
+        (
+            "1 1 1⍉3 3 3⍴⍳27",
+            &[3],
+            &[1.0, 14.0, 27.0],
+            "diagonal of a cube",
+        ),
+    ];
+    for (input, shape, expected, desc) in cases {
+        let val = eval_to_val(input, &mut env).unwrap();
+        assert_eq!(&val.shape, shape, "{desc}");
+        assert_apl_env(input, &mut env, expected, desc);
+    }
+
+    assert!(eval_to_val("1 3 3⍉c", &mut env).is_err());
+    assert!(eval_to_val("1 2⍉c", &mut env).is_err());
+}
+
+#[test]
+fn least_squares_matrix_divide() {
+    let mut env = Env::new();
+    eval_to_val("X←4 2⍴1 1 1 2 1 3 1 4", &mut env).unwrap();
+    eval_to_val("y←6 5 7 10", &mut env).unwrap();
+    let close = |expr: &str, env: &mut Env, expected: &[f64], shape: &[usize]| {
+        let val = eval_to_val(expr, env).unwrap();
+        assert_eq!(val.shape, shape, "{expr}");
+        for (got, want) in val.data.iter().zip(expected) {
+            let got: f64 = got.clone().into();
+            assert!((got - want).abs() < 1e-9, "{expr}: got {got}, want {want}");
+        }
+    };
+
+    // Intercept and slope of a line fitted to y
+    close("y⌹X", &mut env, &[3.5, 1.4], &[2]);
+    close("(⍉2 4⍴y,2×y)⌹X", &mut env, &[3.5, 7.0, 1.4, 2.8], &[2, 2]);
+    // The pseudo-inverse of a tall matrix is a left inverse
+    close(
+        "⌹X",
+        &mut env,
+        &[1.0, 0.5, 0.0, -0.5, -0.3, -0.1, 0.1, 0.3],
+        &[2, 4],
+    );
+    close("(⌹X)+.×X", &mut env, &[1.0, 0.0, 0.0, 1.0], &[2, 2]);
+    // An exact system gives its exact solution
+    close("1 2 3⌹3 1⍴2 4 6", &mut env, &[0.5], &[1]);
+
+    assert!(eval_to_val("1 2 3⌹3 2⍴1 2 2 4 3 6", &mut env).is_err());
+    assert!(eval_to_val("⌹3 2⍴1 2 2 4 3 6", &mut env).is_err());
+    assert!(eval_to_val("⌹2 3⍴⍳6", &mut env).is_err());
+    assert!(eval_to_val("1 2⌹X", &mut env).is_err());
+    assert!(eval_to_val("⌹2 2⍴1 2 2 4", &mut env).is_err());
+}